@@ -0,0 +1,154 @@
+use anyhow::Context;
+use async_graphql::parser::parse_query;
+use async_graphql::parser::types::{Selection, SelectionSet};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuthConfig {
+    /// Secret used to validate HS256 tokens.
+    pub hs256_secret: Option<String>,
+    /// Local JWKS file with the public keys used to validate asymmetric tokens.
+    pub jwks_file: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Whether introspection queries can be executed without a token.
+    pub allow_anonymous_introspection: bool,
+}
+
+/// Authenticated user, available in the GraphQL context.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub subject: String,
+    pub tenant: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// Users of the same tenant share all their items.
+    tenant: Option<String>,
+    /// Space separated list of scopes, as defined by OAuth 2.0.
+    #[serde(default)]
+    scope: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthErrors {
+    #[error("Missing bearer token")]
+    MissingToken,
+
+    #[error("Invalid bearer token")]
+    InvalidToken,
+}
+
+pub struct Authenticator {
+    hs256_key: Option<DecodingKey>,
+    jwks: JwkSet,
+    issuer: Option<String>,
+    audience: Option<String>,
+    pub allow_anonymous_introspection: bool,
+}
+
+impl Authenticator {
+    pub fn load(config: &AuthConfig) -> anyhow::Result<Authenticator> {
+        let jwks = if let Some(path) = &config.jwks_file {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Unable to read JWKS file '{}'", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Invalid JWKS file '{}'", path))?
+        } else {
+            JwkSet { keys: vec![] }
+        };
+        if config.hs256_secret.is_none() && jwks.keys.is_empty() {
+            anyhow::bail!("Either a HS256 secret or a JWKS file must be configured");
+        }
+
+        info!("Authentication configured.");
+        Ok(Authenticator {
+            hs256_key: config
+                .hs256_secret
+                .as_ref()
+                .map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+            jwks,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            allow_anonymous_introspection: config.allow_anonymous_introspection,
+        })
+    }
+
+    /// Validates the value of the `Authorization` header.
+    pub fn authenticate(&self, authorization: &str) -> Result<Principal, AuthErrors> {
+        let token = authorization
+            .strip_prefix("Bearer ")
+            .ok_or(AuthErrors::MissingToken)?;
+        let header = decode_header(token).map_err(|e| {
+            warn!("Invalid token header: {}", e);
+            AuthErrors::InvalidToken
+        })?;
+
+        let key = if header.alg == Algorithm::HS256 {
+            self.hs256_key.clone()
+        } else {
+            header
+                .kid
+                .as_ref()
+                .and_then(|kid| self.jwks.find(kid))
+                // Prevents a token from choosing a different algorithm than the one of the key.
+                .filter(|jwk| jwk.common.algorithm == Some(header.alg))
+                .and_then(|jwk| DecodingKey::from_jwk(jwk).ok())
+        }
+        .ok_or_else(|| {
+            warn!("No key found for the token algorithm {:?}", header.alg);
+            AuthErrors::InvalidToken
+        })?;
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+        }
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(|e| {
+                warn!("Invalid token: {}", e);
+                AuthErrors::InvalidToken
+            })?
+            .claims;
+
+        Ok(Principal {
+            subject: claims.sub,
+            tenant: claims.tenant,
+            scopes: claims.scope.split_whitespace().map(String::from).collect(),
+        })
+    }
+}
+
+/// Whether the query only selects introspection fields, such as the ones sent by the playground.
+pub fn is_introspection_query(query: &str) -> bool {
+    parse_query(query)
+        .map(|document| {
+            document.operations.iter().all(|(_, operation)| {
+                is_introspection_selection_set(&operation.node.selection_set.node)
+            })
+        })
+        .unwrap_or(false)
+}
+
+fn is_introspection_selection_set(selection_set: &SelectionSet) -> bool {
+    selection_set
+        .items
+        .iter()
+        .all(|selection| match &selection.node {
+            Selection::Field(field) => field.node.name.node.starts_with("__"),
+            Selection::InlineFragment(fragment) => {
+                is_introspection_selection_set(&fragment.node.selection_set.node)
+            }
+            // Not needed by introspection queries, and avoids dealing with fragment cycles.
+            Selection::FragmentSpread(_) => false,
+        })
+}
@@ -1,22 +1,38 @@
 use anyhow::Result;
 use figment::{
-    providers::{Format, Toml},
+    providers::{Env, Format, Toml},
     Figment,
 };
 use serde::Deserialize;
 use tracing::info;
 
+use crate::auth::AuthConfig;
 use crate::dynamodb::config::DynamoDBConfig;
+use crate::graphql::config::GraphQLConfig;
+use crate::graphql::reminders::ReminderConfig;
+use crate::graphql::search::SearchConfig;
+use crate::graphql::webhooks::WebhookConfig;
+use crate::rate_limit::RateLimitConfig;
 use crate::serving::ServingConfig;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    pub auth: AuthConfig,
     pub dynamodb: DynamoDBConfig,
+    pub graphql: GraphQLConfig,
+    pub rate_limit: RateLimitConfig,
+    pub reminders: ReminderConfig,
+    pub search: SearchConfig,
     pub serving: ServingConfig,
+    pub webhooks: WebhookConfig,
 }
 
 pub fn load() -> Result<Config> {
-    let config = Figment::new().merge(Toml::file("App.toml")).extract()?;
-    info!("Configuration loaded from App.toml");
+    // Secrets, such as `APP_AUTH__HS256_SECRET`, are provided through the environment.
+    let config = Figment::new()
+        .merge(Toml::file("App.toml"))
+        .merge(Env::prefixed("APP_").split("__"))
+        .extract()?;
+    info!("Configuration loaded from App.toml and environment");
     Ok(config)
 }
@@ -1,7 +1,9 @@
+pub use expression::UpdateExpression;
 pub use item::{AttributesGetterExt, RawAttributes};
 pub use table::DynamoTable;
 
 pub mod config;
 pub mod errors;
+pub mod expression;
 pub mod item;
 pub mod table;
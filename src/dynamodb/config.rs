@@ -8,4 +8,18 @@ pub struct DynamoDBConfig {
     pub gsi1_name: String,
     pub gsi1_partition_key: String,
     pub gsi1_sort_key: String,
+    pub gsi2_name: String,
+    pub gsi2_partition_key: String,
+    pub gsi2_sort_key: String,
+    pub gsi3_name: String,
+    pub gsi3_partition_key: String,
+    pub gsi3_sort_key: String,
+    /// Index of the pending reminders, partitioned by hour.
+    pub gsi4_name: String,
+    pub gsi4_partition_key: String,
+    pub gsi4_sort_key: String,
+    /// Number attribute holding the epoch time at which DynamoDB purges an item.
+    pub ttl_attribute: String,
+    /// Number of days deleted items are kept in the trash.
+    pub trash_retention_days: u32,
 }
@@ -7,4 +7,10 @@ pub enum DynamoDbErrors {
 
     #[error("DynamoDB request failed")]
     RequestFailure,
+
+    #[error("DynamoDB condition check failed")]
+    ConditionalCheckFailed,
+
+    #[error("DynamoDB write conflicted with a concurrent transaction")]
+    TransactionConflict,
 }
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::client::fluent_builders::UpdateItem;
+use aws_sdk_dynamodb::model::update::Builder as UpdateBuilder;
+use aws_sdk_dynamodb::model::AttributeValue;
+
+/// Requests accepting an update expression, either standalone or within a transaction.
+pub trait UpdateRequest: Sized {
+    fn update_expression(self, expression: String) -> Self;
+    fn attribute_name(self, alias: String, name: String) -> Self;
+    fn attribute_value(self, alias: String, value: AttributeValue) -> Self;
+}
+
+impl UpdateRequest for UpdateItem {
+    fn update_expression(self, expression: String) -> Self {
+        UpdateItem::update_expression(self, expression)
+    }
+
+    fn attribute_name(self, alias: String, name: String) -> Self {
+        self.expression_attribute_names(alias, name)
+    }
+
+    fn attribute_value(self, alias: String, value: AttributeValue) -> Self {
+        self.expression_attribute_values(alias, value)
+    }
+}
+
+impl UpdateRequest for UpdateBuilder {
+    fn update_expression(self, expression: String) -> Self {
+        UpdateBuilder::update_expression(self, expression)
+    }
+
+    fn attribute_name(self, alias: String, name: String) -> Self {
+        self.expression_attribute_names(alias, name)
+    }
+
+    fn attribute_value(self, alias: String, value: AttributeValue) -> Self {
+        self.expression_attribute_values(alias, value)
+    }
+}
+
+/// Builds the update expression of an item from the attributes to change. Attribute names are
+/// always aliased, so reserved words can be used safely.
+#[derive(Debug, Default)]
+pub struct UpdateExpression {
+    set: Vec<String>,
+    remove: Vec<String>,
+    add: Vec<String>,
+    delete: Vec<String>,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl UpdateExpression {
+    pub fn set(&mut self, name: &str, value: AttributeValue) {
+        let placeholder = placeholder(name);
+        self.set
+            .push(format!("#{} = :{}", placeholder, placeholder));
+        self.names
+            .insert(format!("#{}", placeholder), name.to_string());
+        self.values.insert(format!(":{}", placeholder), value);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let placeholder = placeholder(name);
+        self.remove.push(format!("#{}", placeholder));
+        self.names
+            .insert(format!("#{}", placeholder), name.to_string());
+    }
+
+    /// Adds elements to a set, or increments a number.
+    pub fn add(&mut self, name: &str, value: AttributeValue) {
+        let placeholder = placeholder(name);
+        self.add.push(format!("#{} :{}", placeholder, placeholder));
+        self.names
+            .insert(format!("#{}", placeholder), name.to_string());
+        self.values.insert(format!(":{}", placeholder), value);
+    }
+
+    /// Removes elements from a set.
+    pub fn delete(&mut self, name: &str, value: AttributeValue) {
+        let placeholder = placeholder(name);
+        self.delete
+            .push(format!("#{} :{}", placeholder, placeholder));
+        self.names
+            .insert(format!("#{}", placeholder), name.to_string());
+        self.values.insert(format!(":{}", placeholder), value);
+    }
+
+    /// Nullable attributes are removed rather than being stored as null.
+    pub fn set_or_remove(&mut self, name: &str, value: Option<AttributeValue>) {
+        if let Some(value) = value {
+            self.set(name, value)
+        } else {
+            self.remove(name)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+            && self.remove.is_empty()
+            && self.add.is_empty()
+            && self.delete.is_empty()
+    }
+
+    pub fn apply<R: UpdateRequest>(self, mut req: R) -> R {
+        if self.is_empty() {
+            return req;
+        }
+        let mut clauses: Vec<String> = vec![];
+        if !self.set.is_empty() {
+            clauses.push(format!("SET {}", self.set.join(", ")));
+        }
+        if !self.remove.is_empty() {
+            clauses.push(format!("REMOVE {}", self.remove.join(", ")));
+        }
+        if !self.add.is_empty() {
+            clauses.push(format!("ADD {}", self.add.join(", ")));
+        }
+        if !self.delete.is_empty() {
+            clauses.push(format!("DELETE {}", self.delete.join(", ")));
+        }
+        req = req.update_expression(clauses.join(" "));
+        req = self
+            .names
+            .into_iter()
+            .fold(req, |req, (alias, name)| req.attribute_name(alias, name));
+        self.values
+            .into_iter()
+            .fold(req, |req, (alias, value)| req.attribute_value(alias, value))
+    }
+}
+
+/// Placeholders can only contain alphanumeric characters, unlike attribute names such as `GSI1-PK`.
+fn placeholder(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, SecondsFormat, Utc};
 use tracing::error;
 
 use super::errors::{DynamoDbErrors, Result};
@@ -19,6 +20,12 @@ pub trait AttributesGetterExt {
     fn get_from_string<F: FromStr>(&self, key: &str) -> Result<F>;
     fn get_string(&self, key: &str) -> Result<&String>;
     fn get_bool(&self, key: &str) -> Result<&bool>;
+    fn get_optional_from_string<F: FromStr>(&self, key: &str) -> Result<Option<F>>;
+    fn get_optional_string(&self, key: &str) -> Result<Option<&String>>;
+    fn get_optional_from_number<F: FromStr>(&self, key: &str) -> Result<Option<F>>;
+    fn get_optional_bool(&self, key: &str) -> Result<Option<&bool>>;
+    fn get_string_set(&self, key: &str) -> Result<Vec<String>>;
+    fn get_map_list(&self, key: &str) -> Result<Vec<&RawAttributes>>;
 }
 
 impl AttributesGetterExt for RawAttributes {
@@ -51,6 +58,94 @@ impl AttributesGetterExt for RawAttributes {
             DynamoDbErrors::UnexpectedDataFormat(message)
         })
     }
+
+    fn get_optional_from_string<F: FromStr>(&self, key: &str) -> Result<Option<F>> {
+        if is_missing(self, key) {
+            Ok(None)
+        } else {
+            self.get_from_string(key).map(Some)
+        }
+    }
+
+    fn get_optional_string(&self, key: &str) -> Result<Option<&String>> {
+        if is_missing(self, key) {
+            Ok(None)
+        } else {
+            self.get_string(key).map(Some)
+        }
+    }
+
+    fn get_optional_bool(&self, key: &str) -> Result<Option<&bool>> {
+        if is_missing(self, key) {
+            Ok(None)
+        } else {
+            self.get_bool(key).map(Some)
+        }
+    }
+
+    /// DynamoDB doesn't store empty sets, so a missing set is an empty one.
+    fn get_string_set(&self, key: &str) -> Result<Vec<String>> {
+        if is_missing(self, key) {
+            return Ok(vec![]);
+        }
+        let mut values = get_attr(self, key)?.as_ss().cloned().map_err(|_| {
+            let message = format!("Expected key '{}' to be a string set", key);
+            error!(message);
+            DynamoDbErrors::UnexpectedDataFormat(message)
+        })?;
+        values.sort();
+        Ok(values)
+    }
+
+    fn get_map_list(&self, key: &str) -> Result<Vec<&RawAttributes>> {
+        let message = format!("Expected key '{}' to be a list of maps", key);
+        get_attr(self, key)?
+            .as_l()
+            .ok()
+            .and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_m().ok())
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| {
+                error!(message);
+                DynamoDbErrors::UnexpectedDataFormat(message)
+            })
+    }
+
+    fn get_optional_from_number<F: FromStr>(&self, key: &str) -> Result<Option<F>> {
+        if is_missing(self, key) {
+            return Ok(None);
+        }
+        let attr_n = get_attr(self, key)?.as_n().map_err(|_| {
+            let message = format!("Expected key '{}' to be a number", key);
+            error!(message);
+            DynamoDbErrors::UnexpectedDataFormat(message)
+        })?;
+        let parsed: F = attr_n.parse().map_err(|_e| {
+            let message = format!("Could not parse '{}'", attr_n);
+            error!(message);
+            DynamoDbErrors::UnexpectedDataFormat(message)
+        })?;
+        Ok(Some(parsed))
+    }
+}
+
+/// Dates are stored in RFC 3339 with a fixed precision, so they can be compared as strings in
+/// key conditions and filters.
+pub fn datetime_attribute(value: &DateTime<Utc>) -> AttributeValue {
+    AttributeValue::S(value.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// DynamoDB expects the TTL attribute to be a number of seconds since the epoch.
+pub fn ttl_attribute(value: &DateTime<Utc>) -> AttributeValue {
+    AttributeValue::N(value.timestamp().to_string())
+}
+
+/// Optional attributes are usually removed, but could also be explicitly set to null.
+fn is_missing(map: &RawAttributes, key: &str) -> bool {
+    matches!(map.get(key), None | Some(AttributeValue::Null(_)))
 }
 
 fn get_attr<'a>(map: &'a RawAttributes, key: &str) -> Result<&'a AttributeValue> {
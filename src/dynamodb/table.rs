@@ -1,23 +1,37 @@
 use std::collections::HashMap;
+use std::convert::identity;
+use std::future::Future;
 use std::str::FromStr;
+use std::time::Duration;
 
 use aws_sdk_dynamodb::client::fluent_builders::{
     DeleteItem, GetItem, PutItem, Query, Scan, TransactWriteItems, UpdateItem,
 };
 use aws_sdk_dynamodb::model::delete::Builder as DeleteBuilder;
 use aws_sdk_dynamodb::model::put::Builder as PutBuilder;
-use aws_sdk_dynamodb::model::{Delete, Put, TransactWriteItem};
+use aws_sdk_dynamodb::model::update::Builder as UpdateBuilder;
+use aws_sdk_dynamodb::model::{Delete, Put, TransactWriteItem, Update};
 use aws_sdk_dynamodb::output::{
     DeleteItemOutput, GetItemOutput, PutItemOutput, QueryOutput, TransactWriteItemsOutput,
     UpdateItemOutput,
 };
+use aws_sdk_dynamodb::types::SdkError;
 use aws_sdk_dynamodb::{model::AttributeValue, Client};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::config::DynamoDBConfig;
 use super::errors::{DynamoDbErrors, Result};
 use super::item::{AttributesGetterExt, ItemKey, RawAttributes};
 
+/// Maximum number of items in a single transaction.
+pub const MAX_TRANSACTION_ITEMS: usize = 100;
+
+/// Attempts of a write cancelled by a concurrent transaction, such as on the item of a list whose
+/// counters are changed along with each of its todos.
+const CONFLICT_ATTEMPTS: u32 = 4;
+const CONFLICT_INITIAL_BACKOFF: Duration = Duration::from_millis(25);
+
+#[derive(Clone)]
 pub struct DynamoTable {
     client: Client,
     pub config: DynamoDBConfig,
@@ -43,17 +57,13 @@ impl DynamoTable {
         })
     }
 
+    /// Transactions cancelled by a conflict with another one are retried with a backoff.
     pub async fn transact_write<C>(&self, configure: C) -> Result<TransactWriteItemsOutput>
     where
         C: FnOnce(TransactWriteItems) -> TransactWriteItems,
     {
-        configure(self.client.transact_write_items())
-            .send()
-            .await
-            .map_err(|e| {
-                error!("{}", e);
-                DynamoDbErrors::RequestFailure
-            })
+        let req = configure(self.client.transact_write_items());
+        retry_conflicts(|| req.clone().send()).await
     }
 
     pub fn transact_put<K, C>(&self, key: &ItemKey<K>, configure: C) -> TransactWriteItem
@@ -97,8 +107,21 @@ impl DynamoTable {
             .build()
     }
 
-    pub fn scan(&self) -> Scan {
-        self.client.scan().table_name(&self.config.table)
+    pub fn transact_update<K, C>(&self, key: &ItemKey<K>, configure: C) -> TransactWriteItem
+    where
+        K: ToString,
+        C: FnOnce(UpdateBuilder) -> UpdateBuilder,
+    {
+        TransactWriteItem::builder()
+            .update(
+                configure(
+                    Update::builder()
+                        .table_name(&self.config.table)
+                        .set_key(self.build_key_attributes(key)),
+                )
+                .build(),
+            )
+            .build()
     }
 
     pub async fn query_gsi1_get<K, C>(
@@ -120,10 +143,87 @@ impl DynamoTable {
             .expression_attribute_names("#sk", &self.config.gsi1_sort_key)
             .expression_attribute_values(":pk", AttributeValue::S(gsi1_key.partition.to_string()))
             .expression_attribute_values(":sk", AttributeValue::S(gsi1_key.sort.to_string()));
-        configure(req).send().await.map_err(|e| {
-            error!("{}", e);
-            DynamoDbErrors::RequestFailure
-        })
+        configure(req).send().await.map_err(request_error)
+    }
+
+    pub fn query_gsi2_by_prefix<K: ToString>(&self, gsi2_pkey: K, gsi2_skey_prefix: &str) -> Query {
+        self.client
+            .query()
+            .table_name(&self.config.table)
+            .index_name(&self.config.gsi2_name)
+            .key_condition_expression("#pk = :pk and begins_with(#sk, :sk)")
+            .expression_attribute_names("#pk", &self.config.gsi2_partition_key)
+            .expression_attribute_names("#sk", &self.config.gsi2_sort_key)
+            .expression_attribute_values(":pk", AttributeValue::S(gsi2_pkey.to_string()))
+            .expression_attribute_values(":sk", AttributeValue::S(gsi2_skey_prefix.to_owned()))
+    }
+
+    pub fn query_gsi3_by_prefix<K: ToString>(&self, gsi3_pkey: K, gsi3_skey_prefix: &str) -> Query {
+        self.client
+            .query()
+            .table_name(&self.config.table)
+            .index_name(&self.config.gsi3_name)
+            .key_condition_expression("#pk = :pk and begins_with(#sk, :sk)")
+            .expression_attribute_names("#pk", &self.config.gsi3_partition_key)
+            .expression_attribute_names("#sk", &self.config.gsi3_sort_key)
+            .expression_attribute_values(":pk", AttributeValue::S(gsi3_pkey.to_string()))
+            .expression_attribute_values(":sk", AttributeValue::S(gsi3_skey_prefix.to_owned()))
+    }
+
+    /// Items of a GSI4 partition whose sort key is lower or equal to `gsi4_skey_max`.
+    pub fn query_gsi4_until<K: ToString>(&self, gsi4_pkey: K, gsi4_skey_max: &str) -> Query {
+        self.client
+            .query()
+            .table_name(&self.config.table)
+            .index_name(&self.config.gsi4_name)
+            .key_condition_expression("#pk = :pk and #sk <= :sk")
+            .expression_attribute_names("#pk", &self.config.gsi4_partition_key)
+            .expression_attribute_names("#sk", &self.config.gsi4_sort_key)
+            .expression_attribute_values(":pk", AttributeValue::S(gsi4_pkey.to_string()))
+            .expression_attribute_values(":sk", AttributeValue::S(gsi4_skey_max.to_owned()))
+    }
+
+    /// Page of the items of a partition with the given prefix, starting after the item with the
+    /// `after` sort key. Also returns whether there are more items.
+    pub async fn query_partition_page<K: ToString>(
+        &self,
+        pkey: K,
+        skey_prefix: &str,
+        after: Option<K>,
+        limit: usize,
+    ) -> Result<(Vec<RawAttributes>, bool)> {
+        self.query_partition_page_with(pkey, skey_prefix, after, limit, identity)
+            .await
+    }
+
+    /// Same as `query_partition_page`, with a custom configuration of the query such as its
+    /// order.
+    pub async fn query_partition_page_with<K, C>(
+        &self,
+        pkey: K,
+        skey_prefix: &str,
+        after: Option<K>,
+        limit: usize,
+        configure: C,
+    ) -> Result<(Vec<RawAttributes>, bool)>
+    where
+        K: ToString,
+        C: FnOnce(Query) -> Query,
+    {
+        let partition = pkey.to_string();
+        let mut query = configure(self.query_partition_by_prefix(&partition, skey_prefix))
+            // One more item to know whether there is a next page.
+            .limit(limit as i32 + 1);
+        if let Some(after) = after {
+            query = query
+                .exclusive_start_key(&self.config.partition_key, AttributeValue::S(partition))
+                .exclusive_start_key(&self.config.sort_key, AttributeValue::S(after.to_string()));
+        }
+        let output = query.send().await.map_err(request_error)?;
+        let mut items = output.items.unwrap_or_default();
+        let has_next_page = items.len() > limit;
+        items.truncate(limit);
+        Ok((items, has_next_page))
     }
 
     pub fn query_partition_by_prefix<K: ToString>(&self, pkey: K, skey_prefix: &str) -> Query {
@@ -137,6 +237,43 @@ impl DynamoTable {
             .expression_attribute_values(":sk", AttributeValue::S(skey_prefix.to_owned()))
     }
 
+    /// Scans the whole table for the items whose sort key is their partition key and starts with
+    /// the prefix, such as the todo lists. Only meant for maintenance commands.
+    pub fn scan_entities_by_prefix(&self, prefix: &str) -> Scan {
+        self.client
+            .scan()
+            .table_name(&self.config.table)
+            .filter_expression("#pk = #sk and begins_with(#sk, :sk)")
+            .expression_attribute_names("#pk", &self.config.partition_key)
+            .expression_attribute_names("#sk", &self.config.sort_key)
+            .expression_attribute_values(":sk", AttributeValue::S(prefix.to_owned()))
+    }
+
+    /// Scans the whole table for the items whose sort key starts with the prefix. Only meant for
+    /// maintenance commands.
+    pub fn scan_by_sort_prefix(&self, prefix: &str) -> Scan {
+        self.client
+            .scan()
+            .table_name(&self.config.table)
+            .filter_expression("begins_with(#sk, :sk)")
+            .expression_attribute_names("#sk", &self.config.sort_key)
+            .expression_attribute_values(":sk", AttributeValue::S(prefix.to_owned()))
+    }
+
+    /// Scans GSI4 for the items of the partitions with the given prefix, such as the reminders,
+    /// whose sort key is before the given one. Only meant for the startup of the scheduler.
+    pub fn scan_gsi4_before(&self, gsi4_pkey_prefix: &str, gsi4_skey_max: &str) -> Scan {
+        self.client
+            .scan()
+            .table_name(&self.config.table)
+            .index_name(&self.config.gsi4_name)
+            .filter_expression("begins_with(#pk, :pk) and #sk < :sk")
+            .expression_attribute_names("#pk", &self.config.gsi4_partition_key)
+            .expression_attribute_names("#sk", &self.config.gsi4_sort_key)
+            .expression_attribute_values(":pk", AttributeValue::S(gsi4_pkey_prefix.to_owned()))
+            .expression_attribute_values(":sk", AttributeValue::S(gsi4_skey_max.to_owned()))
+    }
+
     pub async fn get_item<K, C>(&self, key: &ItemKey<K>, configure: C) -> Result<GetItemOutput>
     where
         K: ToString,
@@ -147,10 +284,7 @@ impl DynamoTable {
             .get_item()
             .table_name(&self.config.table)
             .set_key(self.build_key_attributes(key));
-        configure(req).send().await.map_err(|e| {
-            error!("{}", e);
-            DynamoDbErrors::RequestFailure
-        })
+        configure(req).send().await.map_err(request_error)
     }
 
     pub async fn put_item<K, C>(&self, key: &ItemKey<K>, configure: C) -> Result<PutItemOutput>
@@ -170,10 +304,7 @@ impl DynamoTable {
                 &self.config.sort_key,
                 AttributeValue::S(key.sort.to_string()),
             );
-        configure(req).send().await.map_err(|e| {
-            error!("{}", e);
-            DynamoDbErrors::RequestFailure
-        })
+        configure(req).send().await.map_err(request_error)
     }
 
     pub async fn delete_item<K, C>(
@@ -190,10 +321,7 @@ impl DynamoTable {
             .delete_item()
             .table_name(&self.config.table)
             .set_key(self.build_key_attributes(key));
-        configure(req).send().await.map_err(|e| {
-            error!("{}", e);
-            DynamoDbErrors::RequestFailure
-        })
+        configure(req).send().await.map_err(request_error)
     }
 
     pub async fn update_item<K, C>(
@@ -210,10 +338,8 @@ impl DynamoTable {
             .update_item()
             .table_name(&self.config.table)
             .set_key(self.build_key_attributes(key));
-        configure(req).send().await.map_err(|e| {
-            error!("{}", e);
-            DynamoDbErrors::RequestFailure
-        })
+        let req = configure(req);
+        retry_conflicts(|| req.clone().send()).await
     }
 
     fn build_key_attributes<K>(&self, key: &ItemKey<K>) -> Option<RawAttributes>
@@ -232,3 +358,63 @@ impl DynamoTable {
         Some(map)
     }
 }
+
+/// Sends the request again while it conflicts with a concurrent transaction, up to
+/// `CONFLICT_ATTEMPTS` times.
+async fn retry_conflicts<T, E, F, R>(send: F) -> Result<T>
+where
+    F: Fn() -> R,
+    R: Future<Output = std::result::Result<T, SdkError<E>>>,
+    aws_sdk_dynamodb::Error: From<SdkError<E>>,
+{
+    let mut backoff = CONFLICT_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match send().await.map_err(request_error) {
+            Err(DynamoDbErrors::TransactionConflict) if attempt < CONFLICT_ATTEMPTS => {
+                warn!("Retrying a write conflicting with a concurrent transaction");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Failed conditions are expected, for example when an item does not belong to the current owner,
+/// so they're kept apart from the other failures.
+fn request_error<E>(e: SdkError<E>) -> DynamoDbErrors
+where
+    aws_sdk_dynamodb::Error: From<SdkError<E>>,
+{
+    match aws_sdk_dynamodb::Error::from(e) {
+        aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_) => {
+            DynamoDbErrors::ConditionalCheckFailed
+        }
+        // Failed conditions of transactions are only reported in the cancellation reasons.
+        aws_sdk_dynamodb::Error::TransactionCanceledException(e)
+            if e.cancellation_reasons()
+                .unwrap_or_default()
+                .iter()
+                .any(|reason| reason.code() == Some("ConditionalCheckFailed")) =>
+        {
+            DynamoDbErrors::ConditionalCheckFailed
+        }
+        aws_sdk_dynamodb::Error::TransactionConflictException(_) => {
+            DynamoDbErrors::TransactionConflict
+        }
+        aws_sdk_dynamodb::Error::TransactionCanceledException(e)
+            if e.cancellation_reasons()
+                .unwrap_or_default()
+                .iter()
+                .any(|reason| reason.code() == Some("TransactionConflict")) =>
+        {
+            DynamoDbErrors::TransactionConflict
+        }
+        e => {
+            error!("{}", e);
+            DynamoDbErrors::RequestFailure
+        }
+    }
+}
@@ -1,31 +1,110 @@
+use std::sync::Arc;
+
+use async_graphql::extensions::apollo_persisted_queries::{
+    ApolloPersistedQueries, LruCacheStorage,
+};
 use async_graphql::extensions::Tracing;
-use async_graphql::{EmptySubscription, MergedObject, Schema};
+use async_graphql::{MergedObject, MergedSubscription, Schema};
 
+use model::comment::mutation::CommentMutation;
+use model::search::query::SearchQuery;
 use model::todo::mutation::TodoMutation;
 use model::todo::query::TodoQuery;
+use model::todo::subscription::TodoSubscription;
 use model::todo_list::mutation::TodoListMutation;
 use model::todo_list::query::TodoListQuery;
+use model::todo_list_member::mutation::TodoListMemberMutation;
+use model::trash::query::TrashQuery;
+use model::viewer::ViewerQuery;
+use model::webhook::mutation::WebhookMutation;
+use model::webhook::query::WebhookQuery;
 
 use crate::dynamodb::item::ItemKey;
 use crate::dynamodb::DynamoTable;
+use crate::graphql::config::GraphQLConfig;
+use crate::graphql::limits::QueryLimits;
+use crate::graphql::persisted_queries::{PersistedQueries, PersistedQueryManifest};
+use crate::graphql::reminders::ReminderEvents;
+use crate::graphql::search::SearchIndex;
 use crate::graphql::types::ID;
+use crate::graphql::webhooks::{WebhookConfig, WebhookDispatcher};
+use crate::rate_limit::{RateLimit, RateLimiter};
 
+pub mod calendar;
+pub mod commands;
+pub mod config;
 mod errors;
+mod limits;
 mod model;
+mod pagination;
+mod persisted_queries;
+pub mod reminders;
+pub mod search;
 mod types;
+pub mod webhooks;
 
-pub type GraphQLSchema = Schema<Query, Mutation, EmptySubscription>;
+pub type GraphQLSchema = Schema<Query, Mutation, Subscription>;
 type Key = ItemKey<ID>;
 
 #[derive(MergedObject, Default)]
-pub struct Query(TodoQuery, TodoListQuery);
+pub struct Query(
+    TodoQuery,
+    TodoListQuery,
+    TrashQuery,
+    SearchQuery,
+    WebhookQuery,
+    ViewerQuery,
+);
 
 #[derive(MergedObject, Default)]
-pub struct Mutation(TodoMutation, TodoListMutation);
+pub struct Mutation(
+    TodoMutation,
+    TodoListMutation,
+    TodoListMemberMutation,
+    CommentMutation,
+    WebhookMutation,
+);
+
+#[derive(MergedSubscription, Default)]
+pub struct Subscription(TodoSubscription);
+
+pub fn build_schema(
+    config: &GraphQLConfig,
+    db: DynamoTable,
+    reminder_events: ReminderEvents,
+    webhook_config: WebhookConfig,
+    search: SearchIndex,
+    rate_limiter: Arc<RateLimiter>,
+) -> anyhow::Result<GraphQLSchema> {
+    let manifest = if let Some(path) = &config.persisted_queries_manifest {
+        Some(PersistedQueryManifest::load(path)?)
+    } else {
+        None
+    };
+    let webhooks = WebhookDispatcher::new(webhook_config, db.clone())?;
+    webhooks.spawn_retries();
 
-pub fn build_schema(db: DynamoTable) -> GraphQLSchema {
-    Schema::build(Query::default(), Mutation::default(), EmptySubscription)
-        .extension(Tracing)
-        .data(db)
-        .finish()
+    let schema = Schema::build(
+        Query::default(),
+        Mutation::default(),
+        Subscription::default(),
+    )
+    .extension(Tracing)
+    // Must be registered before the automatic persisted queries to resolve the query from the
+    // manifest first.
+    .extension(PersistedQueries::new(manifest))
+    .extension(ApolloPersistedQueries::new(LruCacheStorage::new(
+        config.apq_cache_size,
+    )))
+    // Registered after the persisted queries to know the type of the operations sent by hash.
+    .extension(RateLimit::new(rate_limiter))
+    .extension(QueryLimits::new(config))
+    .limit_depth(config.max_depth)
+    .limit_complexity(config.max_complexity)
+    .data(webhooks)
+    .data(db)
+    .data(reminder_events)
+    .data(search)
+    .finish();
+    Ok(schema)
 }
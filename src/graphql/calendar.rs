@@ -0,0 +1,65 @@
+use async_graphql::{Request, Variables};
+use serde_json::json;
+
+use super::model::TODO_LIST_TYPE_NAME;
+use super::types::ID;
+
+const EXPORT_QUERY: &str = "query CalendarExport($id: Id!) { todoListCalendar(id: $id) }";
+
+/// Aliased as the export, so both responses are read the same way.
+const FEED_QUERY: &str = "query CalendarFeed($id: Id!, $token: String!) {
+  todoListCalendar: todoListCalendarFeed(id: $id, token: $token)
+}";
+
+const IMPORT_QUERY: &str = "mutation CalendarImport($id: Id!, $content: String!) {
+  todoListImportCalendar(id: $id, content: $content) {
+    todoList { id title todoCount completedCount }
+    created
+    updated
+    unchanged
+    errors { line message }
+  }
+}";
+
+/// Requests of the `/lists/{id}.ics` endpoint, executed as GraphQL operations so they share the
+/// authorization, limits and side effects of the API. The list is identified by its id, or only
+/// by its ULID as the separator of the ids must be escaped in a path. Returns `None` if it isn't
+/// the id of a todo list.
+pub fn export_request(list: &str) -> Option<Request> {
+    let id = list_id(list)?;
+    Some(Request::new(EXPORT_QUERY).variables(Variables::from_json(json!({ "id": id }))))
+}
+
+/// Export of a list by the token of its calendar feed, for the calendar apps which can't send a
+/// bearer token.
+pub fn feed_request(list: &str, token: &str) -> Option<Request> {
+    let id = list_id(list)?;
+    Some(
+        Request::new(FEED_QUERY).variables(Variables::from_json(json!({
+            "id": id,
+            "token": token,
+        }))),
+    )
+}
+
+pub fn import_request(list: &str, content: String) -> Option<Request> {
+    let id = list_id(list)?;
+    Some(
+        Request::new(IMPORT_QUERY).variables(Variables::from_json(json!({
+            "id": id,
+            "content": content,
+        }))),
+    )
+}
+
+fn list_id(list: &str) -> Option<String> {
+    let id = if list.contains('#') {
+        list.to_string()
+    } else {
+        format!("{}{}", ID::prefix(TODO_LIST_TYPE_NAME), list)
+    };
+    id.parse::<ID>()
+        .ok()
+        .filter(|id| id.has_type_name(TODO_LIST_TYPE_NAME))
+        .map(|id| String::from(&id))
+}
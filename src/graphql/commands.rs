@@ -0,0 +1,34 @@
+use anyhow::{anyhow, bail, Result};
+use tracing::info;
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::search::{SearchConfig, SearchIndex};
+
+use super::model::todo_list::counters::repair_todo_list_counts;
+use super::types::ID;
+
+/// Runs a maintenance command given on the command line instead of starting the server, either
+/// `repair-counters [LIST_ID...]` or `rebuild-search`.
+pub async fn run(dynamodb: &DynamoTable, search: &SearchConfig, args: &[String]) -> Result<()> {
+    match args {
+        [command, ids @ ..] if command == "repair-counters" => {
+            let ids = ids
+                .iter()
+                .map(|id| id.parse())
+                .collect::<Result<Vec<ID>, _>>()?;
+            let repaired = repair_todo_list_counts(dynamodb, &ids)
+                .await
+                .map_err(|err| anyhow!(err.message))?;
+            info!("Repaired the counters of {} todo lists", repaired);
+            Ok(())
+        }
+        [command] if command == "rebuild-search" => {
+            // Fails while the server holds the lock of the index.
+            let indexed = SearchIndex::load(search)?.rebuild(dynamodb).await?;
+            info!("Rebuilt the search index with {} items", indexed);
+            Ok(())
+        }
+        [command, ..] => bail!("Unknown command '{}'", command),
+        [] => Ok(()),
+    }
+}
@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GraphQLConfig {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    pub max_aliases: usize,
+    pub max_root_fields: usize,
+    pub apq_cache_size: usize,
+    /// When defined, only the queries listed in this manifest are executed.
+    pub persisted_queries_manifest: Option<String>,
+}
@@ -1,5 +1,6 @@
-use async_graphql::{Error, ErrorExtensions, Result};
+use async_graphql::{Context, Error, ErrorExtensions, MaybeUndefined, Result};
 
+use crate::auth::Principal;
 use crate::graphql::types::ID;
 
 #[derive(Debug, thiserror::Error)]
@@ -9,6 +10,18 @@ pub enum Errors {
 
     #[error("Invalid value")]
     InvalidValue(String),
+
+    #[error("Authentication required")]
+    Unauthenticated,
+
+    #[error("Not allowed to perform this operation")]
+    Forbidden,
+
+    #[error("Aborted along with the rest of an atomic operation")]
+    Aborted,
+
+    #[error("The todo was written, but its parents couldn't be completed")]
+    ParentsNotCompleted(String),
 }
 
 /// Using the proposed API by async-graphql for the custom errors with additional informations.
@@ -20,6 +33,13 @@ impl ErrorExtensions for Errors {
                 e.set("code", "INVALID_VALUE");
                 e.set("details", details.clone());
             }
+            Errors::Unauthenticated => e.set("code", "UNAUTHENTICATED"),
+            Errors::Forbidden => e.set("code", "FORBIDDEN"),
+            Errors::Aborted => e.set("code", "ABORTED"),
+            Errors::ParentsNotCompleted(details) => {
+                e.set("code", "PARENTS_NOT_COMPLETED");
+                e.set("details", details.clone());
+            }
         })
     }
 }
@@ -33,3 +53,21 @@ pub fn check_id_kind(id: &ID, expected_kind: &str) -> Result<()> {
         Ok(())
     }
 }
+
+/// Anonymous requests are only allowed for introspection, but resolvers should not rely on it.
+pub fn principal<'a>(ctx: &Context<'a>) -> Result<&'a Principal> {
+    ctx.data_opt::<Principal>()
+        .ok_or_else(|| Errors::Unauthenticated.extend())
+}
+
+/// Fields of update inputs can be omitted to keep their current value, but only nullable ones can
+/// be cleared with an explicit null.
+pub fn non_nullable<T>(value: MaybeUndefined<T>, field: &str) -> Result<Option<T>> {
+    match value {
+        MaybeUndefined::Undefined => Ok(None),
+        MaybeUndefined::Null => {
+            Err(Errors::InvalidValue(format!("'{}' can't be null", field)).extend())
+        }
+        MaybeUndefined::Value(value) => Ok(Some(value)),
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextRequest,
+};
+use async_graphql::parser::types::{ExecutableDocument, Selection, SelectionSet};
+use async_graphql::{ErrorExtensionValues, Name, Response, ServerError, ServerResult, Variables};
+
+use crate::graphql::config::GraphQLConfig;
+
+/// Cost of a field resolved with an additional DynamoDB request, such as a relation.
+pub const RELATION_COMPLEXITY: usize = 5;
+/// Expected number of items returned by a collection, multiplying the cost of its children.
+pub const COLLECTION_COMPLEXITY: usize = 10;
+
+// Messages used by async-graphql when the depth or complexity limits are exceeded.
+const TOO_DEEP_MESSAGE: &str = "Query is nested too deep.";
+const TOO_COMPLEX_MESSAGE: &str = "Query is too complex.";
+
+/// Rejects queries with too many aliases or root fields and adds an error code to the depth and
+/// complexity errors raised by the schema itself.
+pub struct QueryLimits {
+    max_aliases: usize,
+    max_root_fields: usize,
+}
+
+impl QueryLimits {
+    pub fn new(config: &GraphQLConfig) -> QueryLimits {
+        QueryLimits {
+            max_aliases: config.max_aliases,
+            max_root_fields: config.max_root_fields,
+        }
+    }
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitsExtension {
+            max_aliases: self.max_aliases,
+            max_root_fields: self.max_root_fields,
+        })
+    }
+}
+
+struct QueryLimitsExtension {
+    max_aliases: usize,
+    max_root_fields: usize,
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimitsExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        for error in response.errors.iter_mut() {
+            let code = match error.message.as_str() {
+                TOO_DEEP_MESSAGE => "QUERY_TOO_DEEP",
+                TOO_COMPLEX_MESSAGE => "QUERY_TOO_COMPLEX",
+                _ => continue,
+            };
+            error
+                .extensions
+                .get_or_insert_with(ErrorExtensionValues::default)
+                .set("code", code);
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        let aliases = count_aliases(&document);
+        if aliases > self.max_aliases {
+            return Err(limit_error(
+                format!("Query has too many aliases: {}", aliases),
+                "TOO_MANY_ALIASES",
+            ));
+        }
+
+        for (_, operation) in document.operations.iter() {
+            let root_fields = count_root_fields(
+                &document,
+                &operation.node.selection_set.node,
+                &mut HashSet::new(),
+            );
+            if root_fields > self.max_root_fields {
+                return Err(limit_error(
+                    format!("Query has too many root fields: {}", root_fields),
+                    "TOO_MANY_ROOT_FIELDS",
+                ));
+            }
+        }
+
+        Ok(document)
+    }
+}
+
+fn limit_error(message: String, code: &str) -> ServerError {
+    let mut error = ServerError::new(message, None);
+    let mut extensions = ErrorExtensionValues::default();
+    extensions.set("code", code);
+    error.extensions = Some(extensions);
+    error
+}
+
+fn count_aliases(document: &ExecutableDocument) -> usize {
+    let operations: usize = document
+        .operations
+        .iter()
+        .map(|(_, operation)| count_selection_set_aliases(&operation.node.selection_set.node))
+        .sum();
+    let fragments: usize = document
+        .fragments
+        .values()
+        .map(|fragment| count_selection_set_aliases(&fragment.node.selection_set.node))
+        .sum();
+    operations + fragments
+}
+
+fn count_selection_set_aliases(selection_set: &SelectionSet) -> usize {
+    selection_set
+        .items
+        .iter()
+        .map(|selection| match &selection.node {
+            Selection::Field(field) => {
+                usize::from(field.node.alias.is_some())
+                    + count_selection_set_aliases(&field.node.selection_set.node)
+            }
+            Selection::InlineFragment(fragment) => {
+                count_selection_set_aliases(&fragment.node.selection_set.node)
+            }
+            // Counted once with the fragment definitions.
+            Selection::FragmentSpread(_) => 0,
+        })
+        .sum()
+}
+
+/// Fragments are expanded. Parsing happens before the validation, so cycles between fragments
+/// still need to be handled here.
+fn count_root_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited: &mut HashSet<&'a Name>,
+) -> usize {
+    selection_set
+        .items
+        .iter()
+        .map(|selection| match &selection.node {
+            Selection::Field(_) => 1,
+            Selection::InlineFragment(fragment) => {
+                count_root_fields(document, &fragment.node.selection_set.node, visited)
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.node.fragment_name.node;
+                match document.fragments.get(name) {
+                    Some(fragment) if visited.insert(name) => {
+                        count_root_fields(document, &fragment.node.selection_set.node, visited)
+                    }
+                    _ => 0,
+                }
+            }
+        })
+        .sum()
+}
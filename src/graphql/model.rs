@@ -1,5 +1,15 @@
+pub use audit::AuditRecord;
+pub use comment::Comment;
 pub use todo::{Todo, TODO_TYPE_NAME};
 pub use todo_list::{TodoList, TODO_LIST_TYPE_NAME};
+pub use todo_list_member::{TodoListMember, TodoListRole};
 
+pub mod audit;
+pub mod comment;
+pub mod search;
 pub mod todo;
 pub mod todo_list;
+pub mod todo_list_member;
+pub mod trash;
+pub mod viewer;
+pub mod webhook;
@@ -0,0 +1,101 @@
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+
+use crate::graphql::types::ID;
+
+pub mod extensions;
+
+pub const AUDIT_TYPE_NAME: &str = "audit";
+
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AuditOperation {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl AuditOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOperation::Create => "CREATE",
+            AuditOperation::Update => "UPDATE",
+            AuditOperation::Delete => "DELETE",
+            AuditOperation::Restore => "RESTORE",
+        }
+    }
+}
+
+impl std::str::FromStr for AuditOperation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "CREATE" => Ok(AuditOperation::Create),
+            "UPDATE" => Ok(AuditOperation::Update),
+            "DELETE" => Ok(AuditOperation::Delete),
+            "RESTORE" => Ok(AuditOperation::Restore),
+            _ => Err(anyhow::anyhow!("Invalid operation '{}'", s)),
+        }
+    }
+}
+
+/// Previous and new value of a field, formatted as strings.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Immutable record of a change, written in the same transaction as the change itself. Records
+/// are kept in the item collection of the entity, even after its deletion.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct AuditRecord {
+    pub id: ID,
+    /// Subject of the user who made the change.
+    pub actor: String,
+    pub at: DateTime<Utc>,
+    pub operation: AuditOperation,
+    pub changes: Vec<FieldChange>,
+    #[graphql(skip)]
+    pub entity_id: ID,
+}
+
+impl AuditRecord {
+    pub fn new(
+        entity_id: &ID,
+        actor: &str,
+        operation: AuditOperation,
+        changes: Changes,
+    ) -> AuditRecord {
+        AuditRecord {
+            id: ID::new(AUDIT_TYPE_NAME),
+            actor: actor.to_string(),
+            at: Utc::now(),
+            operation,
+            changes: changes.0,
+            entity_id: entity_id.clone(),
+        }
+    }
+}
+
+/// Collects the fields whose value differs.
+#[derive(Debug, Default)]
+pub struct Changes(Vec<FieldChange>);
+
+impl Changes {
+    pub fn diff(&mut self, field: &str, before: Option<String>, after: Option<String>) {
+        if before != after {
+            self.0.push(FieldChange {
+                field: field.to_string(),
+                before,
+                after,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,116 @@
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::{AttributeValue, TransactWriteItem};
+
+use crate::dynamodb::item::datetime_attribute;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes};
+use crate::graphql::types::ID;
+use crate::graphql::Key;
+
+use super::{AuditRecord, FieldChange, AUDIT_TYPE_NAME};
+
+/// Extension used to decorate the DynamoTable with specialized methods for AuditRecord
+#[async_trait::async_trait]
+pub trait DynamoTableAuditExt {
+    /// Audit records of an entity in chronological order, starting after the given record. Also
+    /// returns whether there are more records.
+    async fn get_audit_records(
+        &self,
+        entity_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<AuditRecord>, bool)>;
+    /// Most recent audit record of an entity, which gives the time of its last change.
+    async fn get_last_audit_record(&self, entity_id: &ID) -> Result<Option<AuditRecord>>;
+    /// Audit records are only written within the transaction of the change.
+    fn transact_put_audit(&self, record: &AuditRecord) -> TransactWriteItem;
+}
+
+#[async_trait::async_trait]
+impl DynamoTableAuditExt for DynamoTable {
+    async fn get_audit_records(
+        &self,
+        entity_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<AuditRecord>, bool)> {
+        let (items, has_next_page) = self
+            .query_partition_page(entity_id, &ID::prefix(AUDIT_TYPE_NAME), after, limit)
+            .await?;
+        let records = items
+            .iter()
+            .map(|item| build_audit_record(self, item))
+            .collect::<Result<Vec<AuditRecord>>>()?;
+        Ok((records, has_next_page))
+    }
+
+    async fn get_last_audit_record(&self, entity_id: &ID) -> Result<Option<AuditRecord>> {
+        let (items, _) = self
+            .query_partition_page_with(entity_id, &ID::prefix(AUDIT_TYPE_NAME), None, 1, |query| {
+                query.scan_index_forward(false)
+            })
+            .await?;
+        items
+            .first()
+            .map(|item| build_audit_record(self, item))
+            .transpose()
+    }
+
+    fn transact_put_audit(&self, record: &AuditRecord) -> TransactWriteItem {
+        let key = Key {
+            partition: record.entity_id.clone(),
+            sort: record.id.clone(),
+        };
+        let changes = record
+            .changes
+            .iter()
+            .map(|change| {
+                let mut attributes = RawAttributes::from([(
+                    "field".to_string(),
+                    AttributeValue::S(change.field.clone()),
+                )]);
+                if let Some(before) = &change.before {
+                    attributes.insert("before".to_string(), AttributeValue::S(before.clone()));
+                }
+                if let Some(after) = &change.after {
+                    attributes.insert("after".to_string(), AttributeValue::S(after.clone()));
+                }
+                AttributeValue::M(attributes)
+            })
+            .collect();
+        self.transact_put(&key, |put| {
+            put.item("actor", AttributeValue::S(record.actor.clone()))
+                .item("at", datetime_attribute(&record.at))
+                .item(
+                    "operation",
+                    AttributeValue::S(record.operation.as_str().to_string()),
+                )
+                .item("changes", AttributeValue::L(changes))
+                // Records are immutable.
+                .condition_expression("attribute_not_exists(#pk)")
+                .expression_attribute_names("#pk", &self.config.partition_key)
+        })
+    }
+}
+
+fn build_audit_record(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<AuditRecord> {
+    let key: Key = dynamodb.extract_key(item)?;
+    let changes = item
+        .get_map_list("changes")?
+        .into_iter()
+        .map(|change| {
+            Ok(FieldChange {
+                field: change.get_string("field")?.clone(),
+                before: change.get_optional_string("before")?.cloned(),
+                after: change.get_optional_string("after")?.cloned(),
+            })
+        })
+        .collect::<Result<Vec<FieldChange>>>()?;
+    Ok(AuditRecord {
+        id: key.sort,
+        actor: item.get_string("actor")?.clone(),
+        at: item.get_from_string("at")?,
+        operation: item.get_from_string("operation")?,
+        changes,
+        entity_id: key.partition,
+    })
+}
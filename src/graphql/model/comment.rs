@@ -0,0 +1,22 @@
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+
+use crate::graphql::types::ID;
+
+pub mod extensions;
+pub mod mutation;
+
+pub const COMMENT_TYPE_NAME: &str = "comment";
+
+/// Comments are stored in the item collection of their todo, identified by the todo id.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Comment {
+    pub id: ID,
+    /// Subject of the user who wrote the comment.
+    pub author: String,
+    /// Formatted in Markdown.
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub todo_id: ID,
+}
@@ -0,0 +1,173 @@
+use std::convert::identity;
+
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::{AttributeValue, ReturnValue};
+use chrono::{DateTime, Utc};
+use tokio_stream::StreamExt;
+
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::item::datetime_attribute;
+use crate::dynamodb::table::MAX_TRANSACTION_ITEMS;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes, UpdateExpression};
+use crate::graphql::model::trash::transact_expiration;
+use crate::graphql::types::ID;
+use crate::graphql::Key;
+
+use super::{Comment, COMMENT_TYPE_NAME};
+
+/// Extension used to decorate the DynamoTable with specialized methods for Comment
+#[async_trait::async_trait]
+pub trait DynamoTableCommentExt {
+    /// Comments of a todo in creation order, starting after the given comment. Also returns
+    /// whether there are more comments.
+    async fn get_todo_comments(
+        &self,
+        todo_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<Comment>, bool)>;
+    async fn get_comment(&self, id: &ID) -> Result<Option<Comment>>;
+    async fn put_comment(&self, comment: &Comment) -> Result<bool>;
+    async fn update_comment(&self, comment: &Comment, body: String) -> Result<Option<Comment>>;
+    async fn delete_comment(&self, comment: &Comment) -> Result<Option<Comment>>;
+    async fn set_todo_comments_expiration(
+        &self,
+        todo_id: &ID,
+        expires_at: Option<&DateTime<Utc>>,
+    ) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl DynamoTableCommentExt for DynamoTable {
+    async fn get_todo_comments(
+        &self,
+        todo_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<Comment>, bool)> {
+        let (items, has_next_page) = self
+            .query_partition_page(todo_id, &ID::prefix(COMMENT_TYPE_NAME), after, limit)
+            .await?;
+        let comments = items
+            .iter()
+            .map(|item| build_comment(self, item))
+            .collect::<Result<Vec<Comment>>>()?;
+        Ok((comments, has_next_page))
+    }
+
+    async fn get_comment(&self, id: &ID) -> Result<Option<Comment>> {
+        let gsi1_key = Key {
+            partition: id.clone(),
+            sort: id.clone(),
+        };
+        let output = self.query_gsi1_get(&gsi1_key, identity).await?;
+        Ok(if let Some(item) = output.items.unwrap_or_default().pop() {
+            Some(build_comment(self, &item)?)
+        } else {
+            None
+        })
+    }
+
+    async fn put_comment(&self, comment: &Comment) -> Result<bool> {
+        self.put_item(&comment_key(comment), |req| {
+            req.item("author", AttributeValue::S(comment.author.clone()))
+                .item("body", AttributeValue::S(comment.body.clone()))
+                .item("created_at", datetime_attribute(&comment.created_at))
+                // Comments can be retrieved directly by their id.
+                .item(
+                    &self.config.gsi1_partition_key,
+                    AttributeValue::S(String::from(&comment.id)),
+                )
+                .item(
+                    &self.config.gsi1_sort_key,
+                    AttributeValue::S(String::from(&comment.id)),
+                )
+        })
+        .await?;
+        Ok(true)
+    }
+
+    /// Returns `None` if the comment was deleted in the meantime.
+    async fn update_comment(&self, comment: &Comment, body: String) -> Result<Option<Comment>> {
+        let output = self
+            .update_item(&comment_key(comment), |req| {
+                let mut update = UpdateExpression::default();
+                update.set("body", AttributeValue::S(body));
+                update.apply(
+                    req.condition_expression("attribute_exists(#pk)")
+                        .expression_attribute_names("#pk", &self.config.partition_key)
+                        .return_values(ReturnValue::AllNew),
+                )
+            })
+            .await;
+        let output = match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => return Ok(None),
+            output => output?,
+        };
+        Ok(if let Some(item) = output.attributes {
+            Some(build_comment(self, &item)?)
+        } else {
+            None
+        })
+    }
+
+    async fn delete_comment(&self, comment: &Comment) -> Result<Option<Comment>> {
+        let output = self
+            .delete_item(&comment_key(comment), |req| {
+                req.return_values(ReturnValue::AllOld)
+            })
+            .await?;
+        Ok(if let Some(item) = output.attributes {
+            Some(build_comment(self, &item)?)
+        } else {
+            None
+        })
+    }
+
+    /// Comments of a todo in the trash expire along with it. They're updated in chunks, each of
+    /// them being atomic.
+    async fn set_todo_comments_expiration(
+        &self,
+        todo_id: &ID,
+        expires_at: Option<&DateTime<Utc>>,
+    ) -> Result<()> {
+        let mut keys: Vec<Key> = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(todo_id, &ID::prefix(COMMENT_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                keys.push(self.extract_key(item)?);
+            }
+        }
+
+        for chunk in keys.chunks(MAX_TRANSACTION_ITEMS) {
+            self.transact_write(|transaction| {
+                chunk.iter().fold(transaction, |transaction, key| {
+                    transaction.transact_items(transact_expiration(self, key, expires_at))
+                })
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+fn comment_key(comment: &Comment) -> Key {
+    Key {
+        partition: comment.todo_id.clone(),
+        sort: comment.id.clone(),
+    }
+}
+
+fn build_comment(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<Comment> {
+    let key: Key = dynamodb.extract_key(item)?;
+    Ok(Comment {
+        id: key.sort,
+        author: item.get_string("author")?.clone(),
+        body: item.get_string("body")?.clone(),
+        created_at: item.get_from_string("created_at")?,
+        todo_id: key.partition,
+    })
+}
@@ -0,0 +1,108 @@
+use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result};
+use chrono::Utc;
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::model::todo_list_member::access::get_todo_with_role;
+use crate::graphql::model::{TodoListRole, TODO_TYPE_NAME};
+use crate::graphql::types::ID;
+
+use super::extensions::DynamoTableCommentExt;
+use super::{Comment, COMMENT_TYPE_NAME};
+
+#[derive(Debug, InputObject)]
+struct CommentCreateInput {
+    todo: ID,
+    body: String,
+}
+
+#[derive(Debug, InputObject)]
+struct CommentUpdateInput {
+    id: ID,
+    body: String,
+}
+
+#[derive(Default)]
+pub struct CommentMutation;
+
+#[Object]
+impl CommentMutation {
+    /// Any user who can see a todo can comment on it.
+    async fn comment_create(
+        &self,
+        ctx: &Context<'_>,
+        input: CommentCreateInput,
+    ) -> Result<Comment> {
+        check_id_kind(&input.todo, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        if get_todo_with_role(dynamodb, principal, &input.todo)
+            .await?
+            .is_none()
+        {
+            return Err(Errors::NotFound.extend());
+        }
+
+        let comment = Comment {
+            id: ID::new(COMMENT_TYPE_NAME),
+            author: principal.subject.clone(),
+            body: input.body,
+            created_at: Utc::now(),
+            todo_id: input.todo,
+        };
+        dynamodb.put_comment(&comment).await.map(|_| comment)
+    }
+
+    /// Only the author can edit a comment.
+    async fn comment_update(
+        &self,
+        ctx: &Context<'_>,
+        input: CommentUpdateInput,
+    ) -> Result<Comment> {
+        check_id_kind(&input.id, COMMENT_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (comment, _) = get_comment_with_role(dynamodb, principal, &input.id).await?;
+        if comment.author != principal.subject {
+            return Err(Errors::Forbidden.extend());
+        }
+
+        dynamodb
+            .update_comment(&comment, input.body)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())
+    }
+
+    /// Comments can be deleted by their author or by the owner of the todo.
+    async fn comment_delete(&self, ctx: &Context<'_>, id: ID) -> Result<Comment> {
+        check_id_kind(&id, COMMENT_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (comment, role) = get_comment_with_role(dynamodb, principal, &id).await?;
+        if comment.author != principal.subject && !role.grants(TodoListRole::Owner) {
+            return Err(Errors::Forbidden.extend());
+        }
+
+        dynamodb
+            .delete_comment(&comment)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())
+    }
+}
+
+/// Comments are only visible to the users who can see their todo.
+async fn get_comment_with_role(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+) -> Result<(Comment, TodoListRole)> {
+    let comment = dynamodb
+        .get_comment(id)
+        .await?
+        .ok_or_else(|| Errors::NotFound.extend())?;
+    let (_, _, role) = get_todo_with_role(dynamodb, principal, &comment.todo_id)
+        .await?
+        .ok_or_else(|| Errors::NotFound.extend())?;
+    Ok((comment, role))
+}
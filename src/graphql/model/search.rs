@@ -0,0 +1,12 @@
+use async_graphql::Union;
+
+use crate::graphql::model::{Todo, TodoList};
+
+pub mod query;
+
+/// Item matching a full-text search.
+#[derive(Union)]
+pub enum SearchResult {
+    Todo(Box<Todo>),
+    TodoList(TodoList),
+}
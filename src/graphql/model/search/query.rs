@@ -0,0 +1,90 @@
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{Context, ErrorExtensions, Object, Result};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::{principal, Errors};
+use crate::graphql::limits::COLLECTION_COMPLEXITY;
+use crate::graphql::model::todo::bulk::BULK_CONCURRENCY;
+use crate::graphql::model::todo::extensions::DynamoTableTodoExt;
+use crate::graphql::model::todo_list::extensions::DynamoTableTodoListExt;
+use crate::graphql::model::{TODO_LIST_TYPE_NAME, TODO_TYPE_NAME};
+use crate::graphql::pagination::page_size;
+use crate::graphql::search::{search, MAX_SEARCH_OFFSET};
+use crate::graphql::types::Owner;
+
+use super::SearchResult;
+
+#[derive(Default)]
+pub struct SearchQuery;
+
+#[Object]
+impl SearchQuery {
+    /// Todos and todo lists of the owner matching the query, most relevant first. The query
+    /// searches the titles, notes and tags, and supports phrases such as `"release notes"`. The
+    /// cursor is the rank of a result.
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        first: Option<usize>,
+        after: Option<String>,
+    ) -> Result<Connection<String, SearchResult>> {
+        let owner = Owner::from(principal(ctx)?);
+        let limit = page_size(first)?;
+        if query.trim().is_empty() {
+            return Err(Errors::InvalidValue("The query can't be empty".to_string()).extend());
+        }
+        let offset = match &after {
+            Some(after) => after.parse::<usize>().map_err(|_| {
+                Errors::InvalidValue(format!("Invalid cursor '{}'", after)).extend()
+            })?,
+            None => 0,
+        };
+        if offset > MAX_SEARCH_OFFSET {
+            return Err(Errors::InvalidValue(format!(
+                "At most {} search results can be skipped",
+                MAX_SEARCH_OFFSET
+            ))
+            .extend());
+        }
+        // One more result to know whether there is a next page.
+        let mut ids = search(ctx)
+            .search(&owner, &query, offset, limit + 1)
+            .await?;
+        let has_next_page = ids.len() > limit;
+        ids.truncate(limit);
+
+        // The index may lag behind the table, so the results are read again, concurrently but
+        // in the order of their rank, and the stale ones skipped.
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let owner = &owner;
+        let results: Vec<Result<Option<SearchResult>>> = stream::iter(ids)
+            .map(|id| async move {
+                Ok(if id.has_type_name(TODO_TYPE_NAME) {
+                    dynamodb
+                        .get_todo(owner, &id)
+                        .await?
+                        .map(|(_, todo)| SearchResult::Todo(Box::new(todo)))
+                } else if id.has_type_name(TODO_LIST_TYPE_NAME) {
+                    dynamodb
+                        .get_todo_list(owner, &id)
+                        .await?
+                        .map(SearchResult::TodoList)
+                } else {
+                    None
+                })
+            })
+            .buffered(BULK_CONCURRENCY)
+            .collect()
+            .await;
+        let mut connection = Connection::new(after.is_some(), has_next_page);
+        for (rank, result) in (offset + 1..).zip(results) {
+            if let Some(result) = result? {
+                connection.edges.push(Edge::new(rank.to_string(), result));
+            }
+        }
+        Ok(connection)
+    }
+}
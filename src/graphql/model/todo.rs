@@ -1,34 +1,182 @@
-use async_graphql::{ComplexObject, Context, Result, SimpleObject};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use async_graphql::connection::Connection;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use chrono::{DateTime, Utc};
 
 use crate::dynamodb::DynamoTable;
+use crate::graphql::limits::{COLLECTION_COMPLEXITY, RELATION_COMPLEXITY};
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::comment::extensions::DynamoTableCommentExt;
+use crate::graphql::model::todo::extensions::DynamoTableTodoExt;
+use crate::graphql::model::todo::recurrence::Recurrence;
 use crate::graphql::model::todo_list::extensions::DynamoTableTodoListExt;
-use crate::graphql::model::TodoList;
-use crate::graphql::types::ID;
+use crate::graphql::model::{AuditRecord, Comment, TodoList};
+use crate::graphql::pagination::{build_connection, page_size};
+use crate::graphql::types::{Owner, ID};
 
+pub mod bulk;
+pub mod effects;
 pub mod extensions;
+pub mod hierarchy;
 pub mod mutation;
+pub mod position;
 pub mod query;
+pub mod recurrence;
+pub mod reminder;
+pub mod subscription;
+pub mod tags;
 
 pub const TODO_TYPE_NAME: &str = "todo";
 
-#[derive(Debug, SimpleObject)]
+/// Completed todos archived within their list keep their id, but are stored under this type name.
+pub const ARCHIVED_TODO_TYPE_NAME: &str = "archived_todo";
+
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TodoPriority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl TodoPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoPriority::Low => "LOW",
+            TodoPriority::Medium => "MEDIUM",
+            TodoPriority::High => "HIGH",
+            TodoPriority::Urgent => "URGENT",
+        }
+    }
+}
+
+impl FromStr for TodoPriority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "LOW" => Ok(TodoPriority::Low),
+            "MEDIUM" => Ok(TodoPriority::Medium),
+            "HIGH" => Ok(TodoPriority::High),
+            "URGENT" => Ok(TodoPriority::Urgent),
+            _ => Err(anyhow!("Invalid priority '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
 #[graphql(complex)]
 pub struct Todo {
     pub id: ID,
     pub title: String,
     pub complete: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    /// The owner is notified once this date is reached, unless the todo is completed before.
+    pub reminder_at: Option<DateTime<Utc>>,
+    /// Set once the reminder was sent, and cleared whenever `reminderAt` changes.
+    pub reminder_sent_at: Option<DateTime<Utc>>,
+    pub priority: Option<TodoPriority>,
+    /// Free text, formatted in Markdown.
+    pub notes: Option<String>,
+    /// Whether the todo is completed once all its children are.
+    pub auto_complete: bool,
+    pub tags: Vec<String>,
+    /// Completing a recurring todo creates its next occurrence.
+    pub recurrence: Option<Recurrence>,
+    /// Set once the next occurrence of a recurring todo was created, so it's created only once
+    /// even if the todo is completed again.
+    #[graphql(skip)]
+    pub next_occurrence_id: Option<ID>,
+    /// Set while the todo is in the trash.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Time of the last audited write, missing for the todos not written since it's stored. It's
+    /// only read from the table, so it isn't updated on the todos returned by a write.
+    #[graphql(skip)]
+    pub updated_at: Option<DateTime<Utc>>,
     #[graphql(skip)]
     pub list_id: Option<ID>,
+    #[graphql(skip)]
+    pub parent_id: Option<ID>,
+    /// Rank of the todo within its list, only the order matters.
+    #[graphql(skip)]
+    pub position: f64,
+    #[graphql(skip)]
+    pub owner: Owner,
 }
 
 #[ComplexObject]
 impl Todo {
+    #[graphql(complexity = "RELATION_COMPLEXITY + child_complexity")]
     async fn list(&self, ctx: &Context<'_>) -> Result<Option<TodoList>> {
         if let Some(id) = self.list_id.clone() {
             let dynamodb = ctx.data_unchecked::<DynamoTable>();
-            dynamodb.get_todo_list(&id).await
+            dynamodb.get_todo_list(&self.owner, &id).await
         } else {
             Ok(None)
         }
     }
+
+    /// Missing if the parent was deleted.
+    #[graphql(complexity = "RELATION_COMPLEXITY + child_complexity")]
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<Todo>> {
+        if let Some(id) = &self.parent_id {
+            let dynamodb = ctx.data_unchecked::<DynamoTable>();
+            Ok(dynamodb
+                .get_todo(&self.owner, id)
+                .await?
+                .map(|(_, todo)| todo))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        dynamodb.get_todo_children(&self.owner, &self.id).await
+    }
+
+    /// Comments in creation order, the cursor being the id of a comment.
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn comments(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<ID>,
+    ) -> Result<Connection<String, Comment>> {
+        let limit = page_size(first)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (comments, has_next_page) = dynamodb
+            .get_todo_comments(&self.id, after.as_ref(), limit)
+            .await?;
+        Ok(build_connection(
+            comments,
+            after.is_some(),
+            has_next_page,
+            |comment| String::from(&comment.id),
+        ))
+    }
+
+    /// Changes of the todo in chronological order, the cursor being the id of a record.
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<ID>,
+    ) -> Result<Connection<String, AuditRecord>> {
+        let limit = page_size(first)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (records, has_next_page) = dynamodb
+            .get_audit_records(&self.id, after.as_ref(), limit)
+            .await?;
+        Ok(build_connection(
+            records,
+            after.is_some(),
+            has_next_page,
+            |record| String::from(&record.id),
+        ))
+    }
 }
@@ -0,0 +1,89 @@
+use async_graphql::{Error, ErrorExtensions, Result, SimpleObject, Union, Value};
+
+use crate::graphql::errors::Errors;
+use crate::graphql::types::ID;
+
+use super::Todo;
+
+/// Maximum number of todos of a bulk mutation.
+pub const MAX_BULK_TODOS: usize = 100;
+
+/// Number of todos written concurrently by the bulk mutations which aren't atomic.
+pub const BULK_CONCURRENCY: usize = 10;
+
+/// Result of a todo of a bulk mutation, in the same order as the inputs.
+#[derive(Union)]
+pub enum TodoResult {
+    Todo(Box<Todo>),
+    TodoError(TodoError),
+}
+
+impl TodoResult {
+    /// `id` identifies the todo in the error when there's no todo to return.
+    pub fn new(index: usize, id: Option<ID>, result: Result<Todo>) -> TodoResult {
+        match result {
+            Ok(todo) => TodoResult::Todo(Box::new(todo)),
+            Err(err) => TodoResult::TodoError(TodoError::new(index, id, err)),
+        }
+    }
+}
+
+/// Failure of a single todo of a bulk mutation, with the same code as the error of the mutation
+/// of a single todo.
+#[derive(Debug, SimpleObject)]
+pub struct TodoError {
+    /// Position of the todo in the inputs, or among the moved todos.
+    index: usize,
+    /// Missing for the todos which couldn't be created.
+    id: Option<ID>,
+    code: String,
+    message: String,
+    details: Option<String>,
+}
+
+impl TodoError {
+    pub fn new(index: usize, id: Option<ID>, err: Error) -> TodoError {
+        let extension = |name: &str| match err.extensions.as_ref().and_then(|e| e.get(name)) {
+            Some(Value::String(value)) => Some(value.clone()),
+            _ => None,
+        };
+        TodoError {
+            index,
+            id,
+            // Failures of DynamoDB don't have any code.
+            code: extension("code").unwrap_or_else(|| "INTERNAL_ERROR".to_string()),
+            details: extension("details"),
+            message: err.message,
+        }
+    }
+}
+
+/// Bulk mutations are limited, as every todo requires at least one write.
+pub fn check_bulk_size(len: usize) -> Result<()> {
+    if len > MAX_BULK_TODOS {
+        Err(Errors::InvalidValue(format!(
+            "Bulk mutations can't change more than {} todos",
+            MAX_BULK_TODOS
+        ))
+        .extend())
+    } else {
+        Ok(())
+    }
+}
+
+/// A todo can only be changed once by a bulk mutation, later occurrences fail.
+pub fn check_duplicates(ids: &[ID]) -> Vec<Result<()>> {
+    ids.iter()
+        .enumerate()
+        .map(|(index, id)| {
+            if ids[..index].contains(id) {
+                Err(
+                    Errors::InvalidValue(format!("Todo '{}' can't be changed more than once", id))
+                        .extend(),
+                )
+            } else {
+                Ok(())
+            }
+        })
+        .collect()
+}
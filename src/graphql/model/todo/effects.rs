@@ -0,0 +1,54 @@
+use async_graphql::{Context, ErrorExtensions, Result};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::webhook::WebhookEvent;
+use crate::graphql::search::search;
+use crate::graphql::webhooks::webhooks;
+
+use super::extensions::TodoWrite;
+use super::hierarchy::auto_complete_parents;
+use super::Todo;
+
+/// Completes the parents of a written todo, publishes its events and indexes it, once the write
+/// succeeded. None of this is part of the write, even an atomic one, so the todo stays written
+/// even if its parents couldn't be completed, which is returned as a `PARENTS_NOT_COMPLETED`
+/// error for the caller to report along with the todo.
+pub async fn todo_written(ctx: &Context<'_>, write: &TodoWrite, actor: &str) -> Result<()> {
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let webhooks = webhooks(ctx);
+    let search = search(ctx);
+    match write {
+        TodoWrite::Create(todo) => {
+            let completed = complete_parents(dynamodb, todo, actor).await;
+            webhooks.todo_event(WebhookEvent::TodoCreated, todo);
+            search.index_todo(todo);
+            completed
+        }
+        TodoWrite::Update(update) => {
+            let todo = &update.new_todo;
+            let completed = complete_parents(dynamodb, todo, actor).await;
+            webhooks.todo_event(WebhookEvent::TodoUpdated, todo);
+            search.index_todo(todo);
+            if todo.complete && !update.old_todo.complete {
+                webhooks.todo_event(WebhookEvent::TodoCompleted, todo);
+            }
+            if let Some(next) = &update.next {
+                webhooks.todo_event(WebhookEvent::TodoCreated, next);
+                search.index_todo(next);
+            }
+            completed
+        }
+        TodoWrite::Delete(_, todo) => {
+            webhooks.todo_event(WebhookEvent::TodoDeleted, todo);
+            search.remove(&todo.id);
+            Ok(())
+        }
+    }
+}
+
+async fn complete_parents(dynamodb: &DynamoTable, todo: &Todo, actor: &str) -> Result<()> {
+    auto_complete_parents(dynamodb, todo, actor)
+        .await
+        .map_err(|err| Errors::ParentsNotCompleted(err.message).extend())
+}
@@ -1,139 +1,676 @@
 use std::convert::identity;
 
-use async_graphql::Result;
-use aws_sdk_dynamodb::model::{AttributeValue, ReturnValue};
-use tokio_stream::StreamExt;
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{ErrorExtensions, Result};
+use aws_sdk_dynamodb::model::update::Builder as UpdateBuilder;
+use aws_sdk_dynamodb::model::{AttributeValue, TransactWriteItem};
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
 
-use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes};
-use crate::graphql::types::ID;
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::item::datetime_attribute;
+use crate::dynamodb::table::MAX_TRANSACTION_ITEMS;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes, UpdateExpression};
+use crate::graphql::errors::Errors;
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::audit::{AuditOperation, Changes};
+use crate::graphql::model::comment::extensions::DynamoTableCommentExt;
+use crate::graphql::model::todo_list::counters::TodoCounts;
+use crate::graphql::model::trash::{
+    filter_trash, set_deleted_at, transact_expiration, trash_expiration,
+};
+use crate::graphql::model::AuditRecord;
+use crate::graphql::types::{Owner, ID};
 use crate::graphql::Key;
 
-use super::{Todo, TODO_TYPE_NAME};
+use super::bulk::BULK_CONCURRENCY;
+use super::reminder::reminder_index_keys;
+use super::tags::{tag_key, tag_prefix};
+use super::{Todo, ARCHIVED_TODO_TYPE_NAME, TODO_TYPE_NAME};
+
+/// Todos moved to the trash along with their list are only restored with it.
+const NOT_DELETED_WITH_LIST: &str = "attribute_not_exists(deleted_with_list)";
+
+/// Filters applied on the todos of an owner.
+#[derive(Debug, Default)]
+pub struct TodoFilter {
+    pub due_before: Option<DateTime<Utc>>,
+    /// Incomplete todos whose due date is in the past.
+    pub overdue: Option<bool>,
+}
+
+/// Change of a todo, along with the todo created in the same transaction such as the next
+/// occurrence of a recurring todo.
+pub struct TodoUpdate {
+    pub old_key: Key,
+    pub old_todo: Todo,
+    pub new_todo: Todo,
+    pub next: Option<Todo>,
+}
+
+/// Write of a bulk mutation, applied along with the others in a single transaction.
+pub enum TodoWrite {
+    Create(Todo),
+    Update(Box<TodoUpdate>),
+    Delete(Key, Todo),
+}
+
+impl TodoWrite {
+    /// Todo as written, the new version of an updated todo.
+    pub fn todo(&self) -> &Todo {
+        match self {
+            TodoWrite::Create(todo) | TodoWrite::Delete(_, todo) => todo,
+            TodoWrite::Update(update) => &update.new_todo,
+        }
+    }
+}
 
 /// Extension used to decorate the DynamoTable with specialized methods for Todo
 #[async_trait::async_trait]
 pub trait DynamoTableTodoExt {
-    async fn scan_todo(&self) -> Result<Vec<Todo>>;
-    async fn get_todo(&self, id: &ID) -> Result<Option<(Key, Todo)>>;
-    async fn put_todo(&self, todo: &Todo) -> Result<bool>;
-    async fn update_todo(
+    async fn scan_todo(&self, owner: &Owner, filter: &TodoFilter) -> Result<Vec<Todo>>;
+    async fn get_todo(&self, owner: &Owner, id: &ID) -> Result<Option<(Key, Todo)>>;
+    async fn scan_deleted_todo(&self, owner: &Owner) -> Result<Vec<Todo>>;
+    async fn get_deleted_todo(&self, owner: &Owner, id: &ID) -> Result<Option<(Key, Todo)>>;
+    async fn put_todo(&self, todo: &Todo, actor: &str) -> Result<bool>;
+    /// Creates the todos in transactional chunks, each todo along with its audit record and tags,
+    /// so a chunk may fail while the others are written. Returns the result of each todo, in the
+    /// same order.
+    async fn put_todos(&self, todos: &[Todo], actor: &str) -> Result<Vec<Result<()>>>;
+    /// Returns the key and the todo once updated, or `None` if the todo doesn't belong to the
+    /// owner anymore.
+    async fn update_todo(&self, update: &TodoUpdate, actor: &str) -> Result<Option<(Key, Todo)>>;
+    /// Applies the updates in transactional chunks, so a chunk may fail while the others are
+    /// written. Returns the result of each update, in the same order.
+    async fn update_todos(&self, updates: &[TodoUpdate], actor: &str) -> Vec<Result<()>>;
+    /// Same as `update_todos`, but returns the result of each chunk along with its number of
+    /// updates, in the same order.
+    async fn update_todo_chunks(
+        &self,
+        updates: &[TodoUpdate],
+        actor: &str,
+    ) -> Vec<(usize, Result<()>)>;
+    async fn delete_todo(&self, owner: &Owner, id: &ID, actor: &str) -> Result<Option<Todo>>;
+    /// Items written by a write, as a transaction is limited to `MAX_TRANSACTION_ITEMS`.
+    fn transact_todo_write(&self, write: &TodoWrite, actor: &str) -> Vec<TransactWriteItem>;
+    /// Applies all the writes within a single transaction and returns the written todos, or
+    /// `None` if any of their conditions failed, in which case nothing was written.
+    async fn transact_todo_writes(
         &self,
-        old_key: Key,
-        old_todo: Todo,
-        new_todo: Todo,
-    ) -> Result<(Key, Todo)>;
-    async fn delete_todo(&self, id: &ID) -> Result<Option<Todo>>;
+        writes: &[TodoWrite],
+        actor: &str,
+    ) -> Result<Option<Vec<Todo>>>;
+    async fn restore_todo(&self, owner: &Owner, id: &ID, actor: &str) -> Result<Option<Todo>>;
+    /// Moves the todos of a list to the trash along with it, or restores them when `deleted_at`
+    /// is missing. Returns the moved todos, archived ones excepted.
+    async fn set_todo_list_todos_deleted_at(
+        &self,
+        todo_list_id: &ID,
+        deleted_at: Option<&DateTime<Utc>>,
+    ) -> Result<Vec<Todo>>;
+    async fn update_todo_positions(&self, positions: &[(&Todo, f64)], actor: &str) -> Result<()>;
+    async fn archive_todos(&self, todos: &[Todo], actor: &str) -> Result<()>;
+    async fn get_todo_children(&self, owner: &Owner, id: &ID) -> Result<Vec<Todo>>;
+    async fn get_tagged_todos(&self, owner: &Owner, tag: &str) -> Result<Vec<Todo>>;
+    async fn add_todo_tags(
+        &self,
+        key: &Key,
+        todo: &Todo,
+        tags: &[String],
+        actor: &str,
+    ) -> Result<()>;
+    async fn remove_todo_tags(
+        &self,
+        key: &Key,
+        todo: &Todo,
+        tags: &[String],
+        actor: &str,
+    ) -> Result<()>;
 }
 
 #[async_trait::async_trait]
 impl DynamoTableTodoExt for DynamoTable {
-    async fn scan_todo(&self) -> Result<Vec<Todo>> {
+    async fn scan_todo(&self, owner: &Owner, filter: &TodoFilter) -> Result<Vec<Todo>> {
         let mut todos: Vec<Todo> = Vec::new();
-        let mut paginator = self
-            .scan()
-            .filter_expression("begins_with(#sk, :sk)")
-            .expression_attribute_names("#sk", &self.config.sort_key)
-            .expression_attribute_values(":sk", AttributeValue::S(ID::prefix(TODO_TYPE_NAME)))
+        let mut query = self.query_gsi2_by_prefix(owner, &ID::prefix(TODO_TYPE_NAME));
+
+        let mut conditions: Vec<&str> = vec!["attribute_not_exists(deleted_at)"];
+        if let Some(due_before) = &filter.due_before {
+            conditions.push("due_at < :due_before");
+            query =
+                query.expression_attribute_values(":due_before", datetime_attribute(due_before));
+        }
+        if let Some(overdue) = filter.overdue {
+            conditions.push(if overdue {
+                "(due_at < :now AND complete = :false)"
+            } else {
+                "(attribute_not_exists(due_at) OR due_at >= :now OR complete <> :false)"
+            });
+            query = query
+                .expression_attribute_values(":now", datetime_attribute(&Utc::now()))
+                .expression_attribute_values(":false", AttributeValue::Bool(false));
+        }
+        query = query.filter_expression(conditions.join(" AND "));
+
+        let mut paginator = query.into_paginator().send();
+
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                todos.push(build_todo(self, &key, item)?);
+            }
+        }
+        Ok(todos)
+    }
+
+    async fn get_todo(&self, owner: &Owner, id: &ID) -> Result<Option<(Key, Todo)>> {
+        query_todo(self, owner, id, false).await
+    }
+
+    async fn scan_deleted_todo(&self, owner: &Owner) -> Result<Vec<Todo>> {
+        let mut todos: Vec<Todo> = Vec::new();
+        let query = self.query_gsi2_by_prefix(owner, &ID::prefix(TODO_TYPE_NAME));
+        let mut paginator = filter_trash(self, query, Some(NOT_DELETED_WITH_LIST))
             .into_paginator()
             .send();
-
         while let Some(output) = paginator.next().await {
             for item in output?.items().unwrap_or_default() {
-                let pkey: ID = item.get_from_string(&self.config.gsi1_partition_key)?;
-                let skey: ID = item.get_from_string(&self.config.gsi1_partition_key)?;
-                let list_id = if pkey != skey { Some(pkey) } else { None };
-                let todo = Todo {
-                    id: skey,
-                    title: item.get_string("title")?.clone(),
-                    complete: *item.get_bool("complete")?,
-                    list_id,
-                };
-                todos.push(todo);
+                let key = self.extract_key(item)?;
+                todos.push(build_todo(self, &key, item)?);
             }
         }
         Ok(todos)
     }
 
-    async fn get_todo(&self, id: &ID) -> Result<Option<(Key, Todo)>> {
-        let gsi1_key = Key {
-            partition: id.clone(),
-            sort: id.clone(),
+    async fn get_deleted_todo(&self, owner: &Owner, id: &ID) -> Result<Option<(Key, Todo)>> {
+        query_todo(self, owner, id, true).await
+    }
+
+    async fn put_todo(&self, todo: &Todo, actor: &str) -> Result<bool> {
+        let mut counts = TodoCounts::default();
+        counts.count(None, Some(todo));
+        counts
+            .transact_write(self, transact_create_todo(self, todo, actor))
+            .await?;
+        Ok(true)
+    }
+
+    async fn put_todos(&self, todos: &[Todo], actor: &str) -> Result<Vec<Result<()>>> {
+        // The items of a todo are kept in the same chunk, so a failed todo wasn't written at all.
+        // Counters of the lists are written once per chunk, along with its todos.
+        let mut chunks: Vec<(usize, Vec<TransactWriteItem>, TodoCounts)> = Vec::new();
+        for todo in todos {
+            let items = transact_create_todo(self, todo, actor);
+            let mut counts = TodoCounts::default();
+            counts.count(None, Some(todo));
+            if let Some((count, chunk, chunk_counts)) = chunks.last_mut() {
+                let mut merged = chunk_counts.clone();
+                merged.extend(&counts);
+                if chunk.len() + items.len() + merged.transact_items(self).len()
+                    <= MAX_TRANSACTION_ITEMS
+                {
+                    *count += 1;
+                    chunk.extend(items);
+                    *chunk_counts = merged;
+                    continue;
+                }
+            }
+            chunks.push((1, items, counts));
+        }
+
+        let mut results: Vec<Result<()>> = Vec::with_capacity(todos.len());
+        let total = chunks.len();
+        for (index, (count, items, counts)) in chunks.into_iter().enumerate() {
+            let result: Result<()> = counts.transact_write(self, items).await.map_err(Into::into);
+            match &result {
+                Ok(()) => info!("Created the todos of chunk {}/{}", index + 1, total),
+                Err(err) => warn!(
+                    "Failed to create the todos of chunk {}/{}: {}",
+                    index + 1,
+                    total,
+                    err.message
+                ),
+            }
+            results.extend((0..count).map(|_| result.clone()));
+        }
+        Ok(results)
+    }
+
+    async fn update_todo(&self, update: &TodoUpdate, actor: &str) -> Result<Option<(Key, Todo)>> {
+        let (new_key, items) = match transact_update_todo(self, update, actor) {
+            Some(items) => items,
+            None => return Ok(Some((update.old_key.clone(), update.new_todo.clone()))),
         };
-        let output = self.query_gsi1_get(&gsi1_key, identity).await?;
+        let mut counts = TodoCounts::default();
+        count_update(&mut counts, update);
+        match counts.transact_write(self, items).await {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(None),
+            output => {
+                output?;
+                Ok(Some((new_key, update.new_todo.clone())))
+            }
+        }
+    }
 
-        Ok(if let Some(item) = output.items.unwrap_or_default().pop() {
-            let key = self.extract_key(&item)?;
-            let todo = build_todo(&key, &item)?;
-            Some((key, todo))
-        } else {
-            None
+    async fn update_todos(&self, updates: &[TodoUpdate], actor: &str) -> Vec<Result<()>> {
+        self.update_todo_chunks(updates, actor)
+            .await
+            .into_iter()
+            .flat_map(|(count, result)| (0..count).map(move |_| result.clone()))
+            .collect()
+    }
+
+    async fn update_todo_chunks(
+        &self,
+        updates: &[TodoUpdate],
+        actor: &str,
+    ) -> Vec<(usize, Result<()>)> {
+        // Counters of the lists are written once per chunk, along with its todos.
+        let mut chunks: Vec<(usize, Vec<TransactWriteItem>, TodoCounts)> = Vec::new();
+        for update in updates {
+            let (items, counts) = match transact_update_todo(self, update, actor) {
+                Some((_, items)) => {
+                    let mut counts = TodoCounts::default();
+                    count_update(&mut counts, update);
+                    (items, counts)
+                }
+                None => (Vec::new(), TodoCounts::default()),
+            };
+            if let Some((count, chunk, chunk_counts)) = chunks.last_mut() {
+                let mut merged = chunk_counts.clone();
+                merged.extend(&counts);
+                if chunk.len() + items.len() + merged.transact_items(self).len()
+                    <= MAX_TRANSACTION_ITEMS
+                {
+                    *count += 1;
+                    chunk.extend(items);
+                    *chunk_counts = merged;
+                    continue;
+                }
+            }
+            chunks.push((1, items, counts));
+        }
+
+        let mut results = Vec::with_capacity(chunks.len());
+        let total = chunks.len();
+        for (index, (count, items, counts)) in chunks.into_iter().enumerate() {
+            let result: Result<()> = match counts.transact_write(self, items).await {
+                Ok(()) => Ok(()),
+                // One of the todos of the chunk changed meanwhile.
+                Err(DynamoDbErrors::ConditionalCheckFailed) => Err(Errors::Aborted.extend()),
+                Err(err) => Err(err.into()),
+            };
+            match &result {
+                Ok(()) => info!("Updated the todos of chunk {}/{}", index + 1, total),
+                Err(err) => warn!(
+                    "Failed to update the todos of chunk {}/{}: {}",
+                    index + 1,
+                    total,
+                    err.message
+                ),
+            }
+            results.push((count, result));
+        }
+        results
+    }
+
+    async fn delete_todo(&self, owner: &Owner, id: &ID, actor: &str) -> Result<Option<Todo>> {
+        match self.get_todo(owner, id).await? {
+            Some((key, todo)) => {
+                set_todo_deleted_at(self, &key, &todo, Some(Utc::now()), actor).await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn restore_todo(&self, owner: &Owner, id: &ID, actor: &str) -> Result<Option<Todo>> {
+        match self.get_deleted_todo(owner, id).await? {
+            Some((key, todo)) => set_todo_deleted_at(self, &key, &todo, None, actor).await,
+            None => Ok(None),
+        }
+    }
+
+    fn transact_todo_write(&self, write: &TodoWrite, actor: &str) -> Vec<TransactWriteItem> {
+        let mut counts = TodoCounts::default();
+        count_write(&mut counts, write);
+        let mut items = transact_todo_write(self, write, &Utc::now(), actor).1;
+        items.extend(counts.transact_items(self));
+        items
+    }
+
+    async fn transact_todo_writes(
+        &self,
+        writes: &[TodoWrite],
+        actor: &str,
+    ) -> Result<Option<Vec<Todo>>> {
+        let deleted_at = Utc::now();
+        let mut todos = Vec::with_capacity(writes.len());
+        let mut items: Vec<TransactWriteItem> = Vec::new();
+        let mut counts = TodoCounts::default();
+        for write in writes {
+            let (todo, write_items) = transact_todo_write(self, write, &deleted_at, actor);
+            if !write_items.is_empty() {
+                count_write(&mut counts, write);
+            }
+            todos.push(todo);
+            items.extend(write_items);
+        }
+        match counts.transact_write(self, items).await {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => return Ok(None),
+            output => output?,
+        };
+        // Comments may not fit in the same transaction as the todos.
+        let expires_at = trash_expiration(self, &deleted_at);
+        for write in writes {
+            if let TodoWrite::Delete(_, todo) = write {
+                self.set_todo_comments_expiration(&todo.id, Some(&expires_at))
+                    .await?;
+            }
+        }
+        Ok(Some(todos))
+    }
+
+    async fn get_tagged_todos(&self, owner: &Owner, tag: &str) -> Result<Vec<Todo>> {
+        let mut ids: Vec<ID> = Vec::new();
+        let prefix = tag_prefix(tag);
+        let mut paginator = self
+            .query_partition_by_prefix(owner, &prefix)
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let sort = item.get_string(&self.config.sort_key)?;
+                let id: ID = sort
+                    .strip_prefix(&prefix)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid tag key '{}'", sort))?
+                    .parse()?;
+                ids.push(id);
+            }
+        }
+        // The todos are fetched concurrently, in the order of the index.
+        let todos: Vec<Result<Option<(Key, Todo)>>> = stream::iter(ids)
+            .map(|id| async move { self.get_todo(owner, &id).await })
+            .buffered(BULK_CONCURRENCY)
+            .collect()
+            .await;
+        // Index items could outlive a todo deleted concurrently.
+        Ok(todos
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .map(|(_, todo)| todo)
+            .collect())
+    }
+
+    async fn add_todo_tags(
+        &self,
+        key: &Key,
+        todo: &Todo,
+        tags: &[String],
+        actor: &str,
+    ) -> Result<()> {
+        let tagged = Todo {
+            tags: {
+                let mut tags = [todo.tags.as_slice(), tags].concat();
+                tags.sort();
+                tags
+            },
+            ..todo.clone()
+        };
+        let record = AuditRecord::new(
+            &todo.id,
+            actor,
+            AuditOperation::Update,
+            todo_changes(Some(todo), Some(&tagged)),
+        );
+        self.transact_write(|transaction| {
+            let update = self.transact_update(key, |req| {
+                let mut update = UpdateExpression::default();
+                update.add("tags", AttributeValue::Ss(tags.to_vec()));
+                update.set("updated_at", datetime_attribute(&record.at));
+                update.apply(owner_condition(self, &todo.owner, req))
+            });
+            tags.iter().fold(
+                transaction
+                    .transact_items(update)
+                    .transact_items(self.transact_put_audit(&record)),
+                |transaction, tag| {
+                    transaction.transact_items(
+                        self.transact_put(&tag_key(&todo.owner, tag, &todo.id), identity),
+                    )
+                },
+            )
         })
+        .await?;
+        Ok(())
     }
 
-    async fn put_todo(&self, todo: &Todo) -> Result<bool> {
-        let key = Key {
-            partition: todo.list_id.clone().unwrap_or_else(|| todo.id.clone()),
-            sort: todo.id.clone(),
+    async fn remove_todo_tags(
+        &self,
+        key: &Key,
+        todo: &Todo,
+        tags: &[String],
+        actor: &str,
+    ) -> Result<()> {
+        let tagged = Todo {
+            tags: todo
+                .tags
+                .iter()
+                .filter(|tag| !tags.contains(tag))
+                .cloned()
+                .collect(),
+            ..todo.clone()
         };
-        self.put_item(&key, |put| {
-            put.item("title", AttributeValue::S(todo.title.clone()))
-                .item("complete", AttributeValue::Bool(todo.complete))
-                // Even if associated with a todo_list, we can retrieve it directly through
-                // the secondary index.
-                .item(
-                    &self.config.gsi1_partition_key,
-                    AttributeValue::S(String::from(&todo.id)),
-                )
-                .item(
-                    &self.config.gsi1_sort_key,
-                    AttributeValue::S(String::from(&todo.id)),
-                )
+        let record = AuditRecord::new(
+            &todo.id,
+            actor,
+            AuditOperation::Update,
+            todo_changes(Some(todo), Some(&tagged)),
+        );
+        self.transact_write(|transaction| {
+            let update = self.transact_update(key, |req| {
+                let mut update = UpdateExpression::default();
+                update.delete("tags", AttributeValue::Ss(tags.to_vec()));
+                update.set("updated_at", datetime_attribute(&record.at));
+                update.apply(owner_condition(self, &todo.owner, req))
+            });
+            tags.iter().fold(
+                transaction
+                    .transact_items(update)
+                    .transact_items(self.transact_put_audit(&record)),
+                |transaction, tag| {
+                    transaction.transact_items(
+                        self.transact_delete(&tag_key(&todo.owner, tag, &todo.id), identity),
+                    )
+                },
+            )
         })
         .await?;
-        Ok(true)
+        Ok(())
+    }
+
+    async fn get_todo_children(&self, owner: &Owner, id: &ID) -> Result<Vec<Todo>> {
+        let mut todos: Vec<Todo> = Vec::new();
+        let mut paginator = self
+            .query_gsi3_by_prefix(id, &ID::prefix(TODO_TYPE_NAME))
+            .filter_expression("#owner = :owner AND attribute_not_exists(deleted_at)")
+            .expression_attribute_names("#owner", &self.config.gsi2_partition_key)
+            .expression_attribute_values(":owner", AttributeValue::S(owner.to_string()))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                todos.push(build_todo(self, &key, item)?);
+            }
+        }
+        Ok(todos)
     }
 
-    async fn update_todo(
+    /// The todos are marked as deleted with their list, so they're restored with it but can't be
+    /// restored on their own. Todos already in the trash are left untouched, and archived todos
+    /// only expire with the list. Each chunk is atomic, and the todos aren't audited as the list
+    /// is.
+    async fn set_todo_list_todos_deleted_at(
         &self,
-        old_key: Key,
-        old_todo: Todo,
-        new_todo: Todo,
-    ) -> Result<(Key, Todo)> {
-        if old_todo.list_id == new_todo.list_id {
-            update_todo_inplace(self, old_key, old_todo, new_todo).await
-        } else {
-            let new_key = Key {
-                partition: new_todo
-                    .list_id
-                    .clone()
-                    .unwrap_or_else(|| old_todo.id.clone()),
-                sort: old_key.sort.clone(),
-            };
-            move_todo(self, old_key, new_key, new_todo).await
+        todo_list_id: &ID,
+        deleted_at: Option<&DateTime<Utc>>,
+    ) -> Result<Vec<Todo>> {
+        let expires_at = deleted_at.map(|deleted_at| trash_expiration(self, deleted_at));
+        let mut todos: Vec<(Key, Todo)> = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(todo_list_id, &ID::prefix(TODO_TYPE_NAME))
+            .filter_expression(if deleted_at.is_some() {
+                "attribute_not_exists(deleted_at)"
+            } else {
+                "attribute_exists(deleted_with_list)"
+            })
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                let todo = build_todo(self, &key, item)?;
+                todos.push((key, todo));
+            }
+        }
+        let mut archived: Vec<(Key, Todo)> = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(todo_list_id, &ID::prefix(ARCHIVED_TODO_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                let todo = build_todo(self, &key, item)?;
+                archived.push((key, todo));
+            }
         }
+
+        // The items of a todo are kept in the same chunk.
+        let mut chunks: Vec<Vec<TransactWriteItem>> = vec![Vec::new()];
+        for (key, todo) in &todos {
+            let mut items = vec![self.transact_update(key, |req| {
+                let mut update = UpdateExpression::default();
+                set_deleted_at(self, &mut update, deleted_at);
+                update.set_or_remove(
+                    "deleted_with_list",
+                    deleted_at.map(|_| AttributeValue::Bool(true)),
+                );
+                update.apply(
+                    // Otherwise a todo purged in the meantime would be recreated.
+                    req.condition_expression("attribute_exists(#pk)")
+                        .expression_attribute_names("#pk", &self.config.partition_key),
+                )
+            })];
+            for tag in &todo.tags {
+                items.push(transact_expiration(
+                    self,
+                    &tag_key(&todo.owner, tag, &todo.id),
+                    expires_at.as_ref(),
+                ));
+            }
+            push_chunked(&mut chunks, items);
+        }
+        for (key, _) in &archived {
+            push_chunked(
+                &mut chunks,
+                vec![transact_expiration(self, key, expires_at.as_ref())],
+            );
+        }
+        for chunk in chunks.into_iter().filter(|chunk| !chunk.is_empty()) {
+            self.transact_write(|transaction| transaction.set_transact_items(Some(chunk)))
+                .await?;
+        }
+        // Comments may not fit in the same transaction as the todos.
+        for (_, todo) in todos.iter().chain(&archived) {
+            self.set_todo_comments_expiration(&todo.id, expires_at.as_ref())
+                .await?;
+        }
+        Ok(todos
+            .into_iter()
+            .map(|(_, todo)| Todo {
+                deleted_at: deleted_at.cloned(),
+                ..todo
+            })
+            .collect())
     }
 
-    async fn delete_todo(&self, id: &ID) -> Result<Option<Todo>> {
-        let gsi1_key = Key {
-            partition: id.clone(),
-            sort: id.clone(),
-        };
-        let query_output = self.query_gsi1_get(&gsi1_key, identity).await?;
-        Ok(
-            if let Some(item) = query_output.items.unwrap_or_default().pop() {
-                let key = self.extract_key(&item)?;
-                let delete_output = self
-                    .delete_item(&key, |req| req.return_values(ReturnValue::AllOld))
-                    .await?;
-                if let Some(item) = delete_output.attributes {
-                    Some(build_todo(&key, &item)?)
-                } else {
-                    None
+    async fn update_todo_positions(&self, positions: &[(&Todo, f64)], actor: &str) -> Result<()> {
+        let changed: Vec<_> = positions
+            .iter()
+            .filter(|(todo, position)| todo.position != *position)
+            .collect();
+        // Each chunk is atomic, but a rebalance of a large list isn't. Every todo requires an
+        // audit record within the same transaction.
+        for chunk in changed.chunks(MAX_TRANSACTION_ITEMS / 2) {
+            let records: Vec<AuditRecord> = chunk
+                .iter()
+                .map(|(todo, position)| {
+                    let moved = Todo {
+                        position: *position,
+                        ..(*todo).clone()
+                    };
+                    AuditRecord::new(
+                        &todo.id,
+                        actor,
+                        AuditOperation::Update,
+                        todo_changes(Some(todo), Some(&moved)),
+                    )
+                })
+                .collect();
+            self.transact_write(|transaction| {
+                chunk.iter().zip(&records).fold(
+                    transaction,
+                    |transaction, ((todo, position), record)| {
+                        let key = Key {
+                            partition: todo.list_id.clone().unwrap_or_else(|| todo.id.clone()),
+                            sort: todo.id.clone(),
+                        };
+                        transaction
+                            .transact_items(self.transact_update(&key, |req| {
+                                let mut update = UpdateExpression::default();
+                                update.set("position", AttributeValue::N(position.to_string()));
+                                update.set("updated_at", datetime_attribute(&record.at));
+                                update.apply(owner_condition(self, &todo.owner, req))
+                            }))
+                            .transact_items(self.transact_put_audit(record))
+                    },
+                )
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Archived todos are moved under another sort key prefix of their list, so the queries on
+    /// todos skip them, and the index items of their tags are deleted. Each chunk is atomic, and
+    /// only completed todos can be archived.
+    async fn archive_todos(&self, todos: &[Todo], actor: &str) -> Result<()> {
+        // The items of a todo are kept in the same chunk, along with the counters of its list.
+        let mut chunks: Vec<(Vec<TransactWriteItem>, TodoCounts)> = Vec::new();
+        for todo in todos {
+            let items = transact_archive_todo(self, todo, actor);
+            let mut counts = TodoCounts::default();
+            counts.count(Some(todo), None);
+            if let Some((chunk, chunk_counts)) = chunks.last_mut() {
+                let mut merged = chunk_counts.clone();
+                merged.extend(&counts);
+                if chunk.len() + items.len() + merged.transact_items(self).len()
+                    <= MAX_TRANSACTION_ITEMS
+                {
+                    chunk.extend(items);
+                    *chunk_counts = merged;
+                    continue;
                 }
-            } else {
-                None
-            },
-        )
+            }
+            chunks.push((items, counts));
+        }
+        for (items, counts) in chunks {
+            counts.transact_write(self, items).await?;
+        }
+        Ok(())
     }
 }
 
@@ -141,87 +678,620 @@ impl DynamoTableTodoExt for DynamoTable {
 // utilities
 //
 
-async fn move_todo(
+/// Audited field of a todo, formatted as a string.
+type TodoField = (&'static str, fn(&Todo) -> Option<String>);
+
+/// Fields of a todo which differ, a missing todo having none of them.
+fn todo_changes(old: Option<&Todo>, new: Option<&Todo>) -> Changes {
+    let fields: [TodoField; 13] = [
+        ("title", |todo| Some(todo.title.clone())),
+        ("complete", |todo| Some(todo.complete.to_string())),
+        ("dueAt", |todo| {
+            todo.due_at.map(|due_at| due_at.to_rfc3339())
+        }),
+        ("reminderAt", |todo| {
+            todo.reminder_at.map(|reminder_at| reminder_at.to_rfc3339())
+        }),
+        ("priority", |todo| {
+            todo.priority.map(|priority| priority.as_str().to_string())
+        }),
+        ("notes", |todo| todo.notes.clone()),
+        ("autoComplete", |todo| Some(todo.auto_complete.to_string())),
+        ("list", |todo| todo.list_id.as_ref().map(String::from)),
+        ("parent", |todo| todo.parent_id.as_ref().map(String::from)),
+        ("tags", |todo| {
+            Some(todo.tags.join(",")).filter(|tags| !tags.is_empty())
+        }),
+        ("recurrence", |todo| {
+            todo.recurrence.map(|recurrence| recurrence.to_string())
+        }),
+        ("position", |todo| Some(todo.position.to_string())),
+        ("deletedAt", |todo| {
+            todo.deleted_at.map(|deleted_at| deleted_at.to_rfc3339())
+        }),
+    ];
+    let mut changes = Changes::default();
+    for (field, value) in fields {
+        changes.diff(field, old.and_then(value), new.and_then(value));
+    }
+    changes
+}
+
+/// Items written when creating a todo: the todo itself, its audit record and the index items of
+/// its tags.
+fn transact_create_todo(
     dynamodb: &DynamoTable,
-    old_key: Key,
-    new_key: Key,
-    new_todo: Todo,
-) -> Result<(Key, Todo)> {
-    dynamodb
-        .transact_write(|transaction| {
-            transaction
-                .transact_items(dynamodb.transact_delete(&old_key, identity))
-                .transact_items(dynamodb.transact_put(&new_key, |put| {
-                    put.item("title", AttributeValue::S(new_todo.title.clone()))
-                        .item("complete", AttributeValue::Bool(new_todo.complete))
-                        .item(
-                            &dynamodb.config.gsi1_partition_key,
-                            AttributeValue::S(String::from(&new_todo.id)),
-                        )
-                        .item(
-                            &dynamodb.config.gsi1_sort_key,
-                            AttributeValue::S(String::from(&new_todo.id)),
-                        )
-                }))
-        })
-        .await?;
-    Ok((new_key, new_todo))
+    todo: &Todo,
+    actor: &str,
+) -> Vec<TransactWriteItem> {
+    let key = Key {
+        partition: todo.list_id.clone().unwrap_or_else(|| todo.id.clone()),
+        sort: todo.id.clone(),
+    };
+    let record = AuditRecord::new(
+        &todo.id,
+        actor,
+        AuditOperation::Create,
+        todo_changes(None, Some(todo)),
+    );
+    let mut items = vec![
+        dynamodb.transact_put(&key, |put| {
+            todo_attributes(dynamodb, todo, &record.at)
+                .into_iter()
+                .fold(put, |put, (name, value)| put.item(name, value))
+        }),
+        dynamodb.transact_put_audit(&record),
+    ];
+    for tag in &todo.tags {
+        items.push(dynamodb.transact_put(&tag_key(&todo.owner, tag, &todo.id), identity));
+    }
+    items
 }
 
-async fn update_todo_inplace(
+/// Items written by a write of a bulk mutation, along with the written todo. Deleted todos are
+/// marked with `deleted_at`.
+fn transact_todo_write(
     dynamodb: &DynamoTable,
-    key: Key,
-    old_todo: Todo,
-    new_todo: Todo,
-) -> Result<(Key, Todo)> {
-    let resp = dynamodb
-        .update_item(&key, |mut req| {
-            req = req.return_values(ReturnValue::AllNew);
-
-            // Is there a better syntax?
-            let mut update_expression: Vec<&str> = vec![];
-            req = if old_todo.title != new_todo.title {
-                update_expression.push(" title = :title ");
-                req.expression_attribute_values(":title", AttributeValue::S(new_todo.title))
-            } else {
-                req
-            };
+    write: &TodoWrite,
+    deleted_at: &DateTime<Utc>,
+    actor: &str,
+) -> (Todo, Vec<TransactWriteItem>) {
+    match write {
+        TodoWrite::Create(todo) => (todo.clone(), transact_create_todo(dynamodb, todo, actor)),
+        TodoWrite::Update(update) => (
+            update.new_todo.clone(),
+            transact_update_todo(dynamodb, update, actor)
+                .map(|(_, items)| items)
+                .unwrap_or_default(),
+        ),
+        TodoWrite::Delete(key, todo) => {
+            transact_set_todo_deleted_at(dynamodb, key, todo, Some(*deleted_at), actor)
+        }
+    }
+}
+
+/// Counts the todo out of its list, and its new version and next occurrence into their list.
+fn count_update(counts: &mut TodoCounts, update: &TodoUpdate) {
+    counts.count(Some(&update.old_todo), Some(&update.new_todo));
+    counts.count(None, update.next.as_ref());
+}
+
+fn count_write(counts: &mut TodoCounts, write: &TodoWrite) {
+    match write {
+        TodoWrite::Create(todo) => counts.count(None, Some(todo)),
+        TodoWrite::Update(update) => count_update(counts, update),
+        // Deleted todos are marked with `deleted_at`, so they aren't counted anymore.
+        TodoWrite::Delete(_, todo) => counts.count(Some(todo), None),
+    }
+}
+
+/// Items written when updating a todo, along with its key once updated. Returns `None` if
+/// nothing changed.
+fn transact_update_todo(
+    dynamodb: &DynamoTable,
+    update: &TodoUpdate,
+    actor: &str,
+) -> Option<(Key, Vec<TransactWriteItem>)> {
+    let TodoUpdate {
+        old_key,
+        old_todo,
+        new_todo,
+        next,
+    } = update;
+    let changes = todo_changes(Some(old_todo), Some(new_todo));
+    if changes.is_empty() {
+        return None;
+    }
+    let record = AuditRecord::new(&old_todo.id, actor, AuditOperation::Update, changes);
+    let mut items = next
+        .as_ref()
+        .map(|next| transact_create_todo(dynamodb, next, actor))
+        .unwrap_or_default();
+    items.push(dynamodb.transact_put_audit(&record));
+    if old_todo.list_id == new_todo.list_id {
+        items.push(transact_update_todo_inplace(
+            dynamodb, old_key, old_todo, new_todo, &record.at,
+        ));
+        Some((old_key.clone(), items))
+    } else {
+        let new_key = Key {
+            partition: new_todo
+                .list_id
+                .clone()
+                .unwrap_or_else(|| old_todo.id.clone()),
+            sort: old_key.sort.clone(),
+        };
+        items.extend(transact_move_todo(
+            dynamodb, old_key, old_todo, &new_key, new_todo, &record.at,
+        ));
+        Some((new_key, items))
+    }
+}
 
-            req = if old_todo.complete != new_todo.complete {
-                update_expression.push(" complete = :complete ");
-                req.expression_attribute_values(
-                    ":complete",
-                    AttributeValue::Bool(new_todo.complete),
+/// Index items of the tags are moved along when the todo changes owner.
+fn transact_move_todo(
+    dynamodb: &DynamoTable,
+    old_key: &Key,
+    old_todo: &Todo,
+    new_key: &Key,
+    new_todo: &Todo,
+    updated_at: &DateTime<Utc>,
+) -> Vec<TransactWriteItem> {
+    let mut items = vec![
+        // Todos in the trash can't be changed.
+        dynamodb.transact_delete(old_key, |req| {
+            req.condition_expression(update_condition(old_todo, new_todo))
+                .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+                .expression_attribute_values(
+                    ":owner",
+                    AttributeValue::S(old_todo.owner.to_string()),
                 )
-            } else {
-                req
-            };
+        }),
+        dynamodb.transact_put(new_key, |put| {
+            todo_attributes(dynamodb, new_todo, updated_at)
+                .into_iter()
+                .fold(put, |put, (name, value)| put.item(name, value))
+        }),
+    ];
+    if old_todo.owner != new_todo.owner {
+        for tag in &new_todo.tags {
+            items.push(
+                dynamodb.transact_delete(&tag_key(&old_todo.owner, tag, &old_todo.id), identity),
+            );
+            items.push(
+                dynamodb.transact_put(&tag_key(&new_todo.owner, tag, &new_todo.id), identity),
+            );
+        }
+    }
+    items
+}
+
+/// Todos in the trash can't be changed.
+fn owner_condition(dynamodb: &DynamoTable, owner: &Owner, req: UpdateBuilder) -> UpdateBuilder {
+    req.condition_expression("#owner = :owner AND attribute_not_exists(deleted_at)")
+        .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+        .expression_attribute_values(":owner", AttributeValue::S(owner.to_string()))
+}
 
-            if !update_expression.is_empty() {
-                req.update_expression("SET ".to_string() + &update_expression.join(" , "))
+/// Items moving a completed todo to the archive of its list, deleting the index items of its
+/// tags so `todosByTag` skips it.
+fn transact_archive_todo(
+    dynamodb: &DynamoTable,
+    todo: &Todo,
+    actor: &str,
+) -> Vec<TransactWriteItem> {
+    let mut changes = Changes::default();
+    changes.diff("archived", Some(false.to_string()), Some(true.to_string()));
+    let record = AuditRecord::new(&todo.id, actor, AuditOperation::Update, changes);
+    let partition = todo.list_id.clone().unwrap_or_else(|| todo.id.clone());
+    let key = Key {
+        partition: partition.clone(),
+        sort: todo.id.clone(),
+    };
+    let archived_key = Key {
+        partition,
+        sort: todo.id.with_type_name(ARCHIVED_TODO_TYPE_NAME),
+    };
+    let mut items = vec![
+        dynamodb.transact_delete(&key, |req| {
+            req.condition_expression(
+                "#owner = :owner AND complete = :true AND attribute_not_exists(deleted_at)",
+            )
+            .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+            .expression_attribute_values(":owner", AttributeValue::S(todo.owner.to_string()))
+            .expression_attribute_values(":true", AttributeValue::Bool(true))
+        }),
+        dynamodb.transact_put(&archived_key, |put| {
+            archived_todo_attributes(dynamodb, todo, &record.at)
+                .into_iter()
+                .fold(put, |put, (name, value)| put.item(name, value))
+        }),
+        dynamodb.transact_put_audit(&record),
+    ];
+    for tag in &todo.tags {
+        items.push(dynamodb.transact_delete(&tag_key(&todo.owner, tag, &todo.id), identity));
+    }
+    items
+}
+
+/// Appends the items of a todo to the last chunk, or to a new one if they don't fit in the same
+/// transaction.
+fn push_chunked(chunks: &mut Vec<Vec<TransactWriteItem>>, items: Vec<TransactWriteItem>) {
+    match chunks.last_mut() {
+        Some(chunk) if chunk.len() + items.len() <= MAX_TRANSACTION_ITEMS => chunk.extend(items),
+        _ => chunks.push(items),
+    }
+}
+
+/// Items of other owners are simply filtered out, so they can't be distinguished from missing
+/// ones. Todos in the trash are only retrieved when `deleted` is set.
+async fn query_todo(
+    dynamodb: &DynamoTable,
+    owner: &Owner,
+    id: &ID,
+    deleted: bool,
+) -> Result<Option<(Key, Todo)>> {
+    let gsi1_key = Key {
+        partition: id.clone(),
+        sort: id.clone(),
+    };
+    let output = dynamodb
+        .query_gsi1_get(&gsi1_key, |query| {
+            let query = query
+                .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+                .expression_attribute_values(":owner", AttributeValue::S(owner.to_string()));
+            if deleted {
+                filter_trash(
+                    dynamodb,
+                    query,
+                    Some(&format!("#owner = :owner AND {}", NOT_DELETED_WITH_LIST)),
+                )
             } else {
-                req
+                query.filter_expression("#owner = :owner AND attribute_not_exists(deleted_at)")
             }
         })
         .await?;
 
-    let item = resp
-        .attributes()
-        .ok_or_else(|| anyhow::anyhow!("Missing attributes"))?;
-    let todo = build_todo(&key, item)?;
-    Ok((key, todo))
+    Ok(if let Some(item) = output.items.unwrap_or_default().pop() {
+        let key = dynamodb.extract_key(&item)?;
+        let todo = build_todo(dynamodb, &key, &item)?;
+        Some((key, todo))
+    } else {
+        None
+    })
+}
+
+/// Moves a todo to the trash, or restores it when `deleted_at` is missing. The index items of its
+/// tags and its comments expire along with it. Returns `None` if the todo doesn't belong to the
+/// owner anymore or was already moved.
+async fn set_todo_deleted_at(
+    dynamodb: &DynamoTable,
+    key: &Key,
+    todo: &Todo,
+    deleted_at: Option<DateTime<Utc>>,
+    actor: &str,
+) -> Result<Option<Todo>> {
+    let (new_todo, items) = transact_set_todo_deleted_at(dynamodb, key, todo, deleted_at, actor);
+    let mut counts = TodoCounts::default();
+    counts.count(Some(todo), Some(&new_todo));
+    match counts.transact_write(dynamodb, items).await {
+        Err(DynamoDbErrors::ConditionalCheckFailed) => return Ok(None),
+        output => output?,
+    };
+    // Comments may not fit in the same transaction as the todo.
+    let expires_at = deleted_at.map(|deleted_at| trash_expiration(dynamodb, &deleted_at));
+    dynamodb
+        .set_todo_comments_expiration(&todo.id, expires_at.as_ref())
+        .await?;
+    Ok(Some(new_todo))
+}
+
+/// Items written when moving a todo to the trash or restoring it, along with the updated todo.
+fn transact_set_todo_deleted_at(
+    dynamodb: &DynamoTable,
+    key: &Key,
+    todo: &Todo,
+    deleted_at: Option<DateTime<Utc>>,
+    actor: &str,
+) -> (Todo, Vec<TransactWriteItem>) {
+    let new_todo = Todo {
+        deleted_at,
+        ..todo.clone()
+    };
+    let operation = if deleted_at.is_some() {
+        AuditOperation::Delete
+    } else {
+        AuditOperation::Restore
+    };
+    let record = AuditRecord::new(
+        &todo.id,
+        actor,
+        operation,
+        todo_changes(Some(todo), Some(&new_todo)),
+    );
+    let expires_at = deleted_at.map(|deleted_at| trash_expiration(dynamodb, &deleted_at));
+
+    let update = dynamodb.transact_update(key, |req| {
+        let mut update = UpdateExpression::default();
+        set_deleted_at(dynamodb, &mut update, deleted_at.as_ref());
+        update.set("updated_at", datetime_attribute(&record.at));
+        let req = if deleted_at.is_some() {
+            owner_condition(dynamodb, &todo.owner, req)
+        } else {
+            req.condition_expression("#owner = :owner AND attribute_exists(deleted_at)")
+                .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+                .expression_attribute_values(":owner", AttributeValue::S(todo.owner.to_string()))
+        };
+        update.apply(req)
+    });
+    let mut items = vec![update, dynamodb.transact_put_audit(&record)];
+    for tag in &todo.tags {
+        items.push(transact_expiration(
+            dynamodb,
+            &tag_key(&todo.owner, tag, &todo.id),
+            expires_at.as_ref(),
+        ));
+    }
+    (new_todo, items)
 }
 
-fn build_todo(key: &Key, item: &RawAttributes) -> Result<Todo> {
+/// Updates only the changed attributes, failing if the todo doesn't belong to the owner anymore.
+fn transact_update_todo_inplace(
+    dynamodb: &DynamoTable,
+    key: &Key,
+    old_todo: &Todo,
+    new_todo: &Todo,
+    updated_at: &DateTime<Utc>,
+) -> TransactWriteItem {
+    let mut update = UpdateExpression::default();
+    update.set("updated_at", datetime_attribute(updated_at));
+    if old_todo.title != new_todo.title {
+        update.set("title", AttributeValue::S(new_todo.title.clone()));
+    }
+    if old_todo.complete != new_todo.complete {
+        update.set("complete", AttributeValue::Bool(new_todo.complete));
+    }
+    if old_todo.due_at != new_todo.due_at {
+        update.set_or_remove("due_at", new_todo.due_at.as_ref().map(datetime_attribute));
+    }
+    if old_todo.reminder_at != new_todo.reminder_at {
+        update.set_or_remove(
+            "reminder_at",
+            new_todo.reminder_at.as_ref().map(datetime_attribute),
+        );
+    }
+    if old_todo.reminder_sent_at != new_todo.reminder_sent_at {
+        update.set_or_remove(
+            "reminder_sent_at",
+            new_todo.reminder_sent_at.as_ref().map(datetime_attribute),
+        );
+    }
+    let index_keys = reminder_index_keys(new_todo);
+    if reminder_index_keys(old_todo) != index_keys {
+        let (bucket, reminder_at) = index_keys.unzip();
+        update.set_or_remove(&dynamodb.config.gsi4_partition_key, bucket);
+        update.set_or_remove(&dynamodb.config.gsi4_sort_key, reminder_at);
+    }
+    if old_todo.priority != new_todo.priority {
+        update.set_or_remove(
+            "priority",
+            new_todo
+                .priority
+                .map(|priority| AttributeValue::S(priority.as_str().to_string())),
+        );
+    }
+    if old_todo.position != new_todo.position {
+        update.set("position", AttributeValue::N(new_todo.position.to_string()));
+    }
+    if old_todo.auto_complete != new_todo.auto_complete {
+        update.set(
+            "auto_complete",
+            AttributeValue::Bool(new_todo.auto_complete),
+        );
+    }
+    if old_todo.parent_id != new_todo.parent_id {
+        let parent = new_todo
+            .parent_id
+            .as_ref()
+            .map(|parent_id| AttributeValue::S(String::from(parent_id)));
+        let id = parent
+            .as_ref()
+            .map(|_| AttributeValue::S(String::from(&new_todo.id)));
+        update.set_or_remove(&dynamodb.config.gsi3_partition_key, parent);
+        update.set_or_remove(&dynamodb.config.gsi3_sort_key, id);
+    }
+    if old_todo.notes != new_todo.notes {
+        update.set_or_remove("notes", new_todo.notes.clone().map(AttributeValue::S));
+    }
+    if old_todo.recurrence != new_todo.recurrence {
+        update.set_or_remove(
+            "recurrence",
+            new_todo
+                .recurrence
+                .map(|recurrence| AttributeValue::S(recurrence.to_string())),
+        );
+    }
+    if old_todo.next_occurrence_id != new_todo.next_occurrence_id {
+        update.set_or_remove(
+            "next_occurrence",
+            new_todo
+                .next_occurrence_id
+                .as_ref()
+                .map(|id| AttributeValue::S(String::from(id))),
+        );
+    }
+
+    dynamodb.transact_update(key, |req| {
+        update.apply(
+            req.condition_expression(update_condition(old_todo, new_todo))
+                .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+                .expression_attribute_values(
+                    ":owner",
+                    AttributeValue::S(old_todo.owner.to_string()),
+                ),
+        )
+    })
+}
+
+/// Updates fail if the todo doesn't belong to the owner anymore or is in the trash. Creating the
+/// next occurrence also fails if another update created it meanwhile.
+fn update_condition(old_todo: &Todo, new_todo: &Todo) -> &'static str {
+    if old_todo.next_occurrence_id.is_none() && new_todo.next_occurrence_id.is_some() {
+        "#owner = :owner AND attribute_not_exists(deleted_at) \
+         AND attribute_not_exists(next_occurrence)"
+    } else {
+        "#owner = :owner AND attribute_not_exists(deleted_at)"
+    }
+}
+
+/// Attributes stored alongside the key of a todo, written at `updated_at` along with its audit
+/// record.
+fn todo_attributes(
+    dynamodb: &DynamoTable,
+    todo: &Todo,
+    updated_at: &DateTime<Utc>,
+) -> RawAttributes {
+    let mut attributes = RawAttributes::from([
+        ("title".to_string(), AttributeValue::S(todo.title.clone())),
+        ("complete".to_string(), AttributeValue::Bool(todo.complete)),
+        // Even if associated with a todo_list, we can retrieve it directly through the
+        // secondary index.
+        (
+            dynamodb.config.gsi1_partition_key.clone(),
+            AttributeValue::S(String::from(&todo.id)),
+        ),
+        (
+            dynamodb.config.gsi1_sort_key.clone(),
+            AttributeValue::S(String::from(&todo.id)),
+        ),
+        (
+            dynamodb.config.gsi2_partition_key.clone(),
+            AttributeValue::S(todo.owner.to_string()),
+        ),
+        (
+            dynamodb.config.gsi2_sort_key.clone(),
+            AttributeValue::S(String::from(&todo.id)),
+        ),
+    ]);
+    attributes.insert(
+        "auto_complete".to_string(),
+        AttributeValue::Bool(todo.auto_complete),
+    );
+    attributes.insert(
+        "position".to_string(),
+        AttributeValue::N(todo.position.to_string()),
+    );
+    attributes.insert("updated_at".to_string(), datetime_attribute(updated_at));
+    // Missing values aren't stored at all.
+    if let Some(due_at) = &todo.due_at {
+        attributes.insert("due_at".to_string(), datetime_attribute(due_at));
+    }
+    if let Some(reminder_at) = &todo.reminder_at {
+        attributes.insert("reminder_at".to_string(), datetime_attribute(reminder_at));
+    }
+    if let Some(reminder_sent_at) = &todo.reminder_sent_at {
+        attributes.insert(
+            "reminder_sent_at".to_string(),
+            datetime_attribute(reminder_sent_at),
+        );
+    }
+    if let Some((bucket, reminder_at)) = reminder_index_keys(todo) {
+        attributes.insert(dynamodb.config.gsi4_partition_key.clone(), bucket);
+        attributes.insert(dynamodb.config.gsi4_sort_key.clone(), reminder_at);
+    }
+    if let Some(priority) = todo.priority {
+        attributes.insert(
+            "priority".to_string(),
+            AttributeValue::S(priority.as_str().to_string()),
+        );
+    }
+    if let Some(notes) = &todo.notes {
+        attributes.insert("notes".to_string(), AttributeValue::S(notes.clone()));
+    }
+    if let Some(recurrence) = &todo.recurrence {
+        attributes.insert(
+            "recurrence".to_string(),
+            AttributeValue::S(recurrence.to_string()),
+        );
+    }
+    if let Some(next_occurrence_id) = &todo.next_occurrence_id {
+        attributes.insert(
+            "next_occurrence".to_string(),
+            AttributeValue::S(String::from(next_occurrence_id)),
+        );
+    }
+    if !todo.tags.is_empty() {
+        attributes.insert("tags".to_string(), AttributeValue::Ss(todo.tags.clone()));
+    }
+    // Only sub-tasks are part of the index of children.
+    if let Some(parent_id) = &todo.parent_id {
+        attributes.insert(
+            dynamodb.config.gsi3_partition_key.clone(),
+            AttributeValue::S(String::from(parent_id)),
+        );
+        attributes.insert(
+            dynamodb.config.gsi3_sort_key.clone(),
+            AttributeValue::S(String::from(&todo.id)),
+        );
+    }
+    attributes
+}
+
+/// Archived todos keep their attributes, but are indexed under their archived id so the queries
+/// on todos skip them.
+fn archived_todo_attributes(
+    dynamodb: &DynamoTable,
+    todo: &Todo,
+    updated_at: &DateTime<Utc>,
+) -> RawAttributes {
+    let archived_id = AttributeValue::S(String::from(
+        &todo.id.with_type_name(ARCHIVED_TODO_TYPE_NAME),
+    ));
+    let mut attributes = todo_attributes(dynamodb, todo, updated_at);
+    for name in [
+        &dynamodb.config.gsi1_partition_key,
+        &dynamodb.config.gsi1_sort_key,
+        &dynamodb.config.gsi2_sort_key,
+    ] {
+        attributes.insert(name.clone(), archived_id.clone());
+    }
+    if todo.parent_id.is_some() {
+        attributes.insert(dynamodb.config.gsi3_sort_key.clone(), archived_id);
+    }
+    attributes
+}
+
+pub fn build_todo(dynamodb: &DynamoTable, key: &Key, item: &RawAttributes) -> Result<Todo> {
     let list_id = if key.partition != key.sort {
         Some(key.partition.clone())
     } else {
         None
     };
     Ok(Todo {
-        id: key.sort.clone(),
+        id: if key.sort.has_type_name(ARCHIVED_TODO_TYPE_NAME) {
+            key.sort.with_type_name(TODO_TYPE_NAME)
+        } else {
+            key.sort.clone()
+        },
         title: item.get_string("title")?.clone(),
         complete: *item.get_bool("complete")?,
+        due_at: item.get_optional_from_string("due_at")?,
+        reminder_at: item.get_optional_from_string("reminder_at")?,
+        reminder_sent_at: item.get_optional_from_string("reminder_sent_at")?,
+        priority: item.get_optional_from_string("priority")?,
+        notes: item.get_optional_string("notes")?.cloned(),
+        auto_complete: *item.get_optional_bool("auto_complete")?.unwrap_or(&false),
+        tags: item.get_string_set("tags")?,
+        recurrence: item.get_optional_from_string("recurrence")?,
+        next_occurrence_id: item.get_optional_from_string("next_occurrence")?,
+        deleted_at: item.get_optional_from_string("deleted_at")?,
+        updated_at: item.get_optional_from_string("updated_at")?,
+        parent_id: item.get_optional_from_string(&dynamodb.config.gsi3_partition_key)?,
+        // Todos created before ordering was introduced are ordered by creation time.
+        position: item
+            .get_optional_from_number("position")?
+            .unwrap_or(key.sort.timestamp_ms() as f64),
         list_id,
+        owner: item.get_from_string(&dynamodb.config.gsi2_partition_key)?,
     })
 }
@@ -0,0 +1,120 @@
+use async_graphql::{ErrorExtensions, Result};
+use chrono::Utc;
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::types::ID;
+
+use super::extensions::{DynamoTableTodoExt, TodoUpdate};
+use super::recurrence::complete_occurrence;
+use super::Todo;
+
+/// Maximum number of levels of todos, a root todo being the first one.
+pub const MAX_TODO_DEPTH: usize = 4;
+
+/// Ensures that `parent` can become the parent of `todo`, without creating a cycle or exceeding
+/// the maximum depth once the children of `todo` are taken into account.
+pub async fn check_parent(dynamodb: &DynamoTable, todo: &Todo, parent: &Todo) -> Result<()> {
+    if parent.owner != todo.owner {
+        return Err(Errors::InvalidValue(format!(
+            "Todo '{}' can't be the parent of a todo of another owner",
+            parent.id
+        ))
+        .extend());
+    }
+
+    // Level of the parent, bounded by the depth limit so a corrupted hierarchy can't loop forever.
+    let mut depth = 1;
+    let mut ancestor_id = Some(parent.id.clone());
+    while let Some(id) = ancestor_id {
+        if id == todo.id {
+            return Err(Errors::InvalidValue(format!(
+                "Todo '{}' can't be one of its own descendants",
+                todo.id
+            ))
+            .extend());
+        }
+        if depth > MAX_TODO_DEPTH {
+            break;
+        }
+        ancestor_id = if id == parent.id {
+            parent.parent_id.clone()
+        } else {
+            depth += 1;
+            dynamodb
+                .get_todo(&todo.owner, &id)
+                .await?
+                .and_then(|(_, ancestor)| ancestor.parent_id)
+        };
+    }
+
+    if depth + subtree_height(dynamodb, todo).await? > MAX_TODO_DEPTH {
+        return Err(Errors::InvalidValue(format!(
+            "Todos can't be nested more than {} levels deep",
+            MAX_TODO_DEPTH
+        ))
+        .extend());
+    }
+    Ok(())
+}
+
+/// Number of levels of the todo and its descendants.
+async fn subtree_height(dynamodb: &DynamoTable, todo: &Todo) -> Result<usize> {
+    let mut height = 1;
+    let mut level: Vec<ID> = vec![todo.id.clone()];
+    while height <= MAX_TODO_DEPTH {
+        let mut next_level: Vec<ID> = vec![];
+        for id in &level {
+            for child in dynamodb.get_todo_children(&todo.owner, id).await? {
+                next_level.push(child.id);
+            }
+        }
+        if next_level.is_empty() {
+            break;
+        }
+        level = next_level;
+        height += 1;
+    }
+    Ok(height)
+}
+
+/// Completes the ancestors of a completed todo which have `auto_complete` enabled, as long as all
+/// their children are complete. Those changes are attributed to the actor who completed the todo.
+pub async fn auto_complete_parents(dynamodb: &DynamoTable, todo: &Todo, actor: &str) -> Result<()> {
+    if !todo.complete {
+        return Ok(());
+    }
+    let mut parent_id = todo.parent_id.clone();
+    for _ in 1..MAX_TODO_DEPTH {
+        let (key, parent) = match &parent_id {
+            Some(id) => match dynamodb.get_todo(&todo.owner, id).await? {
+                Some(parent) => parent,
+                None => break,
+            },
+            None => break,
+        };
+        if !parent.auto_complete || parent.complete {
+            break;
+        }
+        let children = dynamodb.get_todo_children(&todo.owner, &parent.id).await?;
+        if !children.iter().all(|child| child.complete) {
+            break;
+        }
+
+        parent_id = parent.parent_id.clone();
+        let mut completed = Todo {
+            complete: true,
+            ..parent.clone()
+        };
+        // Recurring parents get their next occurrence, as if they were completed directly.
+        let next = complete_occurrence(&parent, &mut completed, Utc::now());
+        let update = TodoUpdate {
+            old_key: key,
+            old_todo: parent,
+            new_todo: completed,
+            next,
+        };
+        dynamodb.update_todo(&update, actor).await?;
+    }
+    Ok(())
+}
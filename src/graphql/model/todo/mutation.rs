@@ -1,17 +1,44 @@
-use async_graphql::{Context, ErrorExtensions, InputObject, Object, OneofObject, Result};
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, MaybeUndefined, Object, OneofObject, PathSegment, Result,
+};
+use chrono::{DateTime, Utc};
+use tracing::warn;
 
+use crate::auth::Principal;
+use crate::dynamodb::table::MAX_TRANSACTION_ITEMS;
 use crate::dynamodb::DynamoTable;
-use crate::graphql::errors::{check_id_kind, Errors};
-use crate::graphql::types::ID;
+use crate::graphql::errors::{check_id_kind, non_nullable, principal, Errors};
+use crate::graphql::model::todo_list::extensions::DynamoTableTodoListExt;
+use crate::graphql::model::todo_list_member::access::{authorize_todo, authorize_todo_list};
+use crate::graphql::model::webhook::WebhookEvent;
+use crate::graphql::model::{TodoListRole, TODO_LIST_TYPE_NAME};
+use crate::graphql::search::search;
+use crate::graphql::types::{Owner, ID};
+use crate::graphql::webhooks::webhooks;
+use crate::graphql::Key;
 
-use super::extensions::DynamoTableTodoExt;
-use super::{Todo, TODO_TYPE_NAME};
+use super::bulk::{check_bulk_size, check_duplicates, TodoResult, BULK_CONCURRENCY};
+use super::effects::todo_written;
+use super::extensions::{DynamoTableTodoExt, TodoUpdate, TodoWrite};
+use super::hierarchy::check_parent;
+use super::position::{last_position, last_positions, position_between, rebalanced_positions};
+use super::recurrence::{complete_occurrence, Recurrence};
+use super::tags::{normalize_tags, MAX_TODO_TAGS};
+use super::{Todo, TodoPriority, TODO_TYPE_NAME};
 
 #[derive(Debug, InputObject)]
 struct TodoCreateInput {
     title: String,
     complete: Option<bool>,
     list: Option<TodoRelationTodoListLinkInput>,
+    due_at: Option<DateTime<Utc>>,
+    reminder_at: Option<DateTime<Utc>>,
+    priority: Option<TodoPriority>,
+    notes: Option<String>,
+    parent: Option<ID>,
+    auto_complete: Option<bool>,
+    recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, InputObject)]
@@ -22,9 +49,20 @@ struct TodoRelationTodoListLinkInput {
 #[derive(Debug, InputObject)]
 struct TodoUpdateInput {
     id: ID,
-    title: Option<String>,
-    list: Option<TodoRelationTodoListUpdateInput>,
-    complete: Option<bool>,
+    title: MaybeUndefined<String>,
+    /// An explicit null unlinks the todo from its current list.
+    list: MaybeUndefined<TodoRelationTodoListUpdateInput>,
+    complete: MaybeUndefined<bool>,
+    /// An explicit null clears the current value.
+    due_at: MaybeUndefined<DateTime<Utc>>,
+    reminder_at: MaybeUndefined<DateTime<Utc>>,
+    priority: MaybeUndefined<TodoPriority>,
+    notes: MaybeUndefined<String>,
+    /// An explicit null makes the todo a root one.
+    parent: MaybeUndefined<ID>,
+    auto_complete: MaybeUndefined<bool>,
+    /// An explicit null stops the recurrence.
+    recurrence: MaybeUndefined<Recurrence>,
 }
 
 #[derive(Debug, OneofObject)]
@@ -39,62 +77,585 @@ pub struct TodoMutation;
 #[Object]
 impl TodoMutation {
     async fn todo_create(&self, ctx: &Context<'_>, input: TodoCreateInput) -> Result<Todo> {
-        let todo = Todo {
-            id: ID::new(TODO_TYPE_NAME),
-            title: input.title,
-            complete: input.complete.unwrap_or(false),
-            list_id: input.list.map(|rel| rel.link),
-        };
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb.put_todo(&todo).await.map(|_| todo)
+        let todo = prepare_todo_create(dynamodb, principal, input).await?;
+        dynamodb.put_todo(&todo, &principal.subject).await?;
+        let write = TodoWrite::Create(todo);
+        Ok(written_todo(ctx, &write, &principal.subject, None).await)
     }
 
     async fn todo_update(&self, ctx: &Context<'_>, input: TodoUpdateInput) -> Result<Todo> {
-        check_id_kind(&input.id, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        let (old_key, old_todo) = if let Some(old) = dynamodb.get_todo(&input.id).await? {
-            old
-        } else {
-            return Err(Errors::NotFound.extend());
-        };
+        let update = prepare_todo_update(dynamodb, principal, input).await?;
+        let (_, todo) = dynamodb
+            .update_todo(&update, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        let write = TodoWrite::Update(Box::new(TodoUpdate {
+            new_todo: todo,
+            ..update
+        }));
+        Ok(written_todo(ctx, &write, &principal.subject, None).await)
+    }
 
-        let new_list_id = if let Some(ref relation_update) = input.list {
-            match relation_update {
-                TodoRelationTodoListUpdateInput::Link(link) => Some(link.clone()),
-                TodoRelationTodoListUpdateInput::Unlink(unlink) => {
-                    if old_key.partition != *unlink {
-                        return Err(Errors::InvalidValue(format!(
-                            "Todo '{}' is not linked to the todo list '{}'",
-                            input.id, unlink
-                        ))
-                        .extend());
-                    }
-                    None
+    /// Moves a todo within its list, either just `before` or `after` another todo of the same list.
+    async fn todo_move(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        before: Option<ID>,
+        after: Option<ID>,
+    ) -> Result<Todo> {
+        check_id_kind(&id, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (_, mut todo) = authorize_todo(dynamodb, principal, &id, TodoListRole::Editor).await?;
+        let list_id = todo.list_id.clone().ok_or_else(|| {
+            Errors::InvalidValue(format!("Todo '{}' doesn't belong to a todo list", id)).extend()
+        })?;
+
+        let mut todos = dynamodb.get_todo_list_todos(&todo.owner, &list_id).await?;
+        todos.retain(|other| other.id != id);
+        let index_of = |other_id: &ID| {
+            todos
+                .iter()
+                .position(|other| other.id == *other_id)
+                .ok_or_else(|| {
+                    Errors::InvalidValue(format!(
+                        "Todo '{}' doesn't belong to the todo list '{}'",
+                        other_id, list_id
+                    ))
+                    .extend()
+                })
+        };
+        let index = match (&before, &after) {
+            (Some(before), Some(after)) => {
+                let index = index_of(before)?;
+                if index == 0 || index_of(after)? != index - 1 {
+                    return Err(Errors::InvalidValue(format!(
+                        "Todos '{}' and '{}' aren't next to each other",
+                        after, before
+                    ))
+                    .extend());
                 }
+                index
+            }
+            (Some(before), None) => index_of(before)?,
+            (None, Some(after)) => index_of(after)? + 1,
+            (None, None) => {
+                return Err(Errors::InvalidValue(
+                    "Either 'before' or 'after' must be provided".to_string(),
+                )
+                .extend())
             }
-        } else {
-            old_todo.list_id.clone()
         };
 
-        let new_todo = Todo {
-            id: old_todo.id.clone(),
-            title: input.title.unwrap_or_else(|| old_todo.title.clone()),
-            complete: input.complete.unwrap_or(old_todo.complete),
-            list_id: new_list_id,
-        };
+        let lower = index.checked_sub(1).map(|index| todos[index].position);
+        let upper = todos.get(index).map(|other| other.position);
+        if let Some(position) = position_between(lower, upper) {
+            dynamodb
+                .update_todo_positions(&[(&todo, position)], &principal.subject)
+                .await?;
+            todo.position = position;
+        } else {
+            todos.insert(index, todo.clone());
+            let positions: Vec<(&Todo, f64)> = todos
+                .iter()
+                .zip(rebalanced_positions(todos.len()))
+                .collect();
+            dynamodb
+                .update_todo_positions(&positions, &principal.subject)
+                .await?;
+            todo.position = positions[index].1;
+        }
+        webhooks(ctx).todo_event(WebhookEvent::TodoUpdated, &todo);
+        Ok(todo)
+    }
+
+    async fn todo_add_tags(&self, ctx: &Context<'_>, id: ID, tags: Vec<String>) -> Result<Todo> {
+        check_id_kind(&id, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (key, todo) = authorize_todo(dynamodb, principal, &id, TodoListRole::Editor).await?;
+        let mut tags = normalize_tags(tags)?;
+        tags.retain(|tag| !todo.tags.contains(tag));
+        if tags.is_empty() {
+            return Ok(todo);
+        }
+        if todo.tags.len() + tags.len() > MAX_TODO_TAGS {
+            return Err(Errors::InvalidValue(format!(
+                "Todos can't have more than {} tags",
+                MAX_TODO_TAGS
+            ))
+            .extend());
+        }
 
         dynamodb
-            .update_todo(old_key, old_todo, new_todo)
-            .await
-            .map(|(_, todo)| todo)
+            .add_todo_tags(&key, &todo, &tags, &principal.subject)
+            .await?;
+        let todo = get_updated_todo(dynamodb, &todo).await?;
+        webhooks(ctx).todo_event(WebhookEvent::TodoUpdated, &todo);
+        search(ctx).index_todo(&todo);
+        Ok(todo)
     }
 
-    async fn todo_delete(&self, ctx: &Context<'_>, id: ID) -> Result<Todo> {
+    async fn todo_remove_tags(&self, ctx: &Context<'_>, id: ID, tags: Vec<String>) -> Result<Todo> {
         check_id_kind(&id, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (key, todo) = authorize_todo(dynamodb, principal, &id, TodoListRole::Editor).await?;
+        let mut tags = normalize_tags(tags)?;
+        tags.retain(|tag| todo.tags.contains(tag));
+        if tags.is_empty() {
+            return Ok(todo);
+        }
+
         dynamodb
-            .delete_todo(&id)
+            .remove_todo_tags(&key, &todo, &tags, &principal.subject)
+            .await?;
+        let todo = get_updated_todo(dynamodb, &todo).await?;
+        webhooks(ctx).todo_event(WebhookEvent::TodoUpdated, &todo);
+        search(ctx).index_todo(&todo);
+        Ok(todo)
+    }
+
+    async fn todo_delete(&self, ctx: &Context<'_>, id: ID) -> Result<Todo> {
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (key, todo) = prepare_todo_delete(dynamodb, principal, &id).await?;
+        let todo = dynamodb
+            .delete_todo(&todo.owner, &id, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        let write = TodoWrite::Delete(key, todo);
+        Ok(written_todo(ctx, &write, &principal.subject, None).await)
+    }
+
+    /// Restores a todo from the trash of the owner.
+    async fn todo_restore(&self, ctx: &Context<'_>, id: ID) -> Result<Todo> {
+        check_id_kind(&id, TODO_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo = dynamodb
+            .restore_todo(&Owner::from(principal), &id, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        webhooks(ctx).todo_event(WebhookEvent::TodoRestored, &todo);
+        search(ctx).index_todo(&todo);
+        Ok(todo)
+    }
+
+    /// Creates the todos in transactional chunks, so a chunk may fail while the others are
+    /// created, a failed todo being never written. With `atomic`, they're all created in a single
+    /// transaction or none of them is. In both cases, the parents are completed and the events
+    /// published after the transactions, outside of them.
+    async fn todo_create_many(
+        &self,
+        ctx: &Context<'_>,
+        inputs: Vec<TodoCreateInput>,
+        atomic: Option<bool>,
+    ) -> Result<Vec<TodoResult>> {
+        check_bulk_size(inputs.len())?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let mut prepared = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            prepared.push(
+                prepare_todo_create(dynamodb, principal, input)
+                    .await
+                    .map(TodoWrite::Create),
+            );
+        }
+        // Todos are appended in the order of the inputs.
+        let positions = last_positions(prepared.len());
+        for (write, position) in prepared.iter_mut().zip(positions) {
+            if let Ok(TodoWrite::Create(todo)) = write {
+                todo.position = position;
+            }
+        }
+
+        let results = if atomic.unwrap_or(false) {
+            write_atomically(ctx, principal, prepared).await?
+        } else {
+            let todos: Vec<Todo> = prepared
+                .iter()
+                .filter_map(|write| match write {
+                    Ok(TodoWrite::Create(todo)) => Some(todo.clone()),
+                    _ => None,
+                })
+                .collect();
+            let mut written = dynamodb
+                .put_todos(&todos, &principal.subject)
+                .await?
+                .into_iter();
+            let mut results = Vec::with_capacity(prepared.len());
+            for (index, write) in prepared.into_iter().enumerate() {
+                // Only the prepared todos were written, in the same order.
+                results.push(match write {
+                    Ok(write) => match written.next() {
+                        Some(Ok(())) => {
+                            Ok(written_todo(ctx, &write, &principal.subject, Some(index)).await)
+                        }
+                        Some(Err(err)) => Err(err),
+                        None => Err(Errors::NotFound.extend()),
+                    },
+                    Err(err) => Err(err),
+                });
+            }
+            results
+        };
+        Ok(results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let id = result.as_ref().ok().map(|todo| todo.id.clone());
+                TodoResult::new(index, id, result)
+            })
+            .collect())
+    }
+
+    /// Updates each todo within its own transaction, so a todo may fail while the others are
+    /// updated. With `atomic`, they're all updated in a single transaction or none of them is.
+    async fn todo_update_many(
+        &self,
+        ctx: &Context<'_>,
+        inputs: Vec<TodoUpdateInput>,
+        atomic: Option<bool>,
+    ) -> Result<Vec<TodoResult>> {
+        check_bulk_size(inputs.len())?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let ids: Vec<ID> = inputs.iter().map(|input| input.id.clone()).collect();
+        let duplicates = check_duplicates(&ids);
+        let results = if atomic.unwrap_or(false) {
+            let mut prepared = Vec::with_capacity(inputs.len());
+            for (input, duplicate) in inputs.into_iter().zip(duplicates) {
+                prepared.push(match duplicate {
+                    Ok(()) => prepare_todo_update(dynamodb, principal, input)
+                        .await
+                        .map(|update| TodoWrite::Update(Box::new(update))),
+                    Err(err) => Err(err),
+                });
+            }
+            write_atomically(ctx, principal, prepared).await?
+        } else {
+            stream::iter(inputs.into_iter().zip(duplicates).enumerate())
+                .map(|(index, (input, duplicate))| async move {
+                    duplicate?;
+                    let update = prepare_todo_update(dynamodb, principal, input).await?;
+                    let (_, todo) = dynamodb
+                        .update_todo(&update, &principal.subject)
+                        .await?
+                        .ok_or_else(|| Errors::NotFound.extend())?;
+                    let write = TodoWrite::Update(Box::new(TodoUpdate {
+                        new_todo: todo,
+                        ..update
+                    }));
+                    Ok(written_todo(ctx, &write, &principal.subject, Some(index)).await)
+                })
+                .buffered(BULK_CONCURRENCY)
+                .collect()
+                .await
+        };
+        Ok(bulk_results(ids, results))
+    }
+
+    /// Moves each todo to the trash within its own transaction, so a todo may fail while the
+    /// others are deleted. With `atomic`, they're all deleted in a single transaction or none of
+    /// them is.
+    async fn todo_delete_many(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<ID>,
+        atomic: Option<bool>,
+    ) -> Result<Vec<TodoResult>> {
+        check_bulk_size(ids.len())?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let duplicates = check_duplicates(&ids);
+        let results = if atomic.unwrap_or(false) {
+            let mut prepared = Vec::with_capacity(ids.len());
+            for (id, duplicate) in ids.iter().zip(duplicates) {
+                prepared.push(match duplicate {
+                    Ok(()) => prepare_todo_delete(dynamodb, principal, id)
+                        .await
+                        .map(|(key, todo)| TodoWrite::Delete(key, todo)),
+                    Err(err) => Err(err),
+                });
+            }
+            write_atomically(ctx, principal, prepared).await?
+        } else {
+            stream::iter(ids.clone().into_iter().zip(duplicates).enumerate())
+                .map(|(index, (id, duplicate))| async move {
+                    duplicate?;
+                    let (key, todo) = prepare_todo_delete(dynamodb, principal, &id).await?;
+                    let todo = dynamodb
+                        .delete_todo(&todo.owner, &id, &principal.subject)
+                        .await?
+                        .ok_or_else(|| Errors::NotFound.extend())?;
+                    let write = TodoWrite::Delete(key, todo);
+                    Ok(written_todo(ctx, &write, &principal.subject, Some(index)).await)
+                })
+                .buffered(BULK_CONCURRENCY)
+                .collect()
+                .await
+        };
+        Ok(bulk_results(ids, results))
+    }
+}
+
+/// Validates the input of a new todo, which is only written by the caller.
+async fn prepare_todo_create(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    input: TodoCreateInput,
+) -> Result<Todo> {
+    let list_id = input.list.map(|rel| rel.link);
+    // Todos of a shared list belong to the owner of the list.
+    let owner = if let Some(list_id) = &list_id {
+        check_id_kind(list_id, TODO_LIST_TYPE_NAME)?;
+        authorize_todo_list(dynamodb, principal, list_id, TodoListRole::Editor)
             .await?
-            .ok_or_else(|| Errors::NotFound.extend())
+            .owner
+    } else {
+        Owner::from(principal)
+    };
+
+    let todo = Todo {
+        id: ID::new(TODO_TYPE_NAME),
+        title: input.title,
+        complete: input.complete.unwrap_or(false),
+        due_at: input.due_at,
+        reminder_at: input.reminder_at,
+        reminder_sent_at: None,
+        priority: input.priority,
+        notes: input.notes,
+        auto_complete: input.auto_complete.unwrap_or(false),
+        tags: vec![],
+        recurrence: input.recurrence,
+        next_occurrence_id: None,
+        deleted_at: None,
+        updated_at: None,
+        list_id,
+        parent_id: input.parent,
+        position: last_position(),
+        owner,
+    };
+    if let Some(parent_id) = &todo.parent_id {
+        let parent = get_parent(dynamodb, principal, parent_id).await?;
+        check_parent(dynamodb, &todo, &parent).await?;
+    }
+    Ok(todo)
+}
+
+/// Validates the changes of a todo, which are only written by the caller. Completing a recurring
+/// todo creates its next occurrence alongside.
+async fn prepare_todo_update(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    input: TodoUpdateInput,
+) -> Result<TodoUpdate> {
+    check_id_kind(&input.id, TODO_TYPE_NAME)?;
+    let (old_key, old_todo) =
+        authorize_todo(dynamodb, principal, &input.id, TodoListRole::Editor).await?;
+
+    let mut owner = old_todo.owner.clone();
+    let new_list_id = match input.list {
+        MaybeUndefined::Value(ref relation_update) => match relation_update {
+            TodoRelationTodoListUpdateInput::Link(link) => {
+                check_id_kind(link, TODO_LIST_TYPE_NAME)?;
+                owner = authorize_todo_list(dynamodb, principal, link, TodoListRole::Editor)
+                    .await?
+                    .owner;
+                Some(link.clone())
+            }
+            TodoRelationTodoListUpdateInput::Unlink(unlink) => {
+                if old_key.partition != *unlink {
+                    return Err(Errors::InvalidValue(format!(
+                        "Todo '{}' is not linked to the todo list '{}'",
+                        input.id, unlink
+                    ))
+                    .extend());
+                }
+                None
+            }
+        },
+        MaybeUndefined::Null => None,
+        MaybeUndefined::Undefined => old_todo.list_id.clone(),
+    };
+
+    let mut new_todo = Todo {
+        id: old_todo.id.clone(),
+        title: non_nullable(input.title, "title")?.unwrap_or_else(|| old_todo.title.clone()),
+        complete: non_nullable(input.complete, "complete")?.unwrap_or(old_todo.complete),
+        due_at: old_todo.due_at,
+        reminder_at: old_todo.reminder_at,
+        reminder_sent_at: old_todo.reminder_sent_at,
+        priority: old_todo.priority,
+        notes: old_todo.notes.clone(),
+        auto_complete: non_nullable(input.auto_complete, "autoComplete")?
+            .unwrap_or(old_todo.auto_complete),
+        tags: old_todo.tags.clone(),
+        recurrence: old_todo.recurrence,
+        next_occurrence_id: old_todo.next_occurrence_id.clone(),
+        deleted_at: None,
+        updated_at: old_todo.updated_at,
+        parent_id: old_todo.parent_id.clone(),
+        // Todos moved to another list are appended at its end.
+        position: if new_list_id != old_todo.list_id {
+            last_position()
+        } else {
+            old_todo.position
+        },
+        list_id: new_list_id,
+        owner,
+    };
+    input.due_at.update_to(&mut new_todo.due_at);
+    input.reminder_at.update_to(&mut new_todo.reminder_at);
+    // A new reminder is sent again.
+    if new_todo.reminder_at != old_todo.reminder_at {
+        new_todo.reminder_sent_at = None;
     }
+    input.priority.update_to(&mut new_todo.priority);
+    input.notes.update_to(&mut new_todo.notes);
+    input.parent.update_to(&mut new_todo.parent_id);
+    input.recurrence.update_to(&mut new_todo.recurrence);
+
+    // The parent must also be checked when the todo is moved to another owner.
+    if let Some(parent_id) = &new_todo.parent_id {
+        if new_todo.parent_id != old_todo.parent_id || new_todo.owner != old_todo.owner {
+            let parent = get_parent(dynamodb, principal, parent_id).await?;
+            check_parent(dynamodb, &new_todo, &parent).await?;
+        }
+    }
+
+    let next = complete_occurrence(&old_todo, &mut new_todo, Utc::now());
+    Ok(TodoUpdate {
+        old_key,
+        old_todo,
+        new_todo,
+        next,
+    })
+}
+
+/// Todos are deleted by the editors of their list.
+async fn prepare_todo_delete(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+) -> Result<(Key, Todo)> {
+    check_id_kind(id, TODO_TYPE_NAME)?;
+    authorize_todo(dynamodb, principal, id, TodoListRole::Editor).await
+}
+
+/// Todo as written. Its parents which couldn't be completed are reported as an error of the
+/// response, at the path of the todo, as the mutation itself succeeded. `index` is the position
+/// of the todo in the results of a bulk mutation.
+async fn written_todo(
+    ctx: &Context<'_>,
+    write: &TodoWrite,
+    actor: &str,
+    index: Option<usize>,
+) -> Todo {
+    if let Err(err) = todo_written(ctx, write, actor).await {
+        warn!(todo = %write.todo().id, "{}", err.message);
+        let mut err = ctx.set_error_path(err.into_server_error(ctx.item.pos));
+        err.path.extend(index.map(PathSegment::Index));
+        ctx.add_error(err);
+    }
+    write.todo().clone()
+}
+
+/// Applies the writes in a single transaction, only if all of them could be prepared. Otherwise
+/// nothing is written and the other todos fail as aborted.
+async fn write_atomically(
+    ctx: &Context<'_>,
+    principal: &Principal,
+    prepared: Vec<Result<TodoWrite>>,
+) -> Result<Vec<Result<Todo>>> {
+    if prepared.iter().any(Result::is_err) {
+        return Ok(prepared
+            .into_iter()
+            .map(|write| write.and_then(|_| Err(Errors::Aborted.extend())))
+            .collect());
+    }
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let writes = prepared.into_iter().collect::<Result<Vec<TodoWrite>>>()?;
+    let items: usize = writes
+        .iter()
+        .map(|write| {
+            dynamodb
+                .transact_todo_write(write, &principal.subject)
+                .len()
+        })
+        .sum();
+    if items > MAX_TRANSACTION_ITEMS {
+        return Err(Errors::InvalidValue(format!(
+            "Atomic bulk mutations can't write more than {} items, audit records and tags \
+             included, but {} are required",
+            MAX_TRANSACTION_ITEMS, items
+        ))
+        .extend());
+    }
+
+    let todos = match dynamodb
+        .transact_todo_writes(&writes, &principal.subject)
+        .await?
+    {
+        Some(todos) => todos,
+        // One of the todos changed meanwhile.
+        None => {
+            return Ok(writes
+                .iter()
+                .map(|_| Err(Errors::Aborted.extend()))
+                .collect())
+        }
+    };
+    let mut results = Vec::with_capacity(writes.len());
+    for (index, (write, todo)) in writes.into_iter().zip(todos).enumerate() {
+        let write = match write {
+            TodoWrite::Create(_) => TodoWrite::Create(todo),
+            TodoWrite::Update(update) => TodoWrite::Update(Box::new(TodoUpdate {
+                new_todo: todo,
+                ..*update
+            })),
+            TodoWrite::Delete(key, _) => TodoWrite::Delete(key, todo),
+        };
+        results.push(Ok(written_todo(
+            ctx,
+            &write,
+            &principal.subject,
+            Some(index),
+        )
+        .await));
+    }
+    Ok(results)
+}
+
+/// Results of the bulk mutations of existing todos, identified by their input id.
+fn bulk_results(ids: Vec<ID>, results: Vec<Result<Todo>>) -> Vec<TodoResult> {
+    ids.into_iter()
+        .zip(results)
+        .enumerate()
+        .map(|(index, (id, result))| TodoResult::new(index, Some(id), result))
+        .collect()
+}
+
+/// Todos can only be nested under a todo visible to the user.
+async fn get_parent(dynamodb: &DynamoTable, principal: &Principal, parent_id: &ID) -> Result<Todo> {
+    check_id_kind(parent_id, TODO_TYPE_NAME)?;
+    authorize_todo(dynamodb, principal, parent_id, TodoListRole::Viewer)
+        .await
+        .map(|(_, parent)| parent)
+}
+
+/// Transactions can't return the updated item, so it's retrieved again.
+async fn get_updated_todo(dynamodb: &DynamoTable, todo: &Todo) -> Result<Todo> {
+    dynamodb
+        .get_todo(&todo.owner, &todo.id)
+        .await?
+        .ok_or_else(|| Errors::NotFound.extend())
+        .map(|(_, todo)| todo)
 }
@@ -0,0 +1,40 @@
+use chrono::Utc;
+
+/// Gap between todos after a rebalance.
+const POSITION_STEP: f64 = 1024.0;
+
+/// Below this gap, the midpoint between two positions starts losing precision for the timestamp
+/// based positions, so the whole list must be rebalanced.
+const MIN_POSITION_GAP: f64 = 1e-3;
+
+/// Todos are appended at the end of a list by default. Using the current time avoids reading the
+/// whole list, and matches the positions of todos created before ordering was introduced.
+pub fn last_position() -> f64 {
+    Utc::now().timestamp_millis() as f64
+}
+
+/// Positions of `count` todos appended together, which keep their order even though they're
+/// created within the same millisecond.
+pub fn last_positions(count: usize) -> impl Iterator<Item = f64> {
+    let position = last_position();
+    (0..count).map(move |index| position + index as f64 * MIN_POSITION_GAP)
+}
+
+/// Position of a todo placed between two others. `None` means there isn't enough room left and
+/// the list must be rebalanced.
+pub fn position_between(lower: Option<f64>, upper: Option<f64>) -> Option<f64> {
+    match (lower, upper) {
+        (None, None) => Some(last_position()),
+        (Some(lower), None) => Some(lower + POSITION_STEP),
+        (None, Some(upper)) => Some(upper - POSITION_STEP),
+        (Some(lower), Some(upper)) if upper - lower >= MIN_POSITION_GAP => {
+            Some(lower + (upper - lower) / 2.0)
+        }
+        _ => None,
+    }
+}
+
+/// Evenly spread positions for a list of `count` todos.
+pub fn rebalanced_positions(count: usize) -> impl Iterator<Item = f64> {
+    (1..=count).map(|index| index as f64 * POSITION_STEP)
+}
@@ -1,10 +1,14 @@
 use async_graphql::{Context, ErrorExtensions, Object, Result};
+use chrono::{DateTime, Utc};
 
 use crate::dynamodb::DynamoTable;
-use crate::graphql::errors::{check_id_kind, Errors};
-use crate::graphql::types::ID;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::limits::COLLECTION_COMPLEXITY;
+use crate::graphql::model::todo_list_member::access::get_todo_with_role;
+use crate::graphql::types::{Owner, ID};
 
-use super::extensions::DynamoTableTodoExt;
+use super::extensions::{DynamoTableTodoExt, TodoFilter};
+use super::tags::normalize_tags;
 use super::{Todo, TODO_TYPE_NAME};
 
 #[derive(Default)]
@@ -12,18 +16,37 @@ pub struct TodoQuery;
 
 #[Object]
 impl TodoQuery {
-    async fn todo_collection(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn todo_collection(
+        &self,
+        ctx: &Context<'_>,
+        due_before: Option<DateTime<Utc>>,
+        overdue: Option<bool>,
+    ) -> Result<Vec<Todo>> {
+        let owner = Owner::from(principal(ctx)?);
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb.scan_todo().await
+        let filter = TodoFilter {
+            due_before,
+            overdue,
+        };
+        dynamodb.scan_todo(&owner, &filter).await
     }
 
     async fn todo(&self, ctx: &Context<'_>, id: ID) -> Result<Todo> {
         check_id_kind(&id, TODO_TYPE_NAME)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb
-            .get_todo(&id)
+        get_todo_with_role(dynamodb, principal(ctx)?, &id)
             .await?
             .ok_or_else(|| Errors::NotFound.extend())
-            .map(|(_, todo)| todo)
+            .map(|(_, todo, _)| todo)
+    }
+
+    /// Todos of the owner with the given tag.
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn todos_by_tag(&self, ctx: &Context<'_>, tag: String) -> Result<Vec<Todo>> {
+        let owner = Owner::from(principal(ctx)?);
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let tag = normalize_tags(vec![tag])?.remove(0);
+        dynamodb.get_tagged_todos(&owner, &tag).await
     }
 }
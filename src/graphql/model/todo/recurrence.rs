@@ -0,0 +1,317 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+use chrono::{DateTime, Duration, Months, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::graphql::types::ID;
+
+use super::position::last_position;
+use super::{Todo, TODO_TYPE_NAME};
+
+const MAX_INTERVAL: u32 = 1000;
+
+const UNTIL_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "DAILY" => Ok(Frequency::Daily),
+            "WEEKLY" => Ok(Frequency::Weekly),
+            "MONTHLY" => Ok(Frequency::Monthly),
+            _ => Err(anyhow!("Unsupported frequency '{}'", s)),
+        }
+    }
+}
+
+/// Subset of the iCalendar RRULE, such as `FREQ=WEEKLY;INTERVAL=2;UNTIL=20261231T000000Z`. Only
+/// `FREQ` is required, the interval defaulting to 1. `UNTIL` also accepts a date, meaning the
+/// start of the day in UTC.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// Date of the occurrence following the one at `date`, if any.
+    pub fn next_after(&self, date: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::days(self.interval.into())),
+            Frequency::Weekly => date.checked_add_signed(Duration::weeks(self.interval.into())),
+            // Days missing in the target month are clamped to its last one.
+            Frequency::Monthly => date.checked_add_months(Months::new(self.interval)),
+        }?;
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FREQ={};INTERVAL={}",
+            self.frequency.as_str(),
+            self.interval
+        )?;
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format(UNTIL_FORMAT))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut until = None;
+        for part in s.trim().split(';') {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid recurrence rule part '{}'", part))?;
+            match name {
+                "FREQ" => frequency = Some(value.parse()?),
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=MAX_INTERVAL).contains(interval))
+                        .ok_or_else(|| anyhow!("Interval must be between 1 and {}", MAX_INTERVAL))?
+                }
+                "UNTIL" => until = Some(parse_until(value)?),
+                _ => return Err(anyhow!("Unsupported recurrence rule part '{}'", name)),
+            }
+        }
+        Ok(Recurrence {
+            frequency: frequency.ok_or_else(|| anyhow!("Recurrence rule requires FREQ"))?,
+            interval,
+            until,
+        })
+    }
+}
+
+fn parse_until(value: &str) -> Result<DateTime<Utc>, anyhow::Error> {
+    let until = NaiveDateTime::parse_from_str(value, UNTIL_FORMAT)
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| anyhow!("Invalid UNTIL '{}'", value))?;
+    Ok(Utc.from_utc_datetime(&until))
+}
+
+#[Scalar]
+impl ScalarType for Recurrence {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        if let Value::String(value) = &value {
+            value.parse().map_err(InputValueError::from)
+        } else {
+            Err(InputValueError::expected_type(value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+/// Next occurrence of a recurring todo, appended to the same list. Its due date is advanced from
+/// the previous one, or from `completed_at` if there was none. Returns `None` once the recurrence
+/// is over.
+pub fn next_occurrence(todo: &Todo, completed_at: DateTime<Utc>) -> Option<Todo> {
+    let recurrence = todo.recurrence?;
+    let previous_due_at = todo.due_at.unwrap_or(completed_at);
+    let due_at = recurrence.next_after(previous_due_at)?;
+    Some(Todo {
+        id: ID::new(TODO_TYPE_NAME),
+        complete: false,
+        due_at: Some(due_at),
+        // The reminder keeps the same offset from the due date.
+        reminder_at: todo
+            .reminder_at
+            .map(|reminder_at| reminder_at + (due_at - previous_due_at)),
+        reminder_sent_at: None,
+        next_occurrence_id: None,
+        deleted_at: None,
+        updated_at: None,
+        position: last_position(),
+        ..todo.clone()
+    })
+}
+
+/// Next occurrence created by the completion of a recurring todo, which is marked so toggling it
+/// doesn't create another one.
+pub fn complete_occurrence(
+    old_todo: &Todo,
+    new_todo: &mut Todo,
+    completed_at: DateTime<Utc>,
+) -> Option<Todo> {
+    if !new_todo.complete || old_todo.complete || old_todo.next_occurrence_id.is_some() {
+        return None;
+    }
+    let next = next_occurrence(new_todo, completed_at)?;
+    new_todo.next_occurrence_id = Some(next.id.clone());
+    Some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn todo(recurrence: &str) -> Todo {
+        Todo {
+            id: ID::new(TODO_TYPE_NAME),
+            title: "Water the plants".to_string(),
+            complete: false,
+            due_at: Some(date_time("2026-01-31T09:00:00Z")),
+            reminder_at: None,
+            reminder_sent_at: None,
+            priority: None,
+            notes: None,
+            auto_complete: false,
+            tags: Vec::new(),
+            recurrence: Some(recurrence.parse().unwrap()),
+            next_occurrence_id: None,
+            deleted_at: None,
+            updated_at: None,
+            list_id: None,
+            parent_id: None,
+            position: 0.0,
+            owner: "owner#subject#user".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn parses_rule() {
+        let recurrence: Recurrence = "FREQ=WEEKLY;INTERVAL=2;UNTIL=20261231T120000Z"
+            .parse()
+            .unwrap();
+        assert_eq!(recurrence.frequency, Frequency::Weekly);
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.until, Some(date_time("2026-12-31T12:00:00Z")));
+        assert_eq!(
+            recurrence.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;UNTIL=20261231T120000Z"
+        );
+    }
+
+    #[test]
+    fn parses_rule_defaults_and_date_until() {
+        let recurrence: Recurrence = "FREQ=DAILY;UNTIL=20261231".parse().unwrap();
+        assert_eq!(recurrence.interval, 1);
+        assert_eq!(recurrence.until, Some(date_time("2026-12-31T00:00:00Z")));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in [
+            "",
+            "INTERVAL=2",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=1001",
+            "FREQ=DAILY;COUNT=3",
+            "FREQ=DAILY;UNTIL=tomorrow",
+            "FREQ",
+        ] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn clamps_monthly_occurrences_to_the_last_day() {
+        let recurrence: Recurrence = "FREQ=MONTHLY".parse().unwrap();
+        assert_eq!(
+            recurrence.next_after(date_time("2026-01-31T09:00:00Z")),
+            Some(date_time("2026-02-28T09:00:00Z"))
+        );
+        let recurrence: Recurrence = "FREQ=MONTHLY;INTERVAL=13".parse().unwrap();
+        assert_eq!(
+            recurrence.next_after(date_time("2027-01-31T09:00:00Z")),
+            Some(date_time("2028-02-29T09:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn stops_after_until() {
+        let recurrence: Recurrence = "FREQ=DAILY;UNTIL=20260202T090000Z".parse().unwrap();
+        assert_eq!(
+            recurrence.next_after(date_time("2026-02-01T09:00:00Z")),
+            Some(date_time("2026-02-02T09:00:00Z"))
+        );
+        assert_eq!(
+            recurrence.next_after(date_time("2026-02-02T09:00:00Z")),
+            None
+        );
+    }
+
+    #[test]
+    fn advances_due_date_and_reminder() {
+        let todo = Todo {
+            reminder_at: Some(date_time("2026-01-31T08:00:00Z")),
+            reminder_sent_at: Some(date_time("2026-01-31T08:00:00Z")),
+            ..todo("FREQ=WEEKLY")
+        };
+        let next = next_occurrence(&todo, date_time("2026-02-03T00:00:00Z")).unwrap();
+        assert_ne!(next.id, todo.id);
+        assert!(!next.complete);
+        assert_eq!(next.due_at, Some(date_time("2026-02-07T09:00:00Z")));
+        assert_eq!(next.reminder_at, Some(date_time("2026-02-07T08:00:00Z")));
+        assert_eq!(next.reminder_sent_at, None);
+    }
+
+    #[test]
+    fn creates_next_occurrence_once() {
+        let old_todo = todo("FREQ=DAILY");
+        let mut completed = Todo {
+            complete: true,
+            ..old_todo.clone()
+        };
+        let next = complete_occurrence(&old_todo, &mut completed, Utc::now()).unwrap();
+        assert_eq!(completed.next_occurrence_id, Some(next.id.clone()));
+        assert_eq!(next.next_occurrence_id, None);
+
+        // Toggled back and completed again.
+        let reopened = Todo {
+            complete: false,
+            ..completed.clone()
+        };
+        let mut completed_again = Todo {
+            complete: true,
+            ..reopened.clone()
+        };
+        assert!(complete_occurrence(&reopened, &mut completed_again, Utc::now()).is_none());
+        assert_eq!(completed_again.next_occurrence_id, Some(next.id));
+    }
+}
@@ -0,0 +1,180 @@
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Duration, DurationRound, SecondsFormat, Utc};
+use tokio_stream::StreamExt;
+
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::item::datetime_attribute;
+use crate::dynamodb::{DynamoTable, UpdateExpression};
+use crate::graphql::Key;
+
+use super::extensions::build_todo;
+use super::Todo;
+
+const REMINDER_BUCKET_PREFIX: &str = "reminder#";
+
+/// Pending reminders are partitioned by hour, so the due ones are retrieved with a query per hour
+/// instead of a scan.
+pub fn reminder_bucket(date: &DateTime<Utc>) -> DateTime<Utc> {
+    date.duration_trunc(Duration::hours(1)).unwrap_or(*date)
+}
+
+fn reminder_bucket_key(bucket: &DateTime<Utc>) -> String {
+    format!("{}{}", REMINDER_BUCKET_PREFIX, bucket.format("%Y-%m-%dT%H"))
+}
+
+/// Keys of the reminder index, only set while the reminder of an incomplete todo wasn't sent.
+pub fn reminder_index_keys(todo: &Todo) -> Option<(AttributeValue, AttributeValue)> {
+    match todo.reminder_at {
+        Some(reminder_at) if todo.reminder_sent_at.is_none() && !todo.complete => Some((
+            AttributeValue::S(reminder_bucket_key(&reminder_bucket(&reminder_at))),
+            datetime_attribute(&reminder_at),
+        )),
+        _ => None,
+    }
+}
+
+/// Extension used to decorate the DynamoTable with the methods of the reminder scheduler.
+#[async_trait::async_trait]
+pub trait DynamoTableReminderExt {
+    async fn get_due_reminders(
+        &self,
+        bucket: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Result<Vec<(Key, Todo)>>;
+    /// Pending reminders due before `before`, excluding the todos in the trash.
+    async fn get_missed_reminders(&self, before: &DateTime<Utc>) -> Result<Vec<(Key, Todo)>>;
+    /// Claims the reminder until the given date, so the other instances don't send it meanwhile.
+    /// Returns `false` if the reminder was changed, sent or claimed by another instance.
+    async fn claim_reminder(&self, key: &Key, todo: &Todo, until: &DateTime<Utc>) -> Result<bool>;
+    /// Releases the claim of a reminder which couldn't be sent, so it's sent on the next poll.
+    async fn release_reminder(&self, key: &Key, until: &DateTime<Utc>) -> Result<()>;
+    async fn mark_reminder_sent(&self, key: &Key, todo: &Todo) -> Result<bool>;
+}
+
+#[async_trait::async_trait]
+impl DynamoTableReminderExt for DynamoTable {
+    /// Reminders of the bucket which are due at `now`, excluding the todos in the trash.
+    async fn get_due_reminders(
+        &self,
+        bucket: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Result<Vec<(Key, Todo)>> {
+        let mut todos = Vec::new();
+        // Sort keys are formatted as the stored dates, so they're compared as strings.
+        let until = now.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut paginator = self
+            .query_gsi4_until(reminder_bucket_key(bucket), &until)
+            .filter_expression("attribute_not_exists(deleted_at)")
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                let todo = build_todo(self, &key, item)?;
+                todos.push((key, todo));
+            }
+        }
+        Ok(todos)
+    }
+
+    async fn get_missed_reminders(&self, before: &DateTime<Utc>) -> Result<Vec<(Key, Todo)>> {
+        let mut todos = Vec::new();
+        let before = before.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut paginator = self
+            .scan_gsi4_before(REMINDER_BUCKET_PREFIX, &before)
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = self.extract_key(item)?;
+                let todo = build_todo(self, &key, item)?;
+                if todo.deleted_at.is_none() {
+                    todos.push((key, todo));
+                }
+            }
+        }
+        Ok(todos)
+    }
+
+    async fn claim_reminder(&self, key: &Key, todo: &Todo, until: &DateTime<Utc>) -> Result<bool> {
+        let reminder_at = match &todo.reminder_at {
+            Some(reminder_at) => reminder_at,
+            None => return Ok(false),
+        };
+        let mut update = UpdateExpression::default();
+        update.set("reminder_claimed_until", datetime_attribute(until));
+        let output = self
+            .update_item(key, |req| {
+                update.apply(
+                    req.condition_expression(
+                        "reminder_at = :reminder_at AND attribute_not_exists(reminder_sent_at) \
+                         AND (attribute_not_exists(reminder_claimed_until) \
+                         OR reminder_claimed_until < :now)",
+                    )
+                    .expression_attribute_values(":reminder_at", datetime_attribute(reminder_at))
+                    .expression_attribute_values(":now", datetime_attribute(&Utc::now())),
+                )
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(false),
+            output => {
+                output?;
+                Ok(true)
+            }
+        }
+    }
+
+    async fn release_reminder(&self, key: &Key, until: &DateTime<Utc>) -> Result<()> {
+        let mut update = UpdateExpression::default();
+        update.remove("reminder_claimed_until");
+        let output = self
+            .update_item(key, |req| {
+                // Only the own claim is released, which also avoids recreating a deleted todo.
+                update.apply(
+                    req.condition_expression("reminder_claimed_until = :until")
+                        .expression_attribute_values(":until", datetime_attribute(until)),
+                )
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(()),
+            output => {
+                output?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Records the reminder as sent and removes it from the index. Returns `false` if the reminder
+    /// was changed or sent in the meantime.
+    async fn mark_reminder_sent(&self, key: &Key, todo: &Todo) -> Result<bool> {
+        let reminder_at = match &todo.reminder_at {
+            Some(reminder_at) => reminder_at,
+            None => return Ok(false),
+        };
+        let mut update = UpdateExpression::default();
+        update.set("reminder_sent_at", datetime_attribute(&Utc::now()));
+        update.remove("reminder_claimed_until");
+        update.remove(&self.config.gsi4_partition_key);
+        update.remove(&self.config.gsi4_sort_key);
+        let output = self
+            .update_item(key, |req| {
+                update.apply(
+                    req.condition_expression(
+                        "reminder_at = :reminder_at AND attribute_not_exists(reminder_sent_at)",
+                    )
+                    .expression_attribute_values(":reminder_at", datetime_attribute(reminder_at)),
+                )
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(false),
+            output => {
+                output?;
+                Ok(true)
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use async_graphql::futures_util::stream::{self, Stream};
+use async_graphql::{Context, Result, Subscription};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::graphql::errors::principal;
+use crate::graphql::reminders::ReminderEvents;
+use crate::graphql::types::Owner;
+
+use super::Todo;
+
+#[derive(Default)]
+pub struct TodoSubscription;
+
+#[Subscription]
+impl TodoSubscription {
+    /// Todos of the user whose reminder is sent by this server. Reminders sent while the client
+    /// lags too far behind are skipped.
+    async fn todo_reminders(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = Todo>> {
+        let owner = Owner::from(principal(ctx)?);
+        let receiver = ctx.data_unchecked::<ReminderEvents>().subscribe();
+        Ok(stream::unfold(receiver, move |mut receiver| {
+            let owner = owner.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(todo) if todo.owner == owner => return Some((todo, receiver)),
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        }))
+    }
+}
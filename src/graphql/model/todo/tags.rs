@@ -0,0 +1,45 @@
+use async_graphql::{ErrorExtensions, Result};
+
+use crate::dynamodb::item::ItemKey;
+use crate::graphql::errors::Errors;
+use crate::graphql::types::{Owner, ID};
+
+pub const TAG_TYPE_NAME: &str = "tag";
+
+/// Each tag requires an index item written in the same transaction as the todo.
+pub const MAX_TODO_TAGS: usize = 20;
+
+const MAX_TAG_LENGTH: usize = 64;
+
+/// Tags are trimmed and deduplicated. They can't contain the key separator, otherwise the prefix
+/// of a tag could match the index items of another one.
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.len() > MAX_TAG_LENGTH || tag.contains('#') {
+            return Err(Errors::InvalidValue(format!(
+                "Tags must be non-empty, at most {} characters long and can't contain '#'",
+                MAX_TAG_LENGTH
+            ))
+            .extend());
+        }
+        if !normalized.iter().any(|other| other == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    Ok(normalized)
+}
+
+/// Index item of a tagged todo. All the tags of an owner share the same partition, so the todos
+/// with a given tag are retrieved with a single query on the prefix of the tag.
+pub fn tag_key(owner: &Owner, tag: &str, id: &ID) -> ItemKey<String> {
+    ItemKey {
+        partition: owner.to_string(),
+        sort: format!("{}{}", tag_prefix(tag), String::from(id)),
+    }
+}
+
+pub fn tag_prefix(tag: &str) -> String {
+    format!("{}{}#", ID::prefix(TAG_TYPE_NAME), tag)
+}
@@ -1,28 +1,109 @@
+use async_graphql::connection::Connection;
 use async_graphql::{ComplexObject, Context, Result, SimpleObject};
+use chrono::{DateTime, Utc};
 
 use extensions::DynamoTableTodoListExt;
 
 use crate::dynamodb::DynamoTable;
-use crate::graphql::model::Todo;
-use crate::graphql::types::ID;
+use crate::graphql::limits::{COLLECTION_COMPLEXITY, RELATION_COMPLEXITY};
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::todo_list_member::extensions::DynamoTableTodoListMemberExt;
+use crate::graphql::model::{AuditRecord, Todo, TodoListMember};
+use crate::graphql::pagination::{build_connection, page_size};
+use crate::graphql::types::{Owner, ID};
 
+pub mod calendar;
+pub mod copy;
+pub mod counters;
 pub mod extensions;
+pub mod moves;
 pub mod mutation;
 pub mod query;
+pub mod transfer;
 
 pub const TODO_LIST_TYPE_NAME: &str = "todo_list";
 
-#[derive(Debug, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject)]
 #[graphql(complex)]
 pub struct TodoList {
     pub id: ID,
     pub title: String,
+    /// Archived lists are hidden from the collection by default.
+    pub archived: bool,
+    /// Templates are copied into new lists with `todoListFromTemplate`.
+    pub is_template: bool,
+    /// Todos of the list outside of the trash and the archive.
+    pub todo_count: usize,
+    pub completed_count: usize,
+    /// Set while the todo list is in the trash.
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[graphql(skip)]
+    pub owner: Owner,
 }
 
 #[ComplexObject]
 impl TodoList {
+    /// Share of the completed todos, from 0 to 1. Lists without todos have no progress.
+    async fn progress(&self) -> f64 {
+        if self.todo_count == 0 {
+            0.0
+        } else {
+            self.completed_count.min(self.todo_count) as f64 / self.todo_count as f64
+        }
+    }
+
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
     async fn todos(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb.get_todo_list_todos(&self.id).await
+        dynamodb.get_todo_list_todos(&self.owner, &self.id).await
+    }
+
+    /// Todos archived once completed, in creation order. The cursor is the id of a todo.
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn archived_todos(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<ID>,
+    ) -> Result<Connection<String, Todo>> {
+        let limit = page_size(first)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (todos, has_next_page) = dynamodb
+            .get_todo_list_archived_todos(&self.id, after.as_ref(), limit)
+            .await?;
+        Ok(build_connection(
+            todos,
+            after.is_some(),
+            has_next_page,
+            |todo| String::from(&todo.id),
+        ))
+    }
+
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn members(&self, ctx: &Context<'_>) -> Result<Vec<TodoListMember>> {
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        dynamodb.get_todo_list_members(&self.id).await
+    }
+
+    /// Changes of the todo list and its members in chronological order, the cursor being the id
+    /// of a record.
+    #[graphql(complexity = "RELATION_COMPLEXITY + COLLECTION_COMPLEXITY * child_complexity")]
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<ID>,
+    ) -> Result<Connection<String, AuditRecord>> {
+        let limit = page_size(first)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (records, has_next_page) = dynamodb
+            .get_audit_records(&self.id, after.as_ref(), limit)
+            .await?;
+        Ok(build_connection(
+            records,
+            after.is_some(),
+            has_next_page,
+            |record| String::from(&record.id),
+        ))
     }
 }
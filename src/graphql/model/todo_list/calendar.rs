@@ -0,0 +1,682 @@
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{Context, ErrorExtensions, Result, SimpleObject};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::todo::bulk::BULK_CONCURRENCY;
+use crate::graphql::model::todo::effects::todo_written;
+use crate::graphql::model::todo::extensions::{DynamoTableTodoExt, TodoUpdate, TodoWrite};
+use crate::graphql::model::todo::position::last_positions;
+use crate::graphql::model::todo::recurrence::complete_occurrence;
+use crate::graphql::model::todo::TodoPriority;
+use crate::graphql::model::{Todo, TodoList, TODO_TYPE_NAME};
+use crate::graphql::types::ID;
+use crate::graphql::Key;
+
+use super::counters::TodoCounts;
+use super::extensions::DynamoTableTodoListExt;
+use super::transfer::{
+    error_message, validate_fields, TodoFields, TodoListImportError, MAX_IMPORTED_TODOS,
+};
+
+const PRODUCT_ID: &str = "-//rust-graphql-exercise//Todo lists//EN";
+
+/// Content lines longer than this number of octets are folded.
+const MAX_LINE_LENGTH: usize = 75;
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Outcome of an iCalendar import. Invalid components are skipped and reported.
+#[derive(SimpleObject)]
+pub struct TodoListCalendarImportResult {
+    pub todo_list: TodoList,
+    /// Number of todos created for the components without any matching todo.
+    pub created: usize,
+    /// Number of todos of the list changed by their component.
+    pub updated: usize,
+    /// Number of todos of the list already matching their component.
+    pub unchanged: usize,
+    pub errors: Vec<TodoListImportError>,
+}
+
+/// Upper-cased name, parameters and value of a content line.
+type Property = (String, String, String);
+
+/// VTODO component parsed from a document.
+struct CalendarTodo {
+    /// Line of the `BEGIN:VTODO`.
+    line: usize,
+    uid: Option<String>,
+    fields: std::result::Result<TodoFields, String>,
+}
+
+/// iCalendar document with a VTODO component per todo of the list. The UID of a todo is the ULID
+/// of its id, and its last change is stored along with it. Only the todos not written since it's
+/// stored require a query of their last audit record.
+pub async fn export_calendar(
+    dynamodb: &DynamoTable,
+    todo_list: &TodoList,
+    todos: &[Todo],
+) -> Result<String> {
+    // Collected first, as the stream must not borrow the todos to be sent across the resolvers.
+    let todos_modified: Vec<(ID, Option<DateTime<Utc>>)> = todos
+        .iter()
+        .map(|todo| (todo.id.clone(), todo.updated_at))
+        .collect();
+    let modified: Vec<Result<DateTime<Utc>>> = stream::iter(todos_modified)
+        .map(|(id, updated_at)| async move {
+            if let Some(updated_at) = updated_at {
+                return Ok(updated_at);
+            }
+            Ok(match dynamodb.get_last_audit_record(&id).await? {
+                Some(record) => record.at,
+                None => created_at(&id),
+            })
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+
+    let now = Utc::now();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        format!("X-WR-CALNAME:{}", escape_text(&todo_list.title)),
+    ];
+    for (todo, modified) in todos.iter().zip(modified) {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", todo.id.ulid()));
+        lines.push(format!("DTSTAMP:{}", format_date_time(&now)));
+        lines.push(format!(
+            "CREATED:{}",
+            format_date_time(&created_at(&todo.id))
+        ));
+        lines.push(format!("LAST-MODIFIED:{}", format_date_time(&modified?)));
+        lines.push(format!("SUMMARY:{}", escape_text(&todo.title)));
+        lines.push(format!(
+            "STATUS:{}",
+            if todo.complete {
+                "COMPLETED"
+            } else {
+                "NEEDS-ACTION"
+            }
+        ));
+        if let Some(due_at) = &todo.due_at {
+            lines.push(format!("DUE:{}", format_date_time(due_at)));
+        }
+        if let Some(priority) = todo.priority {
+            lines.push(format!("PRIORITY:{}", calendar_priority(priority)));
+        }
+        if let Some(notes) = &todo.notes {
+            lines.push(format!("DESCRIPTION:{}", escape_text(notes)));
+        }
+        if !todo.tags.is_empty() {
+            let tags: Vec<String> = todo.tags.iter().map(|tag| escape_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(parent_id) = todo
+            .parent_id
+            .as_ref()
+            .filter(|parent_id| todos.iter().any(|other| other.id == **parent_id))
+        {
+            lines.push(format!("RELATED-TO:{}", parent_id.ulid()));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    Ok(lines.iter().map(|line| fold_line(line)).collect())
+}
+
+/// Updates the todos of the list whose ULID is the UID of a component, and appends a new todo to
+/// the list for each of the other components. Only the title, completion, due date, priority and
+/// notes of the existing todos are changed.
+pub async fn import_calendar(
+    ctx: &Context<'_>,
+    principal: &Principal,
+    mut todo_list: TodoList,
+    content: &str,
+) -> Result<TodoListCalendarImportResult> {
+    let components = parse_calendar(content)?;
+    if components.len() > MAX_IMPORTED_TODOS {
+        return Err(Errors::InvalidValue(format!(
+            "Imports can't contain more than {} todos",
+            MAX_IMPORTED_TODOS
+        ))
+        .extend());
+    }
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let existing = dynamodb
+        .get_todo_list_todos(&todo_list.owner, &todo_list.id)
+        .await?;
+
+    let mut errors = Vec::new();
+    let mut created = Vec::new();
+    let mut updates = Vec::new();
+    let mut update_lines = Vec::new();
+    let mut unchanged = 0;
+    let mut uids: Vec<String> = Vec::new();
+    let now = Utc::now();
+    for component in components {
+        let error = |message: String| TodoListImportError {
+            line: Some(component.line),
+            path: None,
+            message,
+        };
+        if let Some(uid) = &component.uid {
+            if uids.contains(uid) {
+                errors.push(error(format!("UID '{}' appears more than once", uid)));
+                continue;
+            }
+            uids.push(uid.clone());
+        }
+        let fields = match component.fields.and_then(validate_fields) {
+            Ok(fields) => fields,
+            Err(message) => {
+                errors.push(error(message));
+                continue;
+            }
+        };
+        let old_todo = component.uid.as_ref().and_then(|uid| {
+            existing
+                .iter()
+                .find(|todo| todo.id.ulid().to_string() == *uid)
+        });
+        match old_todo {
+            Some(old_todo) => {
+                let mut new_todo = Todo {
+                    title: fields.title,
+                    complete: fields.complete,
+                    due_at: fields.due_at,
+                    priority: fields.priority,
+                    notes: fields.notes,
+                    ..old_todo.clone()
+                };
+                if new_todo.title == old_todo.title
+                    && new_todo.complete == old_todo.complete
+                    && new_todo.due_at == old_todo.due_at
+                    && new_todo.priority == old_todo.priority
+                    && new_todo.notes == old_todo.notes
+                {
+                    unchanged += 1;
+                    continue;
+                }
+                let next = complete_occurrence(old_todo, &mut new_todo, now);
+                update_lines.push(component.line);
+                updates.push(TodoUpdate {
+                    old_key: Key {
+                        partition: todo_list.id.clone(),
+                        sort: old_todo.id.clone(),
+                    },
+                    old_todo: old_todo.clone(),
+                    new_todo,
+                    next,
+                });
+            }
+            None => created.push((component.line, fields)),
+        }
+    }
+
+    let todos: Vec<Todo> = created
+        .iter()
+        .zip(last_positions(created.len()))
+        .map(|((_, fields), position)| Todo {
+            id: ID::new(TODO_TYPE_NAME),
+            title: fields.title.clone(),
+            complete: fields.complete,
+            due_at: fields.due_at,
+            reminder_at: None,
+            reminder_sent_at: None,
+            priority: fields.priority,
+            notes: fields.notes.clone(),
+            auto_complete: false,
+            tags: fields.tags.clone(),
+            recurrence: None,
+            next_occurrence_id: None,
+            deleted_at: None,
+            updated_at: None,
+            list_id: Some(todo_list.id.clone()),
+            parent_id: None,
+            position,
+            owner: todo_list.owner.clone(),
+        })
+        .collect();
+    let create_results = dynamodb.put_todos(&todos, &principal.subject).await?;
+    let update_results = dynamodb.update_todos(&updates, &principal.subject).await;
+
+    let mut counts = TodoCounts::default();
+    let mut result = TodoListCalendarImportResult {
+        todo_list: todo_list.clone(),
+        created: 0,
+        updated: 0,
+        unchanged,
+        errors,
+    };
+    let error = |line: usize, message: String| TodoListImportError {
+        line: Some(line),
+        path: None,
+        message,
+    };
+    let writes = todos
+        .into_iter()
+        .map(TodoWrite::Create)
+        .zip(create_results)
+        .zip(created.iter().map(|(line, _)| *line));
+    let updates = updates
+        .into_iter()
+        .map(|update| TodoWrite::Update(Box::new(update)))
+        .zip(update_results)
+        .zip(update_lines);
+    for ((write, written), line) in writes.chain(updates) {
+        if let Err(err) = written {
+            result.errors.push(error(line, error_message(err)));
+            continue;
+        }
+        match &write {
+            TodoWrite::Create(todo) => {
+                result.created += 1;
+                counts.count(None, Some(todo));
+            }
+            TodoWrite::Update(update) => {
+                result.updated += 1;
+                counts.count(Some(&update.old_todo), Some(&update.new_todo));
+                counts.count(None, update.next.as_ref());
+            }
+            TodoWrite::Delete(..) => {}
+        }
+        if let Err(err) = todo_written(ctx, &write, &principal.subject).await {
+            result.errors.push(error(line, err.message));
+        }
+    }
+    counts.apply_to(&mut todo_list);
+    result.todo_list = todo_list;
+    result.errors.sort_by_key(|error| error.line);
+    Ok(result)
+}
+
+/// VTODO components of the document, the other components being ignored.
+fn parse_calendar(content: &str) -> Result<Vec<CalendarTodo>> {
+    let lines = unfold_lines(content);
+    if !lines
+        .first()
+        .is_some_and(|(_, line)| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err(Errors::InvalidValue(
+            "iCalendar documents must start with BEGIN:VCALENDAR".to_string(),
+        )
+        .extend());
+    }
+
+    let mut components = Vec::new();
+    // Properties of the VTODO being parsed, along with the line of its beginning.
+    let mut current: Option<(usize, Vec<Property>)> = None;
+    // Components nested in the VTODO, such as its alarms.
+    let mut nested: Vec<String> = Vec::new();
+    for (number, line) in lines {
+        let (name, parameters, value) = match split_content_line(&line) {
+            Some(property) => property,
+            None => {
+                if current.is_some() && nested.is_empty() {
+                    components.push(CalendarTodo {
+                        line: number,
+                        uid: None,
+                        fields: Err(format!("Invalid content line '{}'", line)),
+                    });
+                }
+                continue;
+            }
+        };
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some((number, Vec::new()))
+            }
+            ("BEGIN", Some(_)) => nested.push(value.to_uppercase()),
+            ("END", Some(_)) if !nested.is_empty() => {
+                nested.pop();
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VTODO") => {
+                if let Some((line, properties)) = current.take() {
+                    components.push(calendar_todo(line, &properties));
+                }
+            }
+            (_, Some((_, properties))) if nested.is_empty() => {
+                properties.push((name, parameters, value))
+            }
+            _ => {}
+        }
+    }
+    if let Some((line, _)) = current {
+        components.push(CalendarTodo {
+            line,
+            uid: None,
+            fields: Err("The VTODO component isn't terminated by END:VTODO".to_string()),
+        });
+    }
+    Ok(components)
+}
+
+fn calendar_todo(line: usize, properties: &[Property]) -> CalendarTodo {
+    let property = |name: &str| {
+        properties
+            .iter()
+            .find(|(other, _, _)| other == name)
+            .map(|(_, parameters, value)| (parameters.as_str(), value.as_str()))
+    };
+    let fields = || -> std::result::Result<TodoFields, String> {
+        Ok(TodoFields {
+            title: property("SUMMARY")
+                .map(|(_, summary)| unescape_text(summary))
+                .ok_or_else(|| "A VTODO requires a SUMMARY".to_string())?,
+            complete: match property("STATUS").map(|(_, status)| status.to_uppercase()) {
+                None => false,
+                Some(status) => match status.as_str() {
+                    "COMPLETED" => true,
+                    "NEEDS-ACTION" | "IN-PROCESS" | "CANCELLED" => false,
+                    _ => return Err(format!("Invalid STATUS '{}'", status)),
+                },
+            },
+            due_at: property("DUE")
+                .map(|(parameters, due)| parse_date_time(parameters, due))
+                .transpose()?,
+            priority: property("PRIORITY")
+                .map(|(_, priority)| parse_priority(priority))
+                .transpose()?
+                .flatten(),
+            notes: property("DESCRIPTION").map(|(_, description)| unescape_text(description)),
+            tags: properties
+                .iter()
+                .filter(|(name, _, _)| name == "CATEGORIES")
+                .flat_map(|(_, _, categories)| split_text_list(categories))
+                .collect(),
+        })
+    };
+    CalendarTodo {
+        line,
+        uid: property("UID").map(|(_, uid)| uid.trim().to_string()),
+        fields: fields(),
+    }
+}
+
+/// Joins the folded lines, which continue on the next lines starting with a space or a tab.
+/// Returns each content line along with the number of its first line.
+fn unfold_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its property. The value starts at the first colon outside of the
+/// quoted parameter values.
+fn split_content_line(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, parameters) = head.split_once(';').unwrap_or((head, ""));
+    if name.is_empty() {
+        return None;
+    }
+    Some((
+        name.to_uppercase(),
+        parameters.to_string(),
+        value.to_string(),
+    ))
+}
+
+/// Lines are folded at character boundaries, each continuation starting with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Values of a list separated by unescaped commas, such as the categories.
+fn split_text_list(text: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                values.push(unescape_text(&text[start..index]));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(unescape_text(&text[start..]));
+    values.retain(|value| !value.trim().is_empty());
+    values
+}
+
+fn format_date_time(date_time: &DateTime<Utc>) -> String {
+    format!("{}Z", date_time.format(DATE_TIME_FORMAT))
+}
+
+/// Dates are due at midnight UTC. There's no time zone database, so local times, floating or
+/// with a `TZID`, are read as UTC.
+fn parse_date_time(parameters: &str, value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    let is_date = parameters.to_uppercase().contains("VALUE=DATE") && !value.contains('T');
+    let parsed = if is_date || value.len() == 8 {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    } else {
+        NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), DATE_TIME_FORMAT).ok()
+    };
+    parsed
+        .map(|date_time| Utc.from_utc_datetime(&date_time))
+        .ok_or_else(|| format!("Invalid DUE '{}'", value))
+}
+
+/// From 1, the highest priority, to 9, the lowest one.
+fn calendar_priority(priority: TodoPriority) -> u8 {
+    match priority {
+        TodoPriority::Urgent => 1,
+        TodoPriority::High => 3,
+        TodoPriority::Medium => 5,
+        TodoPriority::Low => 9,
+    }
+}
+
+/// 0 means the priority is undefined.
+fn parse_priority(priority: &str) -> std::result::Result<Option<TodoPriority>, String> {
+    match priority.trim().parse::<u8>() {
+        Ok(0) => Ok(None),
+        Ok(1..=2) => Ok(Some(TodoPriority::Urgent)),
+        Ok(3..=4) => Ok(Some(TodoPriority::High)),
+        Ok(5) => Ok(Some(TodoPriority::Medium)),
+        Ok(6..=9) => Ok(Some(TodoPriority::Low)),
+        _ => Err(format!("Invalid PRIORITY '{}'", priority)),
+    }
+}
+
+fn created_at(id: &ID) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(id.timestamp_ms() as i64)
+        .single()
+        .unwrap_or_else(Utc::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn folds_long_lines_at_character_boundaries() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold_line(&line);
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(unfold_lines(&folded), [(1, line)]);
+        assert_eq!(fold_line("SUMMARY:Short"), "SUMMARY:Short\r\n");
+    }
+
+    #[test]
+    fn unescapes_text() {
+        assert_eq!(
+            unescape_text(r"Milk\, eggs\; flour\nand \\butter\N"),
+            "Milk, eggs; flour\nand \\butter\n"
+        );
+        assert_eq!(unescape_text(r"Trailing\"), "Trailing");
+        let text = "a,b;c\\d\ne";
+        assert_eq!(unescape_text(&escape_text(text)), text);
+    }
+
+    #[test]
+    fn splits_text_lists_on_unescaped_commas() {
+        assert_eq!(
+            split_text_list(r"home,milk\, eggs,,\\,work"),
+            ["home", "milk, eggs", "\\", "work"]
+        );
+        assert!(split_text_list("").is_empty());
+    }
+
+    #[test]
+    fn parses_dates_and_date_times() {
+        assert_eq!(
+            parse_date_time("", "20261018T143000Z"),
+            Ok(date_time("2026-10-18T14:30:00Z"))
+        );
+        assert_eq!(
+            parse_date_time(";TZID=Europe/Paris", "20261018T143000"),
+            Ok(date_time("2026-10-18T14:30:00Z"))
+        );
+        assert_eq!(
+            parse_date_time(";VALUE=DATE", "20261018"),
+            Ok(date_time("2026-10-18T00:00:00Z"))
+        );
+        assert_eq!(
+            parse_date_time("", "20261018"),
+            Ok(date_time("2026-10-18T00:00:00Z"))
+        );
+        assert!(parse_date_time("", "tomorrow").is_err());
+        assert!(parse_date_time("", "20261318T143000Z").is_err());
+    }
+
+    #[test]
+    fn parses_todo_components() {
+        let content = [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            "BEGIN:VEVENT",
+            "SUMMARY:Ignored",
+            "END:VEVENT",
+            "BEGIN:VTODO",
+            "UID:01GCG9PT4MR7WHA1X1ETSPY7TX",
+            "SUMMARY:Buy milk\\, eggs and a very long list of groceries which doesn't fit on",
+            "  a single line, café included",
+            "STATUS:COMPLETED",
+            "DUE;VALUE=DATE:20261018",
+            "PRIORITY:1",
+            "DESCRIPTION:First line\\nSecond line",
+            "CATEGORIES:home,shopping\\, weekly",
+            "CATEGORIES:errands",
+            "BEGIN:VALARM",
+            "DESCRIPTION:Nested",
+            "END:VALARM",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "STATUS:UNKNOWN",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "SUMMARY:Unterminated",
+            "END:VCALENDAR",
+        ]
+        .join("\r\n");
+        let components = parse_calendar(&content).unwrap();
+        assert_eq!(components.len(), 3);
+
+        let todo = &components[0];
+        assert_eq!(todo.line, 6);
+        assert_eq!(todo.uid.as_deref(), Some("01GCG9PT4MR7WHA1X1ETSPY7TX"));
+        let fields = todo.fields.as_ref().unwrap();
+        assert_eq!(
+            fields.title,
+            "Buy milk, eggs and a very long list of groceries which doesn't fit on a single line, \
+             café included"
+        );
+        assert!(fields.complete);
+        assert_eq!(fields.due_at, Some(date_time("2026-10-18T00:00:00Z")));
+        assert_eq!(fields.priority, Some(TodoPriority::Urgent));
+        assert_eq!(fields.notes.as_deref(), Some("First line\nSecond line"));
+        assert_eq!(fields.tags, ["home", "shopping, weekly", "errands"]);
+
+        assert_eq!(components[1].line, 20);
+        assert!(components[1].fields.is_err());
+        assert_eq!(components[2].line, 23);
+        assert!(components[2].fields.is_err());
+    }
+
+    #[test]
+    fn rejects_documents_without_calendar() {
+        assert!(parse_calendar("BEGIN:VTODO\r\nSUMMARY:Milk\r\nEND:VTODO\r\n").is_err());
+        assert!(parse_calendar("").is_err());
+    }
+}
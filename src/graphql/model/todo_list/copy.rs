@@ -0,0 +1,133 @@
+use async_graphql::{Context, ErrorExtensions, Result, SimpleObject};
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::todo::bulk::TodoError;
+use crate::graphql::model::todo::effects::todo_written;
+use crate::graphql::model::todo::extensions::{DynamoTableTodoExt, TodoWrite};
+use crate::graphql::model::webhook::WebhookEvent;
+use crate::graphql::model::{Todo, TodoList, TODO_TYPE_NAME};
+use crate::graphql::search::search;
+use crate::graphql::types::ID;
+use crate::graphql::webhooks::webhooks;
+
+use super::counters::TodoCounts;
+use super::extensions::DynamoTableTodoListExt;
+
+/// Outcome of a copy of a list. The copy is kept even if some of its todos couldn't be written.
+#[derive(SimpleObject)]
+pub struct TodoListCopyResult {
+    pub todo_list: TodoList,
+    /// Number of todos copied.
+    pub copied: usize,
+    /// Todos of the source which couldn't be copied, identified by their id in the source.
+    pub failed: Vec<TodoError>,
+    /// Todos of the source which were copied, but whose copy couldn't complete its parents.
+    pub errors: Vec<TodoError>,
+}
+
+/// Copies the todos of the source list into the new list `copy`, each of them with a new id but
+/// the same position. Parents within the source list are replaced by their copy, while the others
+/// are only kept for a copy of the same owner. `edit` is applied on every copied todo.
+///
+/// Todos are written once the list is created, so a failure leaves a partial copy, whose failed
+/// todos are reported along with it.
+pub async fn copy_todo_list<F>(
+    ctx: &Context<'_>,
+    principal: &Principal,
+    source: &TodoList,
+    mut copy: TodoList,
+    edit: F,
+) -> Result<TodoListCopyResult>
+where
+    F: Fn(&mut Todo) -> Result<()>,
+{
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let todos = dynamodb
+        .get_todo_list_todos(&source.owner, &source.id)
+        .await?;
+    let ids: Vec<ID> = todos.iter().map(|_| ID::new(TODO_TYPE_NAME)).collect();
+    let copy_id = |id: &ID| {
+        todos
+            .iter()
+            .position(|todo| todo.id == *id)
+            .map(|index| ids[index].clone())
+    };
+    let mut copies = Vec::with_capacity(todos.len());
+    for (todo, id) in todos.iter().zip(&ids) {
+        let mut todo = Todo {
+            id: id.clone(),
+            list_id: Some(copy.id.clone()),
+            parent_id: todo.parent_id.as_ref().and_then(|parent_id| {
+                copy_id(parent_id)
+                    .or_else(|| Some(parent_id.clone()).filter(|_| copy.owner == source.owner))
+            }),
+            reminder_sent_at: None,
+            next_occurrence_id: None,
+            owner: copy.owner.clone(),
+            ..todo.clone()
+        };
+        edit(&mut todo)?;
+        copies.push(todo);
+    }
+
+    dynamodb.put_todo_list(&copy, &principal.subject).await?;
+    let results = dynamodb.put_todos(&copies, &principal.subject).await?;
+    webhooks(ctx).todo_list_event(WebhookEvent::TodoListCreated, &copy);
+    search(ctx).index_todo_list(&copy);
+    let mut copied = 0;
+    let mut failed = Vec::new();
+    let mut errors = Vec::new();
+    let mut counts = TodoCounts::default();
+    for (index, ((todo, result), original)) in
+        copies.into_iter().zip(results).zip(&todos).enumerate()
+    {
+        match result {
+            Ok(()) => {
+                copied += 1;
+                counts.count(None, Some(&todo));
+                let write = TodoWrite::Create(todo);
+                if let Err(err) = todo_written(ctx, &write, &principal.subject).await {
+                    errors.push(TodoError::new(index, Some(original.id.clone()), err));
+                }
+            }
+            // Failures of DynamoDB are reported with an internal error code.
+            Err(err) => failed.push(TodoError::new(index, Some(original.id.clone()), err)),
+        }
+    }
+    counts.apply_to(&mut copy);
+    Ok(TodoListCopyResult {
+        todo_list: copy,
+        copied,
+        failed,
+        errors,
+    })
+}
+
+/// Replaces the `{{name}}` placeholders by their value. Placeholders without any value are
+/// rejected, so a template can't be instantiated partially.
+pub fn substitute_placeholders(text: &str, values: &[(String, String)]) -> Result<String> {
+    let mut substituted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = rest[start + 2..end].trim();
+        let value = values
+            .iter()
+            .find(|(placeholder, _)| placeholder == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                Errors::InvalidValue(format!("Missing value for the placeholder '{}'", name))
+                    .extend()
+            })?;
+        substituted.push_str(&rest[..start]);
+        substituted.push_str(value);
+        rest = &rest[end + 2..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
@@ -0,0 +1,233 @@
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::update::Builder as UpdateBuilder;
+use aws_sdk_dynamodb::model::{AttributeValue, TransactWriteItem};
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, UpdateExpression};
+use crate::graphql::model::{Todo, TODO_LIST_TYPE_NAME, TODO_TYPE_NAME};
+use crate::graphql::types::ID;
+use crate::graphql::Key;
+
+use super::TodoList;
+
+pub const TODO_COUNT_ATTRIBUTE: &str = "todo_count";
+pub const COMPLETED_COUNT_ATTRIBUTE: &str = "completed_count";
+
+/// Changes of the counters of the todo lists, written with `ADD` in the same transaction as the
+/// todos. A transaction can't write an item twice, so the changes of a list are merged.
+#[derive(Debug, Clone, Default)]
+pub struct TodoCounts(Vec<(ID, i64, i64)>);
+
+impl TodoCounts {
+    /// Counts the change of a todo, a missing todo being created or archived. Only the todos of a
+    /// list outside of the trash are counted.
+    pub fn count(&mut self, old: Option<&Todo>, new: Option<&Todo>) {
+        self.add_todo(old, -1);
+        self.add_todo(new, 1);
+    }
+
+    /// Merges the changes of another set of todos.
+    pub fn extend(&mut self, other: &TodoCounts) {
+        for (id, todos, completed) in &other.0 {
+            self.add(id, *todos, *completed);
+        }
+    }
+
+    fn add_todo(&mut self, todo: Option<&Todo>, delta: i64) {
+        if let Some(Todo {
+            list_id: Some(list_id),
+            deleted_at: None,
+            complete,
+            ..
+        }) = todo
+        {
+            self.add(list_id, delta, if *complete { delta } else { 0 });
+        }
+    }
+
+    fn add(&mut self, list_id: &ID, todos: i64, completed: i64) {
+        match self.0.iter_mut().find(|(id, _, _)| id == list_id) {
+            Some((_, list_todos, list_completed)) => {
+                *list_todos += todos;
+                *list_completed += completed;
+            }
+            None => self.0.push((list_id.clone(), todos, completed)),
+        }
+    }
+
+    /// Updates of the lists whose counters changed. They fail if the list doesn't exist, rather
+    /// than creating an item with only the counters, so they're written with `transact_write`.
+    pub fn transact_items(&self, dynamodb: &DynamoTable) -> Vec<TransactWriteItem> {
+        self.changed()
+            .map(|(id, todos, completed)| {
+                dynamodb.transact_update(&todo_list_key(id), |req| {
+                    counts_update(*todos, *completed).apply(list_condition(dynamodb, req))
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the items in a single transaction along with the changes of the counters. Todos may
+    /// outlive their list, such as when it expired from the trash before them, so if a condition
+    /// fails, the changes of the lists which don't exist anymore are dropped and the transaction
+    /// is written again. Otherwise, the failure is returned as is.
+    pub async fn transact_write(
+        &self,
+        dynamodb: &DynamoTable,
+        items: Vec<TransactWriteItem>,
+    ) -> std::result::Result<(), DynamoDbErrors> {
+        let mut counted = items.clone();
+        counted.extend(self.transact_items(dynamodb));
+        if counted.is_empty() {
+            return Ok(());
+        }
+        match dynamodb
+            .transact_write(|transaction| transaction.set_transact_items(Some(counted)))
+            .await
+        {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => {
+                let existing = self.existing(dynamodb).await?;
+                if existing.changed().count() == self.changed().count() {
+                    return Err(DynamoDbErrors::ConditionalCheckFailed);
+                }
+                warn!("Skipped the counters of missing todo lists");
+                let mut counted = items;
+                counted.extend(existing.transact_items(dynamodb));
+                if counted.is_empty() {
+                    return Ok(());
+                }
+                dynamodb
+                    .transact_write(|transaction| transaction.set_transact_items(Some(counted)))
+                    .await?;
+            }
+            output => {
+                output?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes of the lists which still exist.
+    async fn existing(
+        &self,
+        dynamodb: &DynamoTable,
+    ) -> std::result::Result<TodoCounts, DynamoDbErrors> {
+        let mut existing = TodoCounts::default();
+        for (id, todos, completed) in self.changed() {
+            let output = dynamodb
+                .get_item(&todo_list_key(id), |req| {
+                    req.projection_expression("#list")
+                        .expression_attribute_names("#list", &dynamodb.config.partition_key)
+                })
+                .await?;
+            if output.item.is_some() {
+                existing.add(id, *todos, *completed);
+            }
+        }
+        Ok(existing)
+    }
+
+    /// Applies the changes to a todo list already retrieved, so it can be returned as written.
+    pub fn apply_to(&self, todo_list: &mut TodoList) {
+        if let Some((_, todos, completed)) = self.0.iter().find(|(id, _, _)| *id == todo_list.id) {
+            todo_list.todo_count = add_count(todo_list.todo_count, *todos);
+            todo_list.completed_count = add_count(todo_list.completed_count, *completed);
+        }
+    }
+
+    fn changed(&self) -> impl Iterator<Item = &(ID, i64, i64)> {
+        self.0
+            .iter()
+            .filter(|(_, todos, completed)| *todos != 0 || *completed != 0)
+    }
+}
+
+/// Recomputes the counters of the given todo lists from the todos of their partition, or of every
+/// todo list of the table when none is given. Todos written meanwhile may be missed, so it's
+/// better run while the lists are idle. Returns the number of repaired lists.
+pub async fn repair_todo_list_counts(dynamodb: &DynamoTable, ids: &[ID]) -> Result<usize> {
+    let ids = if ids.is_empty() {
+        let mut ids = Vec::new();
+        let mut paginator = dynamodb
+            .scan_entities_by_prefix(&ID::prefix(TODO_LIST_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                ids.push(item.get_from_string(&dynamodb.config.partition_key)?);
+            }
+        }
+        ids
+    } else {
+        ids.to_vec()
+    };
+
+    for id in &ids {
+        let (mut todos, mut completed) = (0, 0);
+        let mut paginator = dynamodb
+            .query_partition_by_prefix(id, &ID::prefix(TODO_TYPE_NAME))
+            .filter_expression("attribute_not_exists(deleted_at)")
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                todos += 1;
+                if *item.get_bool("complete")? {
+                    completed += 1;
+                }
+            }
+        }
+        dynamodb
+            .update_item(&todo_list_key(id), |req| {
+                let mut update = UpdateExpression::default();
+                update.set(TODO_COUNT_ATTRIBUTE, AttributeValue::N(todos.to_string()));
+                update.set(
+                    COMPLETED_COUNT_ATTRIBUTE,
+                    AttributeValue::N(completed.to_string()),
+                );
+                update.apply(
+                    req.condition_expression("attribute_exists(#list)")
+                        .expression_attribute_names("#list", &dynamodb.config.partition_key),
+                )
+            })
+            .await?;
+        info!(
+            "Repaired the counters of the todo list '{}': {}/{} todos completed",
+            id, completed, todos
+        );
+    }
+    Ok(ids.len())
+}
+
+/// Counters may have drifted below zero before being repaired.
+fn add_count(count: usize, delta: i64) -> usize {
+    usize::try_from(count as i64 + delta).unwrap_or(0)
+}
+
+fn todo_list_key(id: &ID) -> Key {
+    Key {
+        partition: id.clone(),
+        sort: id.clone(),
+    }
+}
+
+fn counts_update(todos: i64, completed: i64) -> UpdateExpression {
+    let mut update = UpdateExpression::default();
+    if todos != 0 {
+        update.add(TODO_COUNT_ATTRIBUTE, AttributeValue::N(todos.to_string()));
+    }
+    if completed != 0 {
+        update.add(
+            COMPLETED_COUNT_ATTRIBUTE,
+            AttributeValue::N(completed.to_string()),
+        );
+    }
+    update
+}
+
+fn list_condition(dynamodb: &DynamoTable, req: UpdateBuilder) -> UpdateBuilder {
+    req.condition_expression("attribute_exists(#list)")
+        .expression_attribute_names("#list", &dynamodb.config.partition_key)
+}
@@ -1,135 +1,458 @@
 use std::convert::identity;
 
-use async_graphql::{Error, Result};
-use aws_sdk_dynamodb::model::{AttributeValue, ReturnValue};
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
 use tokio_stream::StreamExt;
 
-use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes};
-use crate::graphql::model::{Todo, TODO_LIST_TYPE_NAME, TODO_TYPE_NAME};
-use crate::graphql::types::ID;
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes, UpdateExpression};
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::audit::{AuditOperation, Changes};
+use crate::graphql::model::todo::extensions::{build_todo, DynamoTableTodoExt};
+use crate::graphql::model::todo::ARCHIVED_TODO_TYPE_NAME;
+use crate::graphql::model::trash::{filter_trash, is_expired, set_deleted_at};
+use crate::graphql::model::{AuditRecord, Todo, TODO_LIST_TYPE_NAME, TODO_TYPE_NAME};
+use crate::graphql::types::{Owner, ID};
 use crate::graphql::Key;
 
+use super::counters::{COMPLETED_COUNT_ATTRIBUTE, TODO_COUNT_ATTRIBUTE};
 use super::TodoList;
 
+/// Hash of the token of the calendar feed, stored on the item of the list.
+const CALENDAR_TOKEN_ATTRIBUTE: &str = "calendar_token";
+
 /// Extension used to decorate the DynamoTable with specialized methods for TodoList
 #[async_trait::async_trait]
 pub trait DynamoTableTodoListExt {
-    async fn scan_todo_list(&self) -> Result<Vec<TodoList>>;
-    async fn get_todo_list_todos(&self, id: &ID) -> Result<Vec<Todo>>;
-    async fn get_todo_list(&self, id: &ID) -> Result<Option<TodoList>>;
-    async fn put_todo_list(&self, todo_list: &TodoList) -> Result<bool>;
-    async fn update_todo_list(&self, id: &ID, new_title: String) -> Result<TodoList>;
-    async fn delete_todo_list(&self, id: &ID) -> Result<Option<TodoList>>;
+    async fn scan_todo_list(&self, owner: &Owner, include_archived: bool) -> Result<Vec<TodoList>>;
+    async fn get_todo_list_todos(&self, owner: &Owner, id: &ID) -> Result<Vec<Todo>>;
+    /// Archived todos of a list in creation order, starting after the given todo. Also returns
+    /// whether there are more todos.
+    async fn get_todo_list_archived_todos(
+        &self,
+        id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<Todo>, bool)>;
+    async fn get_todo_list(&self, owner: &Owner, id: &ID) -> Result<Option<TodoList>>;
+    async fn scan_deleted_todo_list(&self, owner: &Owner) -> Result<Vec<TodoList>>;
+    async fn get_deleted_todo_list(&self, owner: &Owner, id: &ID) -> Result<Option<TodoList>>;
+    async fn put_todo_list(&self, todo_list: &TodoList, actor: &str) -> Result<bool>;
+    async fn update_todo_list(
+        &self,
+        todo_list: &TodoList,
+        new_todo_list: TodoList,
+        actor: &str,
+    ) -> Result<Option<TodoList>>;
+    /// Moves the todo list to the trash along with its todos, which are returned as well.
+    async fn delete_todo_list(
+        &self,
+        todo_list: &TodoList,
+        actor: &str,
+    ) -> Result<Option<(TodoList, Vec<Todo>)>>;
+    async fn restore_todo_list(
+        &self,
+        todo_list: &TodoList,
+        actor: &str,
+    ) -> Result<Option<(TodoList, Vec<Todo>)>>;
+    /// Stores the hash of the token of the calendar feed of the list, replacing the previous one,
+    /// or removes it so the feed isn't served anymore. Returns `false` if the todo list doesn't
+    /// belong to its owner anymore.
+    async fn set_todo_list_calendar_token(
+        &self,
+        todo_list: &TodoList,
+        token: Option<&str>,
+    ) -> Result<bool>;
+    /// Todo list whose calendar feed has the given token, `None` for any other token.
+    async fn get_todo_list_by_calendar_token(
+        &self,
+        id: &ID,
+        token: &str,
+    ) -> Result<Option<TodoList>>;
 }
 
 #[async_trait::async_trait]
 impl DynamoTableTodoListExt for DynamoTable {
-    async fn scan_todo_list(&self) -> Result<Vec<TodoList>> {
+    async fn scan_todo_list(&self, owner: &Owner, include_archived: bool) -> Result<Vec<TodoList>> {
         let mut todo_lists: Vec<TodoList> = Vec::new();
-        let mut paginator = self
-            .scan()
-            .filter_expression("begins_with(#sk, :sk)")
-            .expression_attribute_names("#sk", &self.config.sort_key)
-            .expression_attribute_values(":sk", AttributeValue::S(ID::prefix(TODO_LIST_TYPE_NAME)))
-            .into_paginator()
-            .send();
+        let query = self.query_gsi2_by_prefix(owner, &ID::prefix(TODO_LIST_TYPE_NAME));
+        let query = if include_archived {
+            query.filter_expression("attribute_not_exists(deleted_at)")
+        } else {
+            query
+                .filter_expression(
+                    "attribute_not_exists(deleted_at) AND \
+                     (attribute_not_exists(archived) OR archived = :false)",
+                )
+                .expression_attribute_values(":false", AttributeValue::Bool(false))
+        };
+        let mut paginator = query.into_paginator().send();
 
         while let Some(output) = paginator.next().await {
             for item in output?.items().unwrap_or_default() {
-                let todo_list = TodoList {
-                    id: item.get_from_string(&self.config.partition_key)?,
-                    title: item.get_string("title")?.clone(),
-                };
-                todo_lists.push(todo_list);
+                let id = item.get_from_string(&self.config.partition_key)?;
+                todo_lists.push(build_todo_list(self, &id, item)?);
             }
         }
         Ok(todo_lists)
     }
 
-    async fn get_todo_list_todos(&self, id: &ID) -> Result<Vec<Todo>> {
+    async fn get_todo_list_todos(&self, owner: &Owner, id: &ID) -> Result<Vec<Todo>> {
         let mut todos: Vec<Todo> = Vec::new();
         let mut paginator = self
-            .query_partition_by_prefix(&id, &ID::prefix(TODO_TYPE_NAME))
+            .query_partition_by_prefix(id, &ID::prefix(TODO_TYPE_NAME))
+            .filter_expression("#owner = :owner AND attribute_not_exists(deleted_at)")
+            .expression_attribute_names("#owner", &self.config.gsi2_partition_key)
+            .expression_attribute_values(":owner", AttributeValue::S(owner.to_string()))
             .into_paginator()
             .send();
         while let Some(output) = paginator.next().await {
             for item in output?.items().unwrap_or_default() {
-                let todo = Todo {
-                    id: item.get_from_string(&self.config.gsi1_partition_key)?,
-                    title: item.get_string("title")?.clone(),
-                    complete: *item.get_bool("complete")?,
-                    list_id: Some(id.clone()),
-                };
-                todos.push(todo);
+                let key = self.extract_key(item)?;
+                todos.push(build_todo(self, &key, item)?);
             }
         }
+        todos.sort_by(|a, b| a.position.total_cmp(&b.position));
         Ok(todos)
     }
 
-    async fn get_todo_list(&self, id: &ID) -> Result<Option<TodoList>> {
+    async fn get_todo_list_archived_todos(
+        &self,
+        id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<Todo>, bool)> {
+        let after = after.map(|todo_id| todo_id.with_type_name(ARCHIVED_TODO_TYPE_NAME));
+        let (items, has_next_page) = self
+            .query_partition_page(
+                id,
+                &ID::prefix(ARCHIVED_TODO_TYPE_NAME),
+                after.as_ref(),
+                limit,
+            )
+            .await?;
+        let todos = items
+            .iter()
+            .map(|item| {
+                let key = self.extract_key(item)?;
+                build_todo(self, &key, item)
+            })
+            .collect::<Result<Vec<Todo>>>()?;
+        Ok((todos, has_next_page))
+    }
+
+    async fn get_todo_list(&self, owner: &Owner, id: &ID) -> Result<Option<TodoList>> {
         let key = Key {
             partition: id.clone(),
             sort: id.clone(),
         };
         let output = self.get_item(&key, identity).await?;
         Ok(if let Some(item) = output.item {
-            Some(build_todo_list(id, &item)?)
+            // Lists of other owners can't be distinguished from missing ones.
+            Some(build_todo_list(self, id, &item)?)
+                .filter(|todo_list| todo_list.owner == *owner && todo_list.deleted_at.is_none())
         } else {
             None
         })
     }
 
-    async fn put_todo_list(&self, todo_list: &TodoList) -> Result<bool> {
+    async fn scan_deleted_todo_list(&self, owner: &Owner) -> Result<Vec<TodoList>> {
+        let mut todo_lists: Vec<TodoList> = Vec::new();
+        let query = self.query_gsi2_by_prefix(owner, &ID::prefix(TODO_LIST_TYPE_NAME));
+        let mut paginator = filter_trash(self, query, None).into_paginator().send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let id = item.get_from_string(&self.config.partition_key)?;
+                todo_lists.push(build_todo_list(self, &id, item)?);
+            }
+        }
+        Ok(todo_lists)
+    }
+
+    async fn get_deleted_todo_list(&self, owner: &Owner, id: &ID) -> Result<Option<TodoList>> {
+        let key = Key {
+            partition: id.clone(),
+            sort: id.clone(),
+        };
+        let output = self.get_item(&key, identity).await?;
+        Ok(match output.item {
+            Some(item) if !is_expired(self, &item)? => Some(build_todo_list(self, id, &item)?)
+                .filter(|todo_list| todo_list.owner == *owner && todo_list.deleted_at.is_some()),
+            _ => None,
+        })
+    }
+
+    async fn put_todo_list(&self, todo_list: &TodoList, actor: &str) -> Result<bool> {
         let key = Key {
             partition: todo_list.id.clone(),
             sort: todo_list.id.clone(),
         };
-
-        self.put_item(&key, |req| {
-            req.item("title", AttributeValue::S(todo_list.title.clone()))
+        let record = AuditRecord::new(
+            &todo_list.id,
+            actor,
+            AuditOperation::Create,
+            todo_list_changes(None, Some(todo_list)),
+        );
+        self.transact_write(|transaction| {
+            transaction
+                .transact_items(self.transact_put(&key, |put| {
+                    put.item("title", AttributeValue::S(todo_list.title.clone()))
+                        .item("archived", AttributeValue::Bool(todo_list.archived))
+                        .item("is_template", AttributeValue::Bool(todo_list.is_template))
+                        .item(
+                            &self.config.gsi2_partition_key,
+                            AttributeValue::S(todo_list.owner.to_string()),
+                        )
+                        .item(
+                            &self.config.gsi2_sort_key,
+                            AttributeValue::S(String::from(&todo_list.id)),
+                        )
+                }))
+                .transact_items(self.transact_put_audit(&record))
         })
         .await?;
         Ok(true)
     }
 
-    async fn update_todo_list(&self, id: &ID, new_title: String) -> Result<TodoList> {
+    /// Returns `None` if the todo list doesn't belong to its owner anymore.
+    async fn update_todo_list(
+        &self,
+        todo_list: &TodoList,
+        new_todo_list: TodoList,
+        actor: &str,
+    ) -> Result<Option<TodoList>> {
         let key = Key {
-            partition: id.clone(),
-            sort: id.clone(),
+            partition: todo_list.id.clone(),
+            sort: todo_list.id.clone(),
+        };
+        let changes = todo_list_changes(Some(todo_list), Some(&new_todo_list));
+        if changes.is_empty() {
+            return Ok(Some(new_todo_list));
+        }
+        let record = AuditRecord::new(&todo_list.id, actor, AuditOperation::Update, changes);
+        let output = self
+            .transact_write(|transaction| {
+                transaction
+                    .transact_items(self.transact_update(&key, |req| {
+                        let mut update = UpdateExpression::default();
+                        if todo_list.title != new_todo_list.title {
+                            update.set("title", AttributeValue::S(new_todo_list.title.clone()));
+                        }
+                        if todo_list.archived != new_todo_list.archived {
+                            update.set("archived", AttributeValue::Bool(new_todo_list.archived));
+                        }
+                        if todo_list.is_template != new_todo_list.is_template {
+                            update.set(
+                                "is_template",
+                                AttributeValue::Bool(new_todo_list.is_template),
+                            );
+                        }
+                        update.apply(
+                            req.condition_expression(
+                                "#owner = :owner AND attribute_not_exists(deleted_at)",
+                            )
+                            .expression_attribute_names("#owner", &self.config.gsi2_partition_key)
+                            .expression_attribute_values(
+                                ":owner",
+                                AttributeValue::S(todo_list.owner.to_string()),
+                            ),
+                        )
+                    }))
+                    .transact_items(self.transact_put_audit(&record))
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(None),
+            output => {
+                output?;
+                Ok(Some(new_todo_list))
+            }
+        }
+    }
+
+    async fn delete_todo_list(
+        &self,
+        todo_list: &TodoList,
+        actor: &str,
+    ) -> Result<Option<(TodoList, Vec<Todo>)>> {
+        set_todo_list_deleted_at(self, todo_list, Some(Utc::now()), actor).await
+    }
+
+    async fn restore_todo_list(
+        &self,
+        todo_list: &TodoList,
+        actor: &str,
+    ) -> Result<Option<(TodoList, Vec<Todo>)>> {
+        set_todo_list_deleted_at(self, todo_list, None, actor).await
+    }
+
+    async fn set_todo_list_calendar_token(
+        &self,
+        todo_list: &TodoList,
+        token: Option<&str>,
+    ) -> Result<bool> {
+        let key = Key {
+            partition: todo_list.id.clone(),
+            sort: todo_list.id.clone(),
         };
         let output = self
             .update_item(&key, |req| {
-                req.update_expression("SET title = :title")
-                    .expression_attribute_values(":title", AttributeValue::S(new_title))
-                    .return_values(ReturnValue::AllNew)
+                let mut update = UpdateExpression::default();
+                update.set_or_remove(
+                    CALENDAR_TOKEN_ATTRIBUTE,
+                    token.map(|token| AttributeValue::S(calendar_token_hash(token))),
+                );
+                update.apply(
+                    req.condition_expression(
+                        "#owner = :owner AND attribute_not_exists(deleted_at)",
+                    )
+                    .expression_attribute_names("#owner", &self.config.gsi2_partition_key)
+                    .expression_attribute_values(
+                        ":owner",
+                        AttributeValue::S(todo_list.owner.to_string()),
+                    ),
+                )
             })
-            .await?;
-        if let Some(item) = output.attributes {
-            Ok(build_todo_list(id, &item)?)
-        } else {
-            Err(Error::new("Missing attributes"))
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(false),
+            output => {
+                output?;
+                Ok(true)
+            }
         }
     }
 
-    async fn delete_todo_list(&self, id: &ID) -> Result<Option<TodoList>> {
+    async fn get_todo_list_by_calendar_token(
+        &self,
+        id: &ID,
+        token: &str,
+    ) -> Result<Option<TodoList>> {
         let key = Key {
             partition: id.clone(),
             sort: id.clone(),
         };
-        let output = self
-            .delete_item(&key, |req| req.return_values(ReturnValue::AllOld))
-            .await?;
-        Ok(if let Some(item) = output.attributes {
-            Some(build_todo_list(id, &item)?)
-        } else {
-            None
-        })
+        let output = self.get_item(&key, identity).await?;
+        let item = match output.item {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        // Only the hash is stored, so the tokens can't be read from the table.
+        if item.get_optional_string(CALENDAR_TOKEN_ATTRIBUTE)? != Some(&calendar_token_hash(token))
+        {
+            return Ok(None);
+        }
+        Ok(Some(build_todo_list(self, id, &item)?)
+            .filter(|todo_list| todo_list.deleted_at.is_none()))
     }
 }
 
-fn build_todo_list(id: &ID, item: &RawAttributes) -> Result<TodoList> {
+fn calendar_token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Moves a todo list to the trash, or restores it when `deleted_at` is missing. Its todos follow
+/// once the list is moved, so they don't outlive it once purged, while its members are left
+/// untouched. Returns `None` if the todo list doesn't belong to the owner anymore or was already
+/// moved.
+async fn set_todo_list_deleted_at(
+    dynamodb: &DynamoTable,
+    todo_list: &TodoList,
+    deleted_at: Option<DateTime<Utc>>,
+    actor: &str,
+) -> Result<Option<(TodoList, Vec<Todo>)>> {
+    let key = Key {
+        partition: todo_list.id.clone(),
+        sort: todo_list.id.clone(),
+    };
+    let new_todo_list = TodoList {
+        deleted_at,
+        ..todo_list.clone()
+    };
+    let operation = if deleted_at.is_some() {
+        AuditOperation::Delete
+    } else {
+        AuditOperation::Restore
+    };
+    let record = AuditRecord::new(
+        &todo_list.id,
+        actor,
+        operation,
+        todo_list_changes(Some(todo_list), Some(&new_todo_list)),
+    );
+    let output = dynamodb
+        .transact_write(|transaction| {
+            transaction
+                .transact_items(dynamodb.transact_update(&key, |req| {
+                    let mut update = UpdateExpression::default();
+                    set_deleted_at(dynamodb, &mut update, deleted_at.as_ref());
+                    update.apply(
+                        req.condition_expression(if deleted_at.is_some() {
+                            "#owner = :owner AND attribute_not_exists(deleted_at)"
+                        } else {
+                            "#owner = :owner AND attribute_exists(deleted_at)"
+                        })
+                        .expression_attribute_names("#owner", &dynamodb.config.gsi2_partition_key)
+                        .expression_attribute_values(
+                            ":owner",
+                            AttributeValue::S(todo_list.owner.to_string()),
+                        ),
+                    )
+                }))
+                .transact_items(dynamodb.transact_put_audit(&record))
+        })
+        .await;
+    match output {
+        Err(DynamoDbErrors::ConditionalCheckFailed) => return Ok(None),
+        output => output?,
+    };
+    let todos = dynamodb
+        .set_todo_list_todos_deleted_at(&todo_list.id, deleted_at.as_ref())
+        .await?;
+    Ok(Some((new_todo_list, todos)))
+}
+
+/// Fields of a todo list which differ, a missing todo list having none of them.
+fn todo_list_changes(old: Option<&TodoList>, new: Option<&TodoList>) -> Changes {
+    let title = |todo_list: &TodoList| todo_list.title.clone();
+    let deleted_at = |todo_list: &TodoList| {
+        todo_list
+            .deleted_at
+            .map(|deleted_at| deleted_at.to_rfc3339())
+    };
+    let archived = |todo_list: &TodoList| todo_list.archived.to_string();
+    let is_template = |todo_list: &TodoList| todo_list.is_template.to_string();
+    let mut changes = Changes::default();
+    changes.diff("title", old.map(title), new.map(title));
+    changes.diff("archived", old.map(archived), new.map(archived));
+    changes.diff("isTemplate", old.map(is_template), new.map(is_template));
+    changes.diff(
+        "deletedAt",
+        old.and_then(deleted_at),
+        new.and_then(deleted_at),
+    );
+    changes
+}
+
+pub fn build_todo_list(dynamodb: &DynamoTable, id: &ID, item: &RawAttributes) -> Result<TodoList> {
     Ok(TodoList {
         id: id.clone(),
         title: item.get_string("title")?.clone(),
+        archived: *item.get_optional_bool("archived")?.unwrap_or(&false),
+        is_template: *item.get_optional_bool("is_template")?.unwrap_or(&false),
+        todo_count: get_count(item, TODO_COUNT_ATTRIBUTE)?,
+        completed_count: get_count(item, COMPLETED_COUNT_ATTRIBUTE)?,
+        deleted_at: item.get_optional_from_string("deleted_at")?,
+        owner: item.get_from_string(&dynamodb.config.gsi2_partition_key)?,
     })
 }
+
+/// Counters are only written once a todo changes, and may have drifted below zero.
+fn get_count(item: &RawAttributes, name: &str) -> Result<usize> {
+    let count: Option<i64> = item.get_optional_from_number(name)?;
+    Ok(count
+        .and_then(|count| usize::try_from(count).ok())
+        .unwrap_or(0))
+}
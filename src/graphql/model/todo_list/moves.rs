@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{Context, Error, ErrorExtensions, Result, SimpleObject};
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::todo::bulk::{TodoError, BULK_CONCURRENCY};
+use crate::graphql::model::todo::effects::todo_written;
+use crate::graphql::model::todo::extensions::{DynamoTableTodoExt, TodoUpdate, TodoWrite};
+use crate::graphql::model::todo::position::last_positions;
+use crate::graphql::model::{Todo, TodoList};
+use crate::graphql::Key;
+
+use super::counters::TodoCounts;
+
+/// Outcome of moving todos from a list to another one.
+#[derive(SimpleObject)]
+pub struct TodoListMoveResult {
+    pub source: TodoList,
+    pub target: TodoList,
+    /// Number of todos which had to be moved, sub-tasks of the selected todos included.
+    pub total: usize,
+    /// Todos moved, appended to the target list in their previous order.
+    pub moved: Vec<Todo>,
+    pub failed: Vec<TodoError>,
+    /// Todos moved, but whose parents couldn't be completed once moved.
+    pub errors: Vec<TodoError>,
+    /// Transactional chunks of the move, in the order they were written.
+    pub chunks: Vec<TodoListMoveChunk>,
+}
+
+/// Progress of a transactional chunk, whose todos all moved or none of them did.
+#[derive(SimpleObject)]
+pub struct TodoListMoveChunk {
+    pub moved: usize,
+    pub failed: usize,
+}
+
+/// Moves the selected todos of the list to the end of the target list in transactional chunks,
+/// along with their sub-tasks within the list so a parent never leaves them behind. Sub-tasks can
+/// only move to a list of another owner along with their parent, and parents along with their
+/// sub-tasks, while sub-tasks whose parent was archived or deleted are detached from it.
+pub async fn move_todos<F>(
+    ctx: &Context<'_>,
+    principal: &Principal,
+    mut source: TodoList,
+    mut target: TodoList,
+    todos: Vec<Todo>,
+    selected: F,
+) -> Result<TodoListMoveResult>
+where
+    F: Fn(&Todo) -> bool,
+{
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let todos = with_subtasks(todos, selected);
+    let mut errors = if target.owner != source.owner {
+        check_other_owner(dynamodb, &todos).await?
+    } else {
+        HashMap::new()
+    };
+    // Sub-tasks of a todo which can't move can't move either, at any depth.
+    loop {
+        let blocked: Vec<String> = todos
+            .iter()
+            .filter(|todo| !errors.contains_key(&String::from(&todo.id)))
+            .filter(|todo| {
+                todo.parent_id.as_ref().is_some_and(|parent_id| {
+                    matches!(
+                        errors.get(&String::from(parent_id)),
+                        Some(TodoMoveError::Invalid(_))
+                    )
+                })
+            })
+            .map(|todo| String::from(&todo.id))
+            .collect();
+        if blocked.is_empty() {
+            break;
+        }
+        for id in blocked {
+            let err = Errors::InvalidValue(format!(
+                "Todo '{}' can't be moved to a list of another owner without its parent",
+                id
+            ));
+            errors.insert(id, TodoMoveError::Invalid(err.extend()));
+        }
+    }
+
+    let mut failed = Vec::new();
+    let mut updates = Vec::with_capacity(todos.len());
+    let mut indexes = Vec::with_capacity(todos.len());
+    for ((index, todo), position) in todos.iter().enumerate().zip(last_positions(todos.len())) {
+        let parent_id = match errors.remove(&String::from(&todo.id)) {
+            Some(TodoMoveError::Invalid(err)) => {
+                failed.push(TodoError::new(index, Some(todo.id.clone()), err));
+                continue;
+            }
+            Some(TodoMoveError::MissingParent) => None,
+            None => todo.parent_id.clone(),
+        };
+        updates.push(TodoUpdate {
+            old_key: Key {
+                partition: source.id.clone(),
+                sort: todo.id.clone(),
+            },
+            old_todo: todo.clone(),
+            new_todo: Todo {
+                list_id: Some(target.id.clone()),
+                owner: target.owner.clone(),
+                parent_id,
+                position,
+                ..todo.clone()
+            },
+            next: None,
+        });
+        indexes.push(index);
+    }
+
+    let mut moved = Vec::with_capacity(updates.len());
+    let mut errors = Vec::new();
+    let mut chunks = Vec::new();
+    // Counters are returned as written along with the moved todos.
+    let mut counts = TodoCounts::default();
+    let results = dynamodb
+        .update_todo_chunks(&updates, &principal.subject)
+        .await;
+    let mut written = updates.into_iter().zip(indexes);
+    for (count, result) in results {
+        chunks.push(TodoListMoveChunk {
+            moved: if result.is_ok() { count } else { 0 },
+            failed: if result.is_ok() { 0 } else { count },
+        });
+        for (update, index) in written.by_ref().take(count) {
+            match &result {
+                Ok(()) => {
+                    counts.count(Some(&update.old_todo), Some(&update.new_todo));
+                    let write = TodoWrite::Update(Box::new(update));
+                    if let Err(err) = todo_written(ctx, &write, &principal.subject).await {
+                        errors.push(TodoError::new(index, Some(write.todo().id.clone()), err));
+                    }
+                    moved.push(write.todo().clone());
+                }
+                Err(err) => {
+                    failed.push(TodoError::new(index, Some(update.new_todo.id), err.clone()))
+                }
+            }
+        }
+    }
+    counts.apply_to(&mut source);
+    counts.apply_to(&mut target);
+    Ok(TodoListMoveResult {
+        source,
+        target,
+        total: todos.len(),
+        moved,
+        failed,
+        errors,
+        chunks,
+    })
+}
+
+enum TodoMoveError {
+    Invalid(Error),
+    /// The parent was archived or deleted, so the todo is detached from it.
+    MissingParent,
+}
+
+/// Todos of the list which are selected or whose parent is moved, at any depth, in the order of
+/// the list.
+fn with_subtasks<F>(todos: Vec<Todo>, selected: F) -> Vec<Todo>
+where
+    F: Fn(&Todo) -> bool,
+{
+    let mut moved: HashSet<String> = todos
+        .iter()
+        .filter(|todo| selected(todo))
+        .map(|todo| String::from(&todo.id))
+        .collect();
+    loop {
+        let len = moved.len();
+        for todo in &todos {
+            if let Some(parent_id) = &todo.parent_id {
+                if moved.contains(&String::from(parent_id)) {
+                    moved.insert(String::from(&todo.id));
+                }
+            }
+        }
+        if moved.len() == len {
+            break;
+        }
+    }
+    todos
+        .into_iter()
+        .filter(|todo| moved.contains(&String::from(&todo.id)))
+        .collect()
+}
+
+/// Links between the moved todos and the todos staying with the previous owner, in other lists,
+/// can't be kept. Such parents and sub-tasks are retrieved concurrently, which costs a query per
+/// moved todo.
+async fn check_other_owner(
+    dynamodb: &DynamoTable,
+    todos: &[Todo],
+) -> Result<HashMap<String, TodoMoveError>> {
+    let ids: HashSet<String> = todos.iter().map(|todo| String::from(&todo.id)).collect();
+    let checks: Vec<Result<Option<TodoMoveError>>> = stream::iter(todos.to_vec())
+        .map(|todo| {
+            let ids = &ids;
+            async move { check_links(dynamodb, &todo, ids).await }
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+    let mut errors = HashMap::new();
+    for (todo, check) in todos.iter().zip(checks) {
+        if let Some(err) = check? {
+            errors.insert(String::from(&todo.id), err);
+        }
+    }
+    Ok(errors)
+}
+
+async fn check_links(
+    dynamodb: &DynamoTable,
+    todo: &Todo,
+    ids: &HashSet<String>,
+) -> Result<Option<TodoMoveError>> {
+    if let Some(parent_id) = &todo.parent_id {
+        if !ids.contains(&String::from(parent_id)) {
+            if dynamodb.get_todo(&todo.owner, parent_id).await?.is_none() {
+                return Ok(Some(TodoMoveError::MissingParent));
+            }
+            let err = Errors::InvalidValue(format!(
+                "Todo '{}' can't be moved to a list of another owner without its parent",
+                todo.id
+            ));
+            return Ok(Some(TodoMoveError::Invalid(err.extend())));
+        }
+    }
+    let children = dynamodb.get_todo_children(&todo.owner, &todo.id).await?;
+    if children
+        .iter()
+        .any(|child| !ids.contains(&String::from(&child.id)))
+    {
+        return Ok(Some(TodoMoveError::Invalid(
+            Errors::InvalidValue(format!(
+                "Todo '{}' can't be moved to a list of another owner without its sub-tasks",
+                todo.id
+            ))
+            .extend(),
+        )));
+    }
+    Ok(None)
+}
@@ -1,21 +1,70 @@
-use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result};
+use std::collections::HashSet;
+
+use async_graphql::futures_util::stream::{self, StreamExt};
+use async_graphql::{Context, ErrorExtensions, InputObject, MaybeUndefined, Object, Result};
+use chrono::{DateTime, Utc};
 
 use crate::dynamodb::DynamoTable;
-use crate::graphql::errors::{check_id_kind, Errors};
-use crate::graphql::types::ID;
+use crate::graphql::errors::{check_id_kind, non_nullable, principal, Errors};
+use crate::graphql::model::todo::bulk::BULK_CONCURRENCY;
+use crate::graphql::model::todo::extensions::{DynamoTableTodoExt, TodoUpdate};
+use crate::graphql::model::todo_list_member::access::authorize_todo_list;
+use crate::graphql::model::webhook::WebhookEvent;
+use crate::graphql::model::{Todo, TodoListRole};
+use crate::graphql::search::search;
+use crate::graphql::types::{Owner, ID};
+use crate::graphql::webhooks::webhooks;
+use crate::graphql::Key;
 
+use super::calendar::{import_calendar, TodoListCalendarImportResult};
+use super::copy::{copy_todo_list, substitute_placeholders, TodoListCopyResult};
+use super::counters::TodoCounts;
 use super::extensions::DynamoTableTodoListExt;
+use super::moves::{move_todos, TodoListMoveResult};
+use super::transfer::{import_todo_list, TodoListFormat, TodoListImportResult};
 use super::{TodoList, TODO_LIST_TYPE_NAME};
 
+/// Number of random bytes of the token of a calendar feed.
+const CALENDAR_TOKEN_BYTES: usize = 32;
+
 #[derive(Debug, InputObject)]
 struct TodoListInputCreate {
     title: String,
+    is_template: Option<bool>,
 }
 
 #[derive(Debug, InputObject)]
 struct TodoListInputUpdate {
     id: ID,
-    title: Option<String>,
+    title: MaybeUndefined<String>,
+    archived: MaybeUndefined<bool>,
+    is_template: MaybeUndefined<bool>,
+}
+
+/// Value of the `{{name}}` placeholders of a template.
+#[derive(Debug, InputObject)]
+struct TemplatePlaceholderInput {
+    name: String,
+    value: String,
+}
+
+/// Todos matching all the set fields.
+#[derive(Debug, Default, InputObject)]
+struct TodoListMoveFilter {
+    complete: Option<bool>,
+    tag: Option<String>,
+    due_before: Option<DateTime<Utc>>,
+}
+
+impl TodoListMoveFilter {
+    fn matches(&self, todo: &Todo) -> bool {
+        self.complete
+            .is_none_or(|complete| todo.complete == complete)
+            && self.tag.as_ref().is_none_or(|tag| todo.tags.contains(tag))
+            && self
+                .due_before
+                .is_none_or(|due_before| todo.due_at.is_some_and(|due_at| due_at < due_before))
+    }
 }
 
 #[derive(Default)]
@@ -28,12 +77,23 @@ impl TodoListMutation {
         ctx: &Context<'_>,
         input: TodoListInputCreate,
     ) -> Result<TodoList> {
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
         let todo_list = TodoList {
             id: ID::new(TODO_LIST_TYPE_NAME),
             title: input.title,
+            archived: false,
+            is_template: input.is_template.unwrap_or(false),
+            todo_count: 0,
+            completed_count: 0,
+            deleted_at: None,
+            owner: Owner::from(principal),
         };
-        dynamodb.put_todo_list(&todo_list).await?;
+        dynamodb
+            .put_todo_list(&todo_list, &principal.subject)
+            .await?;
+        webhooks(ctx).todo_list_event(WebhookEvent::TodoListCreated, &todo_list);
+        search(ctx).index_todo_list(&todo_list);
         Ok(todo_list)
     }
 
@@ -43,24 +103,357 @@ impl TodoListMutation {
         input: TodoListInputUpdate,
     ) -> Result<TodoList> {
         check_id_kind(&input.id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list =
+            authorize_todo_list(dynamodb, principal, &input.id, TodoListRole::Editor).await?;
 
-        if let Some(title) = input.title {
-            dynamodb.update_todo_list(&input.id, title).await
-        } else {
-            dynamodb
-                .get_todo_list(&input.id)
+        let new_todo_list = TodoList {
+            title: non_nullable(input.title, "title")?.unwrap_or_else(|| todo_list.title.clone()),
+            archived: non_nullable(input.archived, "archived")?.unwrap_or(todo_list.archived),
+            is_template: non_nullable(input.is_template, "isTemplate")?
+                .unwrap_or(todo_list.is_template),
+            ..todo_list.clone()
+        };
+        let todo_list = dynamodb
+            .update_todo_list(&todo_list, new_todo_list, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        webhooks(ctx).todo_list_event(WebhookEvent::TodoListUpdated, &todo_list);
+        search(ctx).index_todo_list(&todo_list);
+        Ok(todo_list)
+    }
+
+    /// Archives the completed todos of the list, so they're only listed in `archivedTodos`. Their
+    /// sub-tasks which aren't archived with them, whatever their list, are detached beforehand.
+    async fn todo_list_archive_completed(&self, ctx: &Context<'_>, id: ID) -> Result<TodoList> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let mut todo_list =
+            authorize_todo_list(dynamodb, principal, &id, TodoListRole::Editor).await?;
+
+        let todos: Vec<Todo> = dynamodb
+            .get_todo_list_todos(&todo_list.owner, &id)
+            .await?
+            .into_iter()
+            .filter(|todo| todo.complete)
+            .collect();
+        // Sub-tasks may be in other lists, so they're retrieved concurrently, which costs a query
+        // per archived todo.
+        let ids: HashSet<String> = todos.iter().map(|todo| String::from(&todo.id)).collect();
+        let children: Vec<Result<Vec<Todo>>> = stream::iter(todos.clone())
+            .map(|todo| async move { dynamodb.get_todo_children(&todo.owner, &todo.id).await })
+            .buffered(BULK_CONCURRENCY)
+            .collect()
+            .await;
+        let mut detached: Vec<TodoUpdate> = Vec::new();
+        for child in children {
+            for todo in child? {
+                if ids.contains(&String::from(&todo.id)) {
+                    continue;
+                }
+                detached.push(TodoUpdate {
+                    old_key: Key {
+                        partition: todo.list_id.clone().unwrap_or_else(|| todo.id.clone()),
+                        sort: todo.id.clone(),
+                    },
+                    new_todo: Todo {
+                        parent_id: None,
+                        ..todo.clone()
+                    },
+                    old_todo: todo,
+                    next: None,
+                });
+            }
+        }
+        for result in dynamodb.update_todos(&detached, &principal.subject).await {
+            result?;
+        }
+        dynamodb.archive_todos(&todos, &principal.subject).await?;
+
+        let mut counts = TodoCounts::default();
+        let search = search(ctx);
+        let webhooks = webhooks(ctx);
+        for update in &detached {
+            webhooks.todo_event(WebhookEvent::TodoUpdated, &update.new_todo);
+        }
+        for todo in &todos {
+            counts.count(Some(todo), None);
+            search.remove(&todo.id);
+        }
+        counts.apply_to(&mut todo_list);
+        webhooks.todo_list_event(WebhookEvent::TodoListUpdated, &todo_list);
+        Ok(todo_list)
+    }
+
+    /// Moves the todos matching the filter to the end of another list, in transactional chunks.
+    /// Todos of a failed chunk are reported without preventing the others from moving.
+    async fn todo_list_move_todos(
+        &self,
+        ctx: &Context<'_>,
+        from: ID,
+        to: ID,
+        filter: Option<TodoListMoveFilter>,
+    ) -> Result<TodoListMoveResult> {
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (source, target) = authorize_move(ctx, &from, &to, TodoListRole::Editor).await?;
+        let filter = filter.unwrap_or_default();
+        let todos = dynamodb.get_todo_list_todos(&source.owner, &from).await?;
+        let result = move_todos(ctx, principal, source, target, todos, |todo| {
+            filter.matches(todo)
+        })
+        .await?;
+        let webhooks = webhooks(ctx);
+        webhooks.todo_list_event(WebhookEvent::TodoListUpdated, &result.source);
+        webhooks.todo_list_event(WebhookEvent::TodoListUpdated, &result.target);
+        Ok(result)
+    }
+
+    /// Moves all the todos of the source list to the end of the target list, then moves the
+    /// source list to the trash once all of them moved. Archived todos stay in the source list.
+    async fn todo_list_merge(
+        &self,
+        ctx: &Context<'_>,
+        source: ID,
+        target: ID,
+    ) -> Result<TodoListMoveResult> {
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let (source_list, target_list) =
+            authorize_move(ctx, &source, &target, TodoListRole::Owner).await?;
+        let todos = dynamodb
+            .get_todo_list_todos(&source_list.owner, &source)
+            .await?;
+        let mut result =
+            move_todos(ctx, principal, source_list, target_list, todos, |_| true).await?;
+        let webhooks = webhooks(ctx);
+        webhooks.todo_list_event(WebhookEvent::TodoListUpdated, &result.target);
+        if result.failed.is_empty() {
+            let (source, todos) = dynamodb
+                .delete_todo_list(&result.source, &principal.subject)
                 .await?
-                .ok_or_else(|| Errors::NotFound.extend())
+                .ok_or_else(|| Errors::NotFound.extend())?;
+            result.source = source;
+            webhooks.todo_list_event(WebhookEvent::TodoListDeleted, &result.source);
+            let search = search(ctx);
+            search.remove(&result.source.id);
+            for todo in &todos {
+                search.remove(&todo.id);
+            }
+        } else {
+            webhooks.todo_list_event(WebhookEvent::TodoListUpdated, &result.source);
+        }
+        Ok(result)
+    }
+
+    /// Copies the list and its todos into a new list of the principal. The copy keeps the title
+    /// of the source unless another one is given, and only keeps the completion of the todos when
+    /// `resetComplete` is false.
+    async fn todo_list_duplicate(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        title: Option<String>,
+        #[graphql(default = false)] reset_complete: bool,
+    ) -> Result<TodoListCopyResult> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let source = authorize_todo_list(dynamodb, principal, &id, TodoListRole::Viewer).await?;
+        let copy = TodoList {
+            id: ID::new(TODO_LIST_TYPE_NAME),
+            title: title.unwrap_or_else(|| source.title.clone()),
+            archived: false,
+            is_template: source.is_template,
+            todo_count: 0,
+            completed_count: 0,
+            deleted_at: None,
+            owner: Owner::from(principal),
+        };
+        copy_todo_list(ctx, principal, &source, copy, |todo| {
+            todo.complete &= !reset_complete;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Creates a list from a template, replacing the `{{name}}` placeholders of the titles by
+    /// their value. The todos of the new list aren't completed.
+    async fn todo_list_from_template(
+        &self,
+        ctx: &Context<'_>,
+        template: ID,
+        title: String,
+        #[graphql(default)] placeholders: Vec<TemplatePlaceholderInput>,
+    ) -> Result<TodoListCopyResult> {
+        check_id_kind(&template, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let source =
+            authorize_todo_list(dynamodb, principal, &template, TodoListRole::Viewer).await?;
+        if !source.is_template {
+            return Err(
+                Errors::InvalidValue(format!("Todo list '{}' isn't a template", template)).extend(),
+            );
         }
+        let values: Vec<(String, String)> = placeholders
+            .into_iter()
+            .map(|placeholder| (placeholder.name, placeholder.value))
+            .collect();
+        let copy = TodoList {
+            id: ID::new(TODO_LIST_TYPE_NAME),
+            title: substitute_placeholders(&title, &values)?,
+            archived: false,
+            is_template: false,
+            todo_count: 0,
+            completed_count: 0,
+            deleted_at: None,
+            owner: Owner::from(principal),
+        };
+        copy_todo_list(ctx, principal, &source, copy, |todo| {
+            todo.title = substitute_placeholders(&todo.title, &values)?;
+            todo.complete = false;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Creates a list of the principal from a document in the given format. The title of the
+    /// document is used unless another non-blank one is given, and is required for CSV documents.
+    async fn todo_list_import(
+        &self,
+        ctx: &Context<'_>,
+        format: TodoListFormat,
+        content: String,
+        title: Option<String>,
+    ) -> Result<TodoListImportResult> {
+        let principal = principal(ctx)?;
+        let todo_list = TodoList {
+            id: ID::new(TODO_LIST_TYPE_NAME),
+            title: String::new(),
+            archived: false,
+            is_template: false,
+            todo_count: 0,
+            completed_count: 0,
+            deleted_at: None,
+            owner: Owner::from(principal),
+        };
+        import_todo_list(ctx, principal, todo_list, title, format, &content).await
+    }
+
+    /// Creates or updates the todos of the list from the VTODO components of an iCalendar
+    /// document, matching the todos by UID. It's also accepted at `/lists/{id}.ics`.
+    async fn todo_list_import_calendar(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        content: String,
+    ) -> Result<TodoListCalendarImportResult> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list = authorize_todo_list(dynamodb, principal, &id, TodoListRole::Editor).await?;
+        let result = import_calendar(ctx, principal, todo_list, &content).await?;
+        webhooks(ctx).todo_list_event(WebhookEvent::TodoListUpdated, &result.todo_list);
+        Ok(result)
+    }
+
+    /// Creates the token of the calendar feed of the list, served without any bearer token at
+    /// `/lists/{id}.ics?token=TOKEN`, and revokes the previous one. Only its hash is stored, so
+    /// it's only returned once.
+    async fn todo_list_calendar_feed_create(&self, ctx: &Context<'_>, id: ID) -> Result<String> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list = authorize_todo_list(dynamodb, principal, &id, TodoListRole::Owner).await?;
+        let token = hex::encode(rand::random::<[u8; CALENDAR_TOKEN_BYTES]>());
+        if !dynamodb
+            .set_todo_list_calendar_token(&todo_list, Some(&token))
+            .await?
+        {
+            return Err(Errors::NotFound.extend());
+        }
+        Ok(token)
+    }
+
+    /// Revokes the token of the calendar feed of the list, which isn't served anymore.
+    async fn todo_list_calendar_feed_revoke(&self, ctx: &Context<'_>, id: ID) -> Result<TodoList> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list = authorize_todo_list(dynamodb, principal, &id, TodoListRole::Owner).await?;
+        if !dynamodb
+            .set_todo_list_calendar_token(&todo_list, None)
+            .await?
+        {
+            return Err(Errors::NotFound.extend());
+        }
+        Ok(todo_list)
     }
 
     async fn todo_list_delete(&self, ctx: &Context<'_>, id: ID) -> Result<TodoList> {
         check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb
-            .delete_todo_list(&id)
+        let todo_list = authorize_todo_list(dynamodb, principal, &id, TodoListRole::Owner).await?;
+        let (todo_list, todos) = dynamodb
+            .delete_todo_list(&todo_list, &principal.subject)
             .await?
-            .ok_or_else(|| Errors::NotFound.extend())
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        webhooks(ctx).todo_list_event(WebhookEvent::TodoListDeleted, &todo_list);
+        let search = search(ctx);
+        search.remove(&todo_list.id);
+        for todo in &todos {
+            search.remove(&todo.id);
+        }
+        Ok(todo_list)
+    }
+
+    /// Restores a todo list from the trash of the owner.
+    async fn todo_list_restore(&self, ctx: &Context<'_>, id: ID) -> Result<TodoList> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list = dynamodb
+            .get_deleted_todo_list(&Owner::from(principal), &id)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        let (todo_list, todos) = dynamodb
+            .restore_todo_list(&todo_list, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        webhooks(ctx).todo_list_event(WebhookEvent::TodoListRestored, &todo_list);
+        let search = search(ctx);
+        search.index_todo_list(&todo_list);
+        for todo in &todos {
+            search.index_todo(todo);
+        }
+        Ok(todo_list)
+    }
+}
+
+/// Todos are moved from the source list with the given role, to a target list the principal can
+/// edit.
+async fn authorize_move(
+    ctx: &Context<'_>,
+    source: &ID,
+    target: &ID,
+    required: TodoListRole,
+) -> Result<(TodoList, TodoList)> {
+    check_id_kind(source, TODO_LIST_TYPE_NAME)?;
+    check_id_kind(target, TODO_LIST_TYPE_NAME)?;
+    if source == target {
+        return Err(Errors::InvalidValue(format!(
+            "Todos of the todo list '{}' can't be moved to itself",
+            source
+        ))
+        .extend());
     }
+    let principal = principal(ctx)?;
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    let source = authorize_todo_list(dynamodb, principal, source, required).await?;
+    let target = authorize_todo_list(dynamodb, principal, target, TodoListRole::Editor).await?;
+    Ok((source, target))
 }
@@ -1,10 +1,15 @@
 use async_graphql::{Context, ErrorExtensions, Object, Result};
 
 use crate::dynamodb::DynamoTable;
-use crate::graphql::errors::{check_id_kind, Errors};
-use crate::graphql::types::ID;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::limits::COLLECTION_COMPLEXITY;
+use crate::graphql::model::todo_list_member::access::get_todo_list_with_role;
+use crate::graphql::model::todo_list_member::extensions::DynamoTableTodoListMemberExt;
+use crate::graphql::types::{Owner, ID};
 
+use super::calendar::export_calendar;
 use super::extensions::DynamoTableTodoListExt;
+use super::transfer::{export_todo_list, TodoListFormat};
 use super::{TodoList, TODO_LIST_TYPE_NAME};
 
 #[derive(Default)]
@@ -12,17 +17,96 @@ pub struct TodoListQuery;
 
 #[Object]
 impl TodoListQuery {
-    async fn todo_list_collection(&self, ctx: &Context<'_>) -> Result<Vec<TodoList>> {
+    /// Todo lists of the owner, followed by the ones shared with the user. Archived lists are
+    /// only included if requested.
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn todo_list_collection(
+        &self,
+        ctx: &Context<'_>,
+        include_archived: Option<bool>,
+    ) -> Result<Vec<TodoList>> {
+        let include_archived = include_archived.unwrap_or(false);
+        let principal = principal(ctx)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb.scan_todo_list().await
+        let mut todo_lists = dynamodb
+            .scan_todo_list(&Owner::from(principal), include_archived)
+            .await?;
+        for member in dynamodb
+            .get_shared_todo_list_members(&principal.subject)
+            .await?
+        {
+            if todo_lists
+                .iter()
+                .any(|todo_list| todo_list.id == member.list_id)
+            {
+                continue;
+            }
+            // Deleted lists may still have members.
+            if let Some(todo_list) = dynamodb
+                .get_todo_list(&member.list_owner, &member.list_id)
+                .await?
+                .filter(|todo_list| include_archived || !todo_list.archived)
+            {
+                todo_lists.push(todo_list);
+            }
+        }
+        Ok(todo_lists)
     }
 
     pub async fn todo_list(&self, ctx: &Context<'_>, id: ID) -> Result<TodoList> {
         check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
         let dynamodb = ctx.data_unchecked::<DynamoTable>();
-        dynamodb
-            .get_todo_list(&id)
+        get_todo_list_with_role(dynamodb, principal(ctx)?, &id)
             .await?
             .ok_or_else(|| Errors::NotFound.extend())
+            .map(|(todo_list, _)| todo_list)
+    }
+
+    /// Document of the list and its todos outside of the trash and the archive, which can be
+    /// imported back with `todoListImport`.
+    async fn todo_list_export(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        format: TodoListFormat,
+    ) -> Result<String> {
+        let todo_list = self.todo_list(ctx, id).await?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todos = dynamodb
+            .get_todo_list_todos(&todo_list.owner, &todo_list.id)
+            .await?;
+        export_todo_list(&todo_list, &todos, format)
+    }
+
+    /// iCalendar document of the todos of the list, as VTODO components. It's also served at
+    /// `/lists/{id}.ics`.
+    async fn todo_list_calendar(&self, ctx: &Context<'_>, id: ID) -> Result<String> {
+        let todo_list = self.todo_list(ctx, id).await?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todos = dynamodb
+            .get_todo_list_todos(&todo_list.owner, &todo_list.id)
+            .await?;
+        export_calendar(dynamodb, &todo_list, &todos).await
+    }
+
+    /// iCalendar document of the list whose calendar feed has the given token, created with
+    /// `todoListCalendarFeedCreate`. It doesn't require any principal, so it's served at
+    /// `/lists/{id}.ics?token=TOKEN` for the calendar apps which can't send a bearer token.
+    async fn todo_list_calendar_feed(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        token: String,
+    ) -> Result<String> {
+        check_id_kind(&id, TODO_LIST_TYPE_NAME)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list = dynamodb
+            .get_todo_list_by_calendar_token(&id, &token)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        let todos = dynamodb
+            .get_todo_list_todos(&todo_list.owner, &todo_list.id)
+            .await?;
+        export_calendar(dynamodb, &todo_list, &todos).await
     }
 }
@@ -0,0 +1,843 @@
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::{Context, Enum, Error, ErrorExtensions, Result, SimpleObject, Value};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::todo::effects::todo_written;
+use crate::graphql::model::todo::extensions::{DynamoTableTodoExt, TodoWrite};
+use crate::graphql::model::todo::hierarchy::MAX_TODO_DEPTH;
+use crate::graphql::model::todo::position::rebalanced_positions;
+use crate::graphql::model::todo::tags::{normalize_tags, MAX_TODO_TAGS};
+use crate::graphql::model::todo::TodoPriority;
+use crate::graphql::model::webhook::WebhookEvent;
+use crate::graphql::model::{Todo, TodoList, TODO_TYPE_NAME};
+use crate::graphql::search::search;
+use crate::graphql::types::ID;
+use crate::graphql::webhooks::webhooks;
+
+use super::counters::TodoCounts;
+use super::extensions::DynamoTableTodoListExt;
+
+/// Maximum number of todos of an imported document.
+pub const MAX_IMPORTED_TODOS: usize = 1000;
+
+/// Columns of the CSV documents, only `title` being required on import.
+const CSV_COLUMNS: [&str; 7] = [
+    "title", "complete", "due_at", "priority", "notes", "tags", "depth",
+];
+
+/// Documents describing a list and its todos. Markdown checklists only keep the titles and the
+/// completion of the todos.
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TodoListFormat {
+    /// `{"title": ..., "todos": [...]}`, the sub-tasks being nested in the `children` of a todo.
+    Json,
+    /// A todo per row, the sub-tasks following their parent with a greater `depth`.
+    Csv,
+    /// A `# Title` heading followed by `- [ ]` items, indented by 2 spaces per level.
+    Markdown,
+}
+
+/// Outcome of an import. Invalid todos are skipped along with their sub-tasks.
+#[derive(SimpleObject)]
+pub struct TodoListImportResult {
+    pub todo_list: TodoList,
+    /// Number of todos created.
+    pub imported: usize,
+    pub errors: Vec<TodoListImportError>,
+}
+
+/// Todo of the content which couldn't be imported.
+#[derive(Debug, SimpleObject)]
+pub struct TodoListImportError {
+    /// Line of the content, missing for JSON documents.
+    pub line: Option<usize>,
+    /// Path of the todo within a JSON document, such as `todos[2].children[0]`.
+    pub path: Option<String>,
+    pub message: String,
+}
+
+/// Todo parsed from a document, before being created.
+struct ImportedTodo {
+    line: Option<usize>,
+    path: Option<String>,
+    /// Level of the todo, the sub-tasks of a todo following it with a greater level.
+    depth: usize,
+    fields: std::result::Result<TodoFields, String>,
+}
+
+/// Fields of an imported todo, the others keeping their default value.
+#[derive(Clone, Default)]
+pub struct TodoFields {
+    pub title: String,
+    pub complete: bool,
+    pub due_at: Option<DateTime<Utc>>,
+    pub priority: Option<TodoPriority>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Parsed document, the title being missing for CSV documents and Markdown checklists without
+/// any heading.
+struct ImportedDocument {
+    title: Option<String>,
+    todos: Vec<ImportedTodo>,
+}
+
+/// Writes the todos of the list in the given format, sub-tasks following their parent.
+pub fn export_todo_list(
+    todo_list: &TodoList,
+    todos: &[Todo],
+    format: TodoListFormat,
+) -> Result<String> {
+    let todos = hierarchy(todos);
+    match format {
+        TodoListFormat::Json => Ok(export_json(todo_list, &todos).to_string()),
+        TodoListFormat::Csv => export_csv(&todos),
+        TodoListFormat::Markdown => Ok(export_markdown(todo_list, &todos)),
+    }
+}
+
+/// Creates a list of the principal from the content, with the given title or else the one of the
+/// document. Valid todos are created once the list is written, and the others are
+/// reported along with the todos which couldn't be written.
+pub async fn import_todo_list(
+    ctx: &Context<'_>,
+    principal: &Principal,
+    mut todo_list: TodoList,
+    title: Option<String>,
+    format: TodoListFormat,
+    content: &str,
+) -> Result<TodoListImportResult> {
+    let document = match format {
+        TodoListFormat::Json => parse_json(content)?,
+        TodoListFormat::Csv => parse_csv(content)?,
+        TodoListFormat::Markdown => parse_markdown(content),
+    };
+    if document.todos.len() > MAX_IMPORTED_TODOS {
+        return Err(Errors::InvalidValue(format!(
+            "Imports can't contain more than {} todos",
+            MAX_IMPORTED_TODOS
+        ))
+        .extend());
+    }
+    // A blank title falls back to the one of the document.
+    todo_list.title = title
+        .filter(|title| !title.trim().is_empty())
+        .or(document.title.filter(|title| !title.trim().is_empty()))
+        .ok_or_else(|| {
+            Errors::InvalidValue("A title is required for the imported todo list".to_string())
+                .extend()
+        })?;
+
+    let mut errors = Vec::new();
+    let mut todos = Vec::new();
+    let mut locations = Vec::new();
+    // Ids of the last todo of each level, `None` if it was skipped.
+    let mut parents: Vec<Option<ID>> = Vec::new();
+    let mut positions = rebalanced_positions(document.todos.len());
+    for imported in document.todos {
+        let position = positions.next().unwrap_or_default();
+        let depth = imported.depth.min(parents.len());
+        parents.truncate(depth);
+        let parent_id = match parents.last() {
+            Some(None) => {
+                errors.push(imported.error("The parent of the todo couldn't be imported"));
+                parents.push(None);
+                continue;
+            }
+            Some(Some(parent_id)) => Some(parent_id.clone()),
+            None => None,
+        };
+        let fields = if depth >= MAX_TODO_DEPTH {
+            Err(format!(
+                "Todos can't be nested more than {} levels deep",
+                MAX_TODO_DEPTH
+            ))
+        } else {
+            imported.fields.clone().and_then(validate_fields)
+        };
+        match fields {
+            Ok(fields) => {
+                let todo = Todo {
+                    id: ID::new(TODO_TYPE_NAME),
+                    title: fields.title,
+                    complete: fields.complete,
+                    due_at: fields.due_at,
+                    reminder_at: None,
+                    reminder_sent_at: None,
+                    priority: fields.priority,
+                    notes: fields.notes,
+                    auto_complete: false,
+                    tags: fields.tags,
+                    recurrence: None,
+                    next_occurrence_id: None,
+                    deleted_at: None,
+                    updated_at: None,
+                    list_id: Some(todo_list.id.clone()),
+                    parent_id,
+                    position,
+                    owner: todo_list.owner.clone(),
+                };
+                parents.push(Some(todo.id.clone()));
+                todos.push(todo);
+                locations.push(imported);
+            }
+            Err(message) => {
+                errors.push(imported.error(message));
+                parents.push(None);
+            }
+        }
+    }
+
+    let dynamodb = ctx.data_unchecked::<DynamoTable>();
+    dynamodb
+        .put_todo_list(&todo_list, &principal.subject)
+        .await?;
+    let results = dynamodb.put_todos(&todos, &principal.subject).await?;
+    webhooks(ctx).todo_list_event(WebhookEvent::TodoListCreated, &todo_list);
+    search(ctx).index_todo_list(&todo_list);
+    let mut imported = 0;
+    let mut counts = TodoCounts::default();
+    for ((todo, result), source) in todos.into_iter().zip(results).zip(locations) {
+        match result {
+            Ok(()) => {
+                imported += 1;
+                counts.count(None, Some(&todo));
+                let write = TodoWrite::Create(todo);
+                if let Err(err) = todo_written(ctx, &write, &principal.subject).await {
+                    errors.push(source.error(err.message));
+                }
+            }
+            Err(err) => errors.push(source.error(error_message(err))),
+        }
+    }
+    counts.apply_to(&mut todo_list);
+    errors.sort_by_key(|error| error.line);
+    Ok(TodoListImportResult {
+        todo_list,
+        imported,
+        errors,
+    })
+}
+
+impl ImportedTodo {
+    fn error(&self, message: impl Into<String>) -> TodoListImportError {
+        TodoListImportError {
+            line: self.line,
+            path: self.path.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Trims the title and normalizes the tags, as the todo mutations would.
+pub fn validate_fields(fields: TodoFields) -> std::result::Result<TodoFields, String> {
+    let title = fields.title.trim();
+    if title.is_empty() {
+        return Err("The title of a todo can't be empty".to_string());
+    }
+    if fields.tags.len() > MAX_TODO_TAGS {
+        return Err(format!(
+            "A todo can't have more than {} tags",
+            MAX_TODO_TAGS
+        ));
+    }
+    Ok(TodoFields {
+        title: title.to_string(),
+        tags: normalize_tags(fields.tags).map_err(error_message)?,
+        notes: fields.notes.filter(|notes| !notes.is_empty()),
+        ..fields
+    })
+}
+
+/// The details of the invalid values are more helpful than their message.
+pub fn error_message(err: Error) -> String {
+    match err.extensions.as_ref().and_then(|e| e.get("details")) {
+        Some(Value::String(details)) => details.clone(),
+        _ => err.message,
+    }
+}
+
+/// Todos ordered depth first, with their level. Todos whose parent is outside of the list are
+/// exported as top-level todos, as well as the first todo reached of each cycle of a corrupted
+/// hierarchy, which couldn't be reached otherwise.
+fn hierarchy(todos: &[Todo]) -> Vec<(usize, &Todo)> {
+    let by_id: HashMap<String, &Todo> = todos
+        .iter()
+        .map(|todo| (String::from(&todo.id), todo))
+        .collect();
+    let parent = |todo: &Todo| {
+        todo.parent_id
+            .as_ref()
+            .and_then(|parent_id| by_id.get(&String::from(parent_id)).copied())
+    };
+    let mut visited = HashSet::with_capacity(todos.len());
+    let mut ordered = Vec::with_capacity(todos.len());
+    let roots = todos.iter().filter(|todo| parent(todo).is_none());
+    for todo in roots.chain(todos) {
+        if visited.contains(&String::from(&todo.id)) {
+            continue;
+        }
+        // Todos not reached from the roots descend from a cycle, whose todos are found by going
+        // up the parents until one of them repeats.
+        let mut root = todo;
+        let mut ancestors = HashSet::new();
+        while ancestors.insert(String::from(&root.id)) {
+            match parent(root) {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        let mut stack = vec![(0, root)];
+        while let Some((depth, todo)) = stack.pop() {
+            if !visited.insert(String::from(&todo.id)) {
+                continue;
+            }
+            ordered.push((depth, todo));
+            stack.extend(
+                todos
+                    .iter()
+                    .rev()
+                    .filter(|child| child.parent_id.as_ref() == Some(&todo.id))
+                    .map(|child| (depth + 1, child)),
+            );
+        }
+    }
+    ordered
+}
+
+fn export_json(todo_list: &TodoList, todos: &[(usize, &Todo)]) -> JsonValue {
+    // Todos of each level being built, the last one of a level receiving the next level.
+    let mut levels: Vec<Vec<JsonValue>> = vec![vec![]];
+    let close_level = |levels: &mut Vec<Vec<JsonValue>>| {
+        let children = levels.pop().unwrap_or_default();
+        if let Some(parent) = levels.last_mut().and_then(|level| level.last_mut()) {
+            parent["children"] = JsonValue::Array(children);
+        }
+    };
+    for (depth, todo) in todos {
+        while levels.len() > depth + 1 {
+            close_level(&mut levels);
+        }
+        if levels.len() < depth + 1 {
+            levels.push(vec![]);
+        }
+        levels[*depth].push(json!({
+            "title": todo.title,
+            "complete": todo.complete,
+            "dueAt": todo.due_at.map(|due_at| due_at.to_rfc3339()),
+            "priority": todo.priority.map(|priority| priority.as_str()),
+            "notes": todo.notes,
+            "tags": todo.tags,
+            "children": [],
+        }));
+    }
+    while levels.len() > 1 {
+        close_level(&mut levels);
+    }
+    json!({
+        "title": todo_list.title,
+        "todos": levels.pop().unwrap_or_default(),
+    })
+}
+
+fn export_csv(todos: &[(usize, &Todo)]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(CSV_COLUMNS)?;
+    for (depth, todo) in todos {
+        writer.write_record(
+            [
+                todo.title.clone(),
+                todo.complete.to_string(),
+                todo.due_at
+                    .map(|due_at| due_at.to_rfc3339())
+                    .unwrap_or_default(),
+                todo.priority
+                    .map(|priority| priority.as_str().to_string())
+                    .unwrap_or_default(),
+                todo.notes.clone().unwrap_or_default(),
+                todo.tags.join(","),
+                depth.to_string(),
+            ]
+            .map(escape_formula),
+        )?;
+    }
+    let content = writer.into_inner().map_err(|err| err.to_string())?;
+    Ok(String::from_utf8(content)?)
+}
+
+/// Spreadsheets evaluate the values starting with one of these characters as formulas, tabs and
+/// carriage returns being able to start a new cell.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefixes the values which would be evaluated as formulas with `'`, as well as the values
+/// already starting with it so that imports only have to remove the first `'` of a value.
+fn escape_formula(value: String) -> String {
+    if value.starts_with(FORMULA_PREFIXES)
+        || value.trim_start().starts_with(FORMULA_PREFIXES)
+        || value.starts_with('\'')
+    {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
+
+fn unescape_formula(value: &str) -> &str {
+    value.strip_prefix('\'').unwrap_or(value)
+}
+
+fn export_markdown(todo_list: &TodoList, todos: &[(usize, &Todo)]) -> String {
+    let mut content = format!("# {}\n\n", single_line(&todo_list.title));
+    for (depth, todo) in todos {
+        content.push_str(&format!(
+            "{}- [{}] {}\n",
+            "  ".repeat(*depth),
+            if todo.complete { 'x' } else { ' ' },
+            single_line(&todo.title)
+        ));
+    }
+    content
+}
+
+fn single_line(text: &str) -> String {
+    text.lines().collect::<Vec<_>>().join(" ")
+}
+
+fn parse_json(content: &str) -> Result<ImportedDocument> {
+    let document: JsonValue = serde_json::from_str(content)
+        .map_err(|err| Errors::InvalidValue(format!("Invalid JSON document: {}", err)).extend())?;
+    let document = document.as_object().ok_or_else(|| {
+        Errors::InvalidValue("The JSON document must be an object".to_string()).extend()
+    })?;
+    let title = match document.get("title") {
+        None | Some(JsonValue::Null) => None,
+        Some(JsonValue::String(title)) => Some(title.clone()),
+        Some(_) => {
+            return Err(Errors::InvalidValue(
+                "The title of the JSON document must be a string".to_string(),
+            )
+            .extend())
+        }
+    };
+    let mut todos = Vec::new();
+    match document.get("todos") {
+        None | Some(JsonValue::Null) => {}
+        Some(JsonValue::Array(items)) => parse_json_todos(items, "todos", 0, &mut todos),
+        Some(_) => {
+            return Err(Errors::InvalidValue(
+                "The todos of the JSON document must be an array".to_string(),
+            )
+            .extend())
+        }
+    }
+    Ok(ImportedDocument { title, todos })
+}
+
+fn parse_json_todos(items: &[JsonValue], path: &str, depth: usize, todos: &mut Vec<ImportedTodo>) {
+    for (index, item) in items.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        let children = item.get("children").cloned();
+        todos.push(ImportedTodo {
+            line: None,
+            path: Some(path.clone()),
+            depth,
+            fields: match item.as_object() {
+                Some(item) => parse_json_fields(item),
+                None => Err("A todo must be an object".to_string()),
+            },
+        });
+        match children {
+            None | Some(JsonValue::Null) => {}
+            Some(JsonValue::Array(children)) => {
+                parse_json_todos(&children, &format!("{}.children", path), depth + 1, todos)
+            }
+            // Skipped as a child of a failed todo.
+            Some(_) => todos.push(ImportedTodo {
+                line: None,
+                path: Some(format!("{}.children", path)),
+                depth: depth + 1,
+                fields: Err("The children of a todo must be an array".to_string()),
+            }),
+        }
+    }
+}
+
+fn parse_json_fields(item: &Map<String, JsonValue>) -> std::result::Result<TodoFields, String> {
+    let string = |name: &str| match item.get(name) {
+        None | Some(JsonValue::Null) => Ok(None),
+        Some(JsonValue::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(format!("'{}' must be a string", name)),
+    };
+    Ok(TodoFields {
+        title: string("title")?.ok_or_else(|| "A todo requires a 'title'".to_string())?,
+        complete: match item.get("complete") {
+            None | Some(JsonValue::Null) => false,
+            Some(JsonValue::Bool(complete)) => *complete,
+            Some(_) => return Err("'complete' must be a boolean".to_string()),
+        },
+        due_at: string("dueAt")?.as_deref().map(parse_due_at).transpose()?,
+        priority: string("priority")?
+            .as_deref()
+            .map(parse_priority)
+            .transpose()?,
+        notes: string("notes")?,
+        tags: match item.get("tags") {
+            None | Some(JsonValue::Null) => vec![],
+            Some(JsonValue::Array(tags)) => tags
+                .iter()
+                .map(|tag| {
+                    tag.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| "'tags' must be an array of strings".to_string())
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            Some(_) => return Err("'tags' must be an array of strings".to_string()),
+        },
+    })
+}
+
+fn parse_csv(content: &str) -> Result<ImportedDocument> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|err| Errors::InvalidValue(format!("Invalid CSV header: {}", err)).extend())?
+        .clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let columns: Vec<Option<usize>> = CSV_COLUMNS.iter().map(|name| column(name)).collect();
+    if columns[0].is_none() {
+        return Err(
+            Errors::InvalidValue("The CSV header requires a 'title' column".to_string()).extend(),
+        );
+    }
+
+    let mut todos = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                todos.push(ImportedTodo {
+                    line: err.position().map(|position| position.line() as usize),
+                    path: None,
+                    depth: 0,
+                    fields: Err(format!("Invalid CSV record: {}", err)),
+                });
+                continue;
+            }
+        };
+        let value = |index: usize| {
+            columns[index]
+                .and_then(|column| record.get(column))
+                .map(|value| unescape_formula(value.trim()).trim())
+                .filter(|value| !value.is_empty())
+        };
+        let depth = value(6).map(|depth| {
+            depth
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid depth '{}'", depth))
+        });
+        let fields = || -> std::result::Result<TodoFields, String> {
+            Ok(TodoFields {
+                title: value(0).unwrap_or_default().to_string(),
+                complete: match value(1) {
+                    None | Some("false") => false,
+                    Some("true") => true,
+                    Some(complete) => {
+                        return Err(format!(
+                            "Invalid completion '{}', expected true or false",
+                            complete
+                        ))
+                    }
+                },
+                due_at: value(2).map(parse_due_at).transpose()?,
+                priority: value(3).map(parse_priority).transpose()?,
+                notes: value(4).map(str::to_string),
+                tags: value(5)
+                    .map(|tags| tags.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+            })
+        };
+        todos.push(ImportedTodo {
+            line: record.position().map(|position| position.line() as usize),
+            path: None,
+            depth: depth.clone().and_then(Result::ok).unwrap_or(0),
+            fields: match depth {
+                Some(Err(message)) => Err(message),
+                _ => fields(),
+            },
+        });
+    }
+    Ok(ImportedDocument { title: None, todos })
+}
+
+/// Lines are either a checklist item, or the heading before the first item giving the title.
+/// Blank lines are ignored.
+fn parse_markdown(content: &str) -> ImportedDocument {
+    let mut title = None;
+    let mut todos = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if title.is_none() && todos.is_empty() {
+            if let Some(heading) = line.strip_prefix("# ") {
+                title = Some(heading.trim().to_string());
+                continue;
+            }
+        }
+        let item = line.trim_start();
+        let indent: usize = line[..line.len() - item.len()]
+            .chars()
+            .map(|c| if c == '\t' { 2 } else { 1 })
+            .sum();
+        todos.push(ImportedTodo {
+            line: Some(index + 1),
+            path: None,
+            depth: indent / 2,
+            fields: parse_markdown_item(item),
+        });
+    }
+    ImportedDocument { title, todos }
+}
+
+fn parse_markdown_item(item: &str) -> std::result::Result<TodoFields, String> {
+    let checkbox = ["- [", "* [", "+ ["]
+        .iter()
+        .find_map(|marker| item.strip_prefix(marker))
+        .and_then(|rest| rest.split_once(']'));
+    let complete = match checkbox {
+        Some((" ", _)) => false,
+        Some(("x" | "X", _)) => true,
+        _ => return Err("Expected a checklist item such as '- [ ] Title'".to_string()),
+    };
+    Ok(TodoFields {
+        title: checkbox
+            .map(|(_, title)| title.to_string())
+            .unwrap_or_default(),
+        complete,
+        ..TodoFields::default()
+    })
+}
+
+fn parse_due_at(due_at: &str) -> std::result::Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(due_at)
+        .map(|due_at| due_at.with_timezone(&Utc))
+        .map_err(|_| format!("Invalid due date '{}', expected an RFC 3339 date", due_at))
+}
+
+fn parse_priority(priority: &str) -> std::result::Result<TodoPriority, String> {
+    priority
+        .to_uppercase()
+        .parse()
+        .map_err(|err: anyhow::Error| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::model::TODO_LIST_TYPE_NAME;
+
+    fn todo_list() -> TodoList {
+        TodoList {
+            id: ID::new(TODO_LIST_TYPE_NAME),
+            title: "Release".to_string(),
+            archived: false,
+            is_template: false,
+            todo_count: 0,
+            completed_count: 0,
+            deleted_at: None,
+            owner: "owner#subject#user".parse().unwrap(),
+        }
+    }
+
+    fn todo(title: &str, parent: Option<&Todo>) -> Todo {
+        Todo {
+            id: ID::new(TODO_TYPE_NAME),
+            title: title.to_string(),
+            complete: false,
+            due_at: None,
+            reminder_at: None,
+            reminder_sent_at: None,
+            priority: None,
+            notes: None,
+            auto_complete: false,
+            tags: Vec::new(),
+            recurrence: None,
+            next_occurrence_id: None,
+            deleted_at: None,
+            updated_at: None,
+            list_id: None,
+            parent_id: parent.map(|parent| parent.id.clone()),
+            position: 0.0,
+            owner: "owner#subject#user".parse().unwrap(),
+        }
+    }
+
+    /// Changelog, with the sub-task Breaking changes, and Announcement.
+    fn todos() -> Vec<Todo> {
+        let mut changelog = todo("Changelog", None);
+        changelog.due_at = Some(
+            DateTime::parse_from_rfc3339("2026-05-01T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+        changelog.priority = Some(TodoPriority::High);
+        changelog.notes = Some("From the \"merged\" PRs,\nby area".to_string());
+        changelog.tags = vec!["docs".to_string(), "release".to_string()];
+        let mut breaking_changes = todo("Breaking changes", Some(&changelog));
+        breaking_changes.complete = true;
+        let announcement = todo("Announcement", None);
+        vec![changelog, announcement, breaking_changes]
+    }
+
+    fn titles(todos: &[(usize, &Todo)]) -> Vec<(usize, String)> {
+        todos
+            .iter()
+            .map(|(depth, todo)| (*depth, todo.title.clone()))
+            .collect()
+    }
+
+    fn fields(document: &ImportedDocument) -> Vec<(usize, TodoFields)> {
+        document
+            .todos
+            .iter()
+            .map(|todo| {
+                let fields = todo.fields.clone().and_then(validate_fields);
+                (todo.depth, fields.unwrap())
+            })
+            .collect()
+    }
+
+    fn assert_same_todos(document: &ImportedDocument, todos: &[Todo], full: bool) {
+        let exported = hierarchy(todos);
+        let imported = fields(document);
+        assert_eq!(imported.len(), exported.len());
+        for ((depth, fields), (exported_depth, todo)) in imported.iter().zip(exported) {
+            assert_eq!(*depth, exported_depth);
+            assert_eq!(fields.title, todo.title);
+            assert_eq!(fields.complete, todo.complete);
+            if full {
+                assert_eq!(fields.due_at, todo.due_at);
+                assert_eq!(fields.priority, todo.priority);
+                assert_eq!(fields.notes, todo.notes);
+                assert_eq!(fields.tags, todo.tags);
+            }
+        }
+    }
+
+    #[test]
+    fn orders_sub_tasks_after_their_parent() {
+        let todos = todos();
+        assert_eq!(
+            titles(&hierarchy(&todos)),
+            [
+                (0, "Changelog".to_string()),
+                (1, "Breaking changes".to_string()),
+                (0, "Announcement".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn exports_todos_of_other_lists_and_cycles_at_the_top_level() {
+        let outside = todo("Outside", None);
+        let orphan = todo("Orphan", Some(&outside));
+        let mut first = todo("First", None);
+        let second = todo("Second", Some(&first));
+        first.parent_id = Some(second.id.clone());
+        let third = todo("Third", Some(&second));
+        let todos = [third, first, orphan, second];
+        assert_eq!(
+            titles(&hierarchy(&todos)),
+            [
+                (0, "Orphan".to_string()),
+                (0, "Second".to_string()),
+                (1, "Third".to_string()),
+                (1, "First".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let todos = todos();
+        let content = export_todo_list(&todo_list(), &todos, TodoListFormat::Json).unwrap();
+        let document = parse_json(&content).unwrap();
+        assert_eq!(document.title.as_deref(), Some("Release"));
+        assert_same_todos(&document, &todos, true);
+    }
+
+    #[test]
+    fn round_trips_csv() {
+        let todos = todos();
+        let content = export_todo_list(&todo_list(), &todos, TodoListFormat::Csv).unwrap();
+        let document = parse_csv(&content).unwrap();
+        assert_eq!(document.title, None);
+        assert_same_todos(&document, &todos, true);
+    }
+
+    #[test]
+    fn round_trips_markdown() {
+        let todos = todos();
+        let content = export_todo_list(&todo_list(), &todos, TodoListFormat::Markdown).unwrap();
+        let document = parse_markdown(&content);
+        assert_eq!(document.title.as_deref(), Some("Release"));
+        assert_same_todos(&document, &todos, false);
+    }
+
+    #[test]
+    fn escapes_csv_formulas() {
+        let mut formula = todo("=HYPERLINK(\"http://example.com\")", None);
+        formula.notes = Some("+1".to_string());
+        formula.tags = vec!["-x".to_string(), "@y".to_string()];
+        let quoted = todo("'quoted", None);
+        let mut tab = todo("Tab", None);
+        tab.notes = Some("\tcmd".to_string());
+        let mut carriage_return = todo("Carriage return", None);
+        carriage_return.notes = Some("\r=1".to_string());
+        let todos = vec![formula, quoted, todo("Plain", None), tab, carriage_return];
+        let content = export_todo_list(&todo_list(), &todos, TodoListFormat::Csv).unwrap();
+        let records: Vec<csv::StringRecord> = csv::Reader::from_reader(content.as_bytes())
+            .records()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(&records[0][0], "'=HYPERLINK(\"http://example.com\")");
+        assert_eq!(&records[0][4], "'+1");
+        assert_eq!(&records[0][5], "'-x,@y");
+        assert_eq!(&records[1][0], "''quoted");
+        assert_eq!(&records[2][0], "Plain");
+        assert_eq!(&records[3][4], "'\tcmd");
+        assert_eq!(&records[4][4], "'\r=1");
+        // Notes are trimmed once imported.
+        let mut todos = todos;
+        todos[3].notes = Some("cmd".to_string());
+        todos[4].notes = Some("=1".to_string());
+        assert_same_todos(&parse_csv(&content).unwrap(), &todos, true);
+    }
+
+    #[test]
+    fn parses_nested_markdown_items() {
+        let document = parse_markdown("# Release\n\n- [ ] Changelog\n\t- [X] Breaking\nNotes\n");
+        assert_eq!(document.todos[1].depth, 1);
+        assert!(document.todos[1].fields.as_ref().unwrap().complete);
+        assert_eq!(document.todos[2].line, Some(5));
+        assert!(document.todos[2].fields.is_err());
+    }
+
+    #[test]
+    fn reports_invalid_csv_values() {
+        let document =
+            parse_csv("title,complete,depth\nChangelog,yes,0\nBreaking,true,one\n").unwrap();
+        assert_eq!(document.todos[0].line, Some(2));
+        assert!(document.todos[0].fields.is_err());
+        assert!(document.todos[1].fields.is_err());
+        assert!(parse_csv("complete\ntrue\n").is_err());
+    }
+}
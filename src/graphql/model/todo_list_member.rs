@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use async_graphql::{Enum, SimpleObject};
+
+use crate::graphql::types::{Owner, ID};
+
+pub mod access;
+pub mod extensions;
+pub mod mutation;
+
+pub const TODO_LIST_MEMBER_TYPE_NAME: &str = "member";
+
+/// Roles are ordered, each one grants the permissions of the previous ones.
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TodoListRole {
+    /// Can share the list and delete it.
+    Owner,
+    /// Can update the list and manage its todos.
+    Editor,
+    /// Can only read the list and its todos.
+    Viewer,
+}
+
+impl TodoListRole {
+    pub fn grants(&self, required: TodoListRole) -> bool {
+        self.level() >= required.level()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoListRole::Owner => "OWNER",
+            TodoListRole::Editor => "EDITOR",
+            TodoListRole::Viewer => "VIEWER",
+        }
+    }
+
+    fn level(&self) -> u8 {
+        match self {
+            TodoListRole::Owner => 2,
+            TodoListRole::Editor => 1,
+            TodoListRole::Viewer => 0,
+        }
+    }
+}
+
+impl FromStr for TodoListRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "OWNER" => Ok(TodoListRole::Owner),
+            "EDITOR" => Ok(TodoListRole::Editor),
+            "VIEWER" => Ok(TodoListRole::Viewer),
+            _ => Err(anyhow!("Invalid role '{}'", s)),
+        }
+    }
+}
+
+/// User with whom a todo list is shared. The owner of the list isn't a member, it always has the
+/// `OWNER` role.
+#[derive(Debug, SimpleObject)]
+pub struct TodoListMember {
+    pub subject: String,
+    pub role: TodoListRole,
+    #[graphql(skip)]
+    pub list_id: ID,
+    #[graphql(skip)]
+    pub list_owner: Owner,
+}
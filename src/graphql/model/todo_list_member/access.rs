@@ -0,0 +1,82 @@
+use async_graphql::{ErrorExtensions, Result};
+
+use crate::auth::Principal;
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::Errors;
+use crate::graphql::model::todo::extensions::DynamoTableTodoExt;
+use crate::graphql::model::todo_list::extensions::DynamoTableTodoListExt;
+use crate::graphql::model::{Todo, TodoList};
+use crate::graphql::types::{Owner, ID};
+use crate::graphql::Key;
+
+use super::extensions::DynamoTableTodoListMemberExt;
+use super::TodoListRole;
+
+/// Retrieves a todo list with the role of the principal on it. Lists of other owners which
+/// aren't shared with the principal are considered missing.
+pub async fn get_todo_list_with_role(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+) -> Result<Option<(TodoList, TodoListRole)>> {
+    let owner = Owner::from(principal);
+    if let Some(todo_list) = dynamodb.get_todo_list(&owner, id).await? {
+        return Ok(Some((todo_list, TodoListRole::Owner)));
+    }
+
+    Ok(
+        if let Some(member) = dynamodb
+            .get_todo_list_member(id, &principal.subject)
+            .await?
+        {
+            dynamodb
+                .get_todo_list(&member.list_owner, id)
+                .await?
+                .map(|todo_list| (todo_list, member.role))
+        } else {
+            None
+        },
+    )
+}
+
+/// Retrieves a todo list on which the principal has at least the required role.
+pub async fn authorize_todo_list(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+    required: TodoListRole,
+) -> Result<TodoList> {
+    match get_todo_list_with_role(dynamodb, principal, id).await? {
+        Some((todo_list, role)) if role.grants(required) => Ok(todo_list),
+        Some(_) => Err(Errors::Forbidden.extend()),
+        None => Err(Errors::NotFound.extend()),
+    }
+}
+
+/// Retrieves a todo with the role of the principal on it. The owner of a todo has all the
+/// permissions, otherwise it depends on the role in the todo list it belongs to.
+pub async fn get_todo_with_role(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+) -> Result<Option<(Key, Todo, TodoListRole)>> {
+    let owner = Owner::from(principal);
+    if let Some((key, todo)) = dynamodb.get_todo(&owner, id).await? {
+        return Ok(Some((key, todo, TodoListRole::Owner)));
+    }
+    dynamodb.get_shared_todo(&principal.subject, id).await
+}
+
+/// Retrieves a todo on which the principal has at least the required role.
+pub async fn authorize_todo(
+    dynamodb: &DynamoTable,
+    principal: &Principal,
+    id: &ID,
+    required: TodoListRole,
+) -> Result<(Key, Todo)> {
+    match get_todo_with_role(dynamodb, principal, id).await? {
+        Some((key, todo, role)) if role.grants(required) => Ok((key, todo)),
+        Some(_) => Err(Errors::Forbidden.extend()),
+        None => Err(Errors::NotFound.extend()),
+    }
+}
@@ -0,0 +1,234 @@
+use std::convert::identity;
+
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::AttributeValue;
+use tokio_stream::StreamExt;
+
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::item::ItemKey;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes};
+use crate::graphql::model::audit::extensions::DynamoTableAuditExt;
+use crate::graphql::model::audit::{AuditOperation, Changes};
+use crate::graphql::model::todo::extensions::build_todo;
+use crate::graphql::model::{AuditRecord, Todo, TODO_LIST_TYPE_NAME};
+use crate::graphql::types::ID;
+use crate::graphql::Key;
+
+use super::{TodoListMember, TodoListRole, TODO_LIST_MEMBER_TYPE_NAME};
+
+/// Extension used to decorate the DynamoTable with specialized methods for TodoListMember
+#[async_trait::async_trait]
+pub trait DynamoTableTodoListMemberExt {
+    async fn get_todo_list_members(&self, list_id: &ID) -> Result<Vec<TodoListMember>>;
+    async fn get_todo_list_member(
+        &self,
+        list_id: &ID,
+        subject: &str,
+    ) -> Result<Option<TodoListMember>>;
+    async fn get_shared_todo_list_members(&self, subject: &str) -> Result<Vec<TodoListMember>>;
+    async fn get_shared_todo(
+        &self,
+        subject: &str,
+        id: &ID,
+    ) -> Result<Option<(Key, Todo, TodoListRole)>>;
+    async fn put_todo_list_member(&self, member: &TodoListMember, actor: &str) -> Result<bool>;
+    async fn delete_todo_list_member(
+        &self,
+        list_id: &ID,
+        subject: &str,
+        actor: &str,
+    ) -> Result<Option<TodoListMember>>;
+}
+
+#[async_trait::async_trait]
+impl DynamoTableTodoListMemberExt for DynamoTable {
+    async fn get_todo_list_members(&self, list_id: &ID) -> Result<Vec<TodoListMember>> {
+        let mut members: Vec<TodoListMember> = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(list_id, &ID::prefix(TODO_LIST_MEMBER_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                members.push(build_todo_list_member(self, item)?);
+            }
+        }
+        Ok(members)
+    }
+
+    async fn get_todo_list_member(
+        &self,
+        list_id: &ID,
+        subject: &str,
+    ) -> Result<Option<TodoListMember>> {
+        let output = self
+            .get_item(&member_key(list_id, subject), identity)
+            .await?;
+        Ok(if let Some(item) = output.item {
+            Some(build_todo_list_member(self, &item)?)
+        } else {
+            None
+        })
+    }
+
+    async fn get_shared_todo_list_members(&self, subject: &str) -> Result<Vec<TodoListMember>> {
+        let mut members: Vec<TodoListMember> = Vec::new();
+        let mut paginator = self
+            .query_gsi2_by_prefix(
+                member_gsi2_partition(subject),
+                &ID::prefix(TODO_LIST_TYPE_NAME),
+            )
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                members.push(build_todo_list_member(self, item)?);
+            }
+        }
+        Ok(members)
+    }
+
+    /// Retrieves a todo of another owner, only if it belongs to a list shared with the subject.
+    async fn get_shared_todo(
+        &self,
+        subject: &str,
+        id: &ID,
+    ) -> Result<Option<(Key, Todo, TodoListRole)>> {
+        let gsi1_key = Key {
+            partition: id.clone(),
+            sort: id.clone(),
+        };
+        let output = self
+            .query_gsi1_get(&gsi1_key, |query| {
+                query.filter_expression("attribute_not_exists(deleted_at)")
+            })
+            .await?;
+        let (key, todo) = if let Some(item) = output.items.unwrap_or_default().pop() {
+            let key = self.extract_key(&item)?;
+            let todo = build_todo(self, &key, &item)?;
+            (key, todo)
+        } else {
+            return Ok(None);
+        };
+
+        Ok(if let Some(list_id) = &todo.list_id {
+            self.get_todo_list_member(list_id, subject)
+                .await?
+                // The todo could have been moved to another owner after being shared.
+                .filter(|member| member.list_owner == todo.owner)
+                .map(|member| (key, todo, member.role))
+        } else {
+            None
+        })
+    }
+
+    /// Changes of members are recorded in the history of the todo list.
+    async fn put_todo_list_member(&self, member: &TodoListMember, actor: &str) -> Result<bool> {
+        let previous = self
+            .get_todo_list_member(&member.list_id, &member.subject)
+            .await?;
+        let changes = member_changes(&member.subject, previous.as_ref(), Some(member));
+        let operation = if previous.is_some() {
+            AuditOperation::Update
+        } else {
+            AuditOperation::Create
+        };
+        let record = AuditRecord::new(&member.list_id, actor, operation, changes);
+        self.transact_write(|transaction| {
+            transaction
+                .transact_items(self.transact_put(
+                    &member_key(&member.list_id, &member.subject),
+                    |put| {
+                        put.item("role", AttributeValue::S(member.role.as_str().to_string()))
+                            .item(
+                                "list_owner",
+                                AttributeValue::S(member.list_owner.to_string()),
+                            )
+                            // Used to retrieve the todo lists shared with the subject.
+                            .item(
+                                &self.config.gsi2_partition_key,
+                                AttributeValue::S(member_gsi2_partition(&member.subject)),
+                            )
+                            .item(
+                                &self.config.gsi2_sort_key,
+                                AttributeValue::S(String::from(&member.list_id)),
+                            )
+                    },
+                ))
+                .transact_items(self.transact_put_audit(&record))
+        })
+        .await?;
+        Ok(true)
+    }
+
+    async fn delete_todo_list_member(
+        &self,
+        list_id: &ID,
+        subject: &str,
+        actor: &str,
+    ) -> Result<Option<TodoListMember>> {
+        let member = match self.get_todo_list_member(list_id, subject).await? {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        let record = AuditRecord::new(
+            list_id,
+            actor,
+            AuditOperation::Update,
+            member_changes(subject, Some(&member), None),
+        );
+        let output = self
+            .transact_write(|transaction| {
+                transaction
+                    .transact_items(self.transact_delete(&member_key(list_id, subject), |req| {
+                        req.condition_expression("attribute_exists(#pk)")
+                            .expression_attribute_names("#pk", &self.config.partition_key)
+                    }))
+                    .transact_items(self.transact_put_audit(&record))
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(None),
+            output => {
+                output?;
+                Ok(Some(member))
+            }
+        }
+    }
+}
+
+/// Role of a member, as a change of the `member:SUBJECT` field of the todo list.
+fn member_changes(
+    subject: &str,
+    old: Option<&TodoListMember>,
+    new: Option<&TodoListMember>,
+) -> Changes {
+    let role = |member: &TodoListMember| member.role.as_str().to_string();
+    let mut changes = Changes::default();
+    changes.diff(&format!("member:{}", subject), old.map(role), new.map(role));
+    changes
+}
+
+fn member_key(list_id: &ID, subject: &str) -> ItemKey<String> {
+    ItemKey {
+        partition: String::from(list_id),
+        sort: format!("{}{}", ID::prefix(TODO_LIST_MEMBER_TYPE_NAME), subject),
+    }
+}
+
+fn member_gsi2_partition(subject: &str) -> String {
+    format!("{}{}", ID::prefix(TODO_LIST_MEMBER_TYPE_NAME), subject)
+}
+
+fn build_todo_list_member(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<TodoListMember> {
+    let sort = item.get_string(&dynamodb.config.sort_key)?;
+    let subject = sort
+        .strip_prefix(&ID::prefix(TODO_LIST_MEMBER_TYPE_NAME))
+        .ok_or_else(|| anyhow::anyhow!("Invalid member key '{}'", sort))?;
+    Ok(TodoListMember {
+        subject: subject.to_owned(),
+        role: item.get_from_string("role")?,
+        list_id: item.get_from_string(&dynamodb.config.partition_key)?,
+        list_owner: item.get_from_string("list_owner")?,
+    })
+}
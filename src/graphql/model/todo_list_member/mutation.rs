@@ -0,0 +1,63 @@
+use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::model::TODO_LIST_TYPE_NAME;
+use crate::graphql::types::ID;
+
+use super::access::authorize_todo_list;
+use super::extensions::DynamoTableTodoListMemberExt;
+use super::{TodoListMember, TodoListRole};
+
+#[derive(Debug, InputObject)]
+struct TodoListInputShare {
+    list: ID,
+    subject: String,
+    role: TodoListRole,
+}
+
+#[derive(Default)]
+pub struct TodoListMemberMutation;
+
+#[Object]
+impl TodoListMemberMutation {
+    /// Shares the todo list with a user, or changes its role if already shared.
+    async fn todo_list_share(
+        &self,
+        ctx: &Context<'_>,
+        input: TodoListInputShare,
+    ) -> Result<TodoListMember> {
+        check_id_kind(&input.list, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let todo_list =
+            authorize_todo_list(dynamodb, principal, &input.list, TodoListRole::Owner).await?;
+
+        let member = TodoListMember {
+            subject: input.subject,
+            role: input.role,
+            list_id: todo_list.id,
+            list_owner: todo_list.owner,
+        };
+        dynamodb
+            .put_todo_list_member(&member, &principal.subject)
+            .await?;
+        Ok(member)
+    }
+
+    async fn todo_list_unshare(
+        &self,
+        ctx: &Context<'_>,
+        list: ID,
+        subject: String,
+    ) -> Result<TodoListMember> {
+        check_id_kind(&list, TODO_LIST_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        authorize_todo_list(dynamodb, principal, &list, TodoListRole::Owner).await?;
+        dynamodb
+            .delete_todo_list_member(&list, &subject, &principal.subject)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())
+    }
+}
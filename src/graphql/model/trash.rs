@@ -0,0 +1,82 @@
+use async_graphql::{Result, Union};
+use aws_sdk_dynamodb::client::fluent_builders::Query;
+use aws_sdk_dynamodb::model::TransactWriteItem;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::dynamodb::item::{datetime_attribute, ttl_attribute, ItemKey};
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes, UpdateExpression};
+use crate::graphql::model::{Todo, TodoList};
+
+pub mod query;
+
+/// Deleted item, kept until it's restored or purged.
+#[derive(Union)]
+pub enum TrashItem {
+    Todo(Box<Todo>),
+    TodoList(TodoList),
+}
+
+impl TrashItem {
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            TrashItem::Todo(todo) => todo.deleted_at,
+            TrashItem::TodoList(todo_list) => todo_list.deleted_at,
+        }
+    }
+}
+
+/// Time at which DynamoDB purges an item deleted at `deleted_at`.
+pub fn trash_expiration(dynamodb: &DynamoTable, deleted_at: &DateTime<Utc>) -> DateTime<Utc> {
+    *deleted_at + Duration::days(dynamodb.config.trash_retention_days.into())
+}
+
+/// Only keeps the items of the trash, matching the condition if any. DynamoDB only purges expired
+/// items eventually, so they're filtered out as well.
+pub fn filter_trash(dynamodb: &DynamoTable, query: Query, condition: Option<&str>) -> Query {
+    let mut conditions = vec!["attribute_exists(deleted_at)", "#ttl > :expiration"];
+    conditions.extend(condition);
+    query
+        .filter_expression(conditions.join(" AND "))
+        .expression_attribute_names("#ttl", &dynamodb.config.ttl_attribute)
+        .expression_attribute_values(":expiration", ttl_attribute(&Utc::now()))
+}
+
+/// Whether an item of the trash is past its retention period, but not purged yet.
+pub fn is_expired(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<bool> {
+    let expiration: Option<i64> = item.get_optional_from_number(&dynamodb.config.ttl_attribute)?;
+    Ok(expiration.is_some_and(|expiration| expiration <= Utc::now().timestamp()))
+}
+
+/// Sets, or removes, the expiration of an item depending on a deleted one, such as a comment or
+/// a tag of a todo.
+pub fn transact_expiration<K: ToString>(
+    dynamodb: &DynamoTable,
+    key: &ItemKey<K>,
+    expires_at: Option<&DateTime<Utc>>,
+) -> TransactWriteItem {
+    dynamodb.transact_update(key, |req| {
+        let mut update = UpdateExpression::default();
+        update.set_or_remove(
+            &dynamodb.config.ttl_attribute,
+            expires_at.map(ttl_attribute),
+        );
+        update.apply(
+            // Otherwise an item purged in the meantime would be recreated with its key only.
+            req.condition_expression("attribute_exists(#pk)")
+                .expression_attribute_names("#pk", &dynamodb.config.partition_key),
+        )
+    })
+}
+
+/// Moves an item to the trash, or restores it when `deleted_at` is missing.
+pub fn set_deleted_at(
+    dynamodb: &DynamoTable,
+    update: &mut UpdateExpression,
+    deleted_at: Option<&DateTime<Utc>>,
+) {
+    update.set_or_remove("deleted_at", deleted_at.map(datetime_attribute));
+    update.set_or_remove(
+        &dynamodb.config.ttl_attribute,
+        deleted_at.map(|deleted_at| ttl_attribute(&trash_expiration(dynamodb, deleted_at))),
+    );
+}
@@ -0,0 +1,40 @@
+use std::cmp::Reverse;
+
+use async_graphql::{Context, Object, Result};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::principal;
+use crate::graphql::limits::COLLECTION_COMPLEXITY;
+use crate::graphql::model::todo::extensions::DynamoTableTodoExt;
+use crate::graphql::model::todo_list::extensions::DynamoTableTodoListExt;
+use crate::graphql::types::Owner;
+
+use super::TrashItem;
+
+#[derive(Default)]
+pub struct TrashQuery;
+
+#[Object]
+impl TrashQuery {
+    /// Deleted todos and todo lists of the owner, most recently deleted first.
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn trash(&self, ctx: &Context<'_>) -> Result<Vec<TrashItem>> {
+        let owner = Owner::from(principal(ctx)?);
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let mut items: Vec<TrashItem> = dynamodb
+            .scan_deleted_todo(&owner)
+            .await?
+            .into_iter()
+            .map(|todo| TrashItem::Todo(Box::new(todo)))
+            .collect();
+        items.extend(
+            dynamodb
+                .scan_deleted_todo_list(&owner)
+                .await?
+                .into_iter()
+                .map(TrashItem::TodoList),
+        );
+        items.sort_by_key(|item| Reverse(item.deleted_at()));
+        Ok(items)
+    }
+}
@@ -0,0 +1,26 @@
+use async_graphql::{Context, Object, Result, SimpleObject};
+
+use crate::graphql::errors::principal;
+
+/// Authenticated user, as described by the claims of its token.
+#[derive(Debug, SimpleObject)]
+pub struct Viewer {
+    pub subject: String,
+    pub tenant: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct ViewerQuery;
+
+#[Object]
+impl ViewerQuery {
+    async fn viewer(&self, ctx: &Context<'_>) -> Result<Viewer> {
+        let principal = principal(ctx)?;
+        Ok(Viewer {
+            subject: principal.subject.clone(),
+            tenant: principal.tenant.clone(),
+            scopes: principal.scopes.clone(),
+        })
+    }
+}
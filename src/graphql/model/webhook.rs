@@ -0,0 +1,161 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+
+use crate::graphql::types::{Owner, ID};
+
+pub mod extensions;
+pub mod mutation;
+pub mod query;
+
+pub const WEBHOOK_TYPE_NAME: &str = "webhook";
+
+pub const WEBHOOK_DELIVERY_TYPE_NAME: &str = "webhook_delivery";
+
+/// Events published after a successful mutation. Completing a todo publishes both `TODO_UPDATED`
+/// and `TODO_COMPLETED`.
+// Variants are named after the published events.
+#[allow(clippy::enum_variant_names)]
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WebhookEvent {
+    TodoCreated,
+    TodoUpdated,
+    TodoCompleted,
+    TodoDeleted,
+    TodoRestored,
+    TodoListCreated,
+    TodoListUpdated,
+    TodoListDeleted,
+    TodoListRestored,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TodoCreated => "TODO_CREATED",
+            WebhookEvent::TodoUpdated => "TODO_UPDATED",
+            WebhookEvent::TodoCompleted => "TODO_COMPLETED",
+            WebhookEvent::TodoDeleted => "TODO_DELETED",
+            WebhookEvent::TodoRestored => "TODO_RESTORED",
+            WebhookEvent::TodoListCreated => "TODO_LIST_CREATED",
+            WebhookEvent::TodoListUpdated => "TODO_LIST_UPDATED",
+            WebhookEvent::TodoListDeleted => "TODO_LIST_DELETED",
+            WebhookEvent::TodoListRestored => "TODO_LIST_RESTORED",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "TODO_CREATED" => Ok(WebhookEvent::TodoCreated),
+            "TODO_UPDATED" => Ok(WebhookEvent::TodoUpdated),
+            "TODO_COMPLETED" => Ok(WebhookEvent::TodoCompleted),
+            "TODO_DELETED" => Ok(WebhookEvent::TodoDeleted),
+            "TODO_RESTORED" => Ok(WebhookEvent::TodoRestored),
+            "TODO_LIST_CREATED" => Ok(WebhookEvent::TodoListCreated),
+            "TODO_LIST_UPDATED" => Ok(WebhookEvent::TodoListUpdated),
+            "TODO_LIST_DELETED" => Ok(WebhookEvent::TodoListDeleted),
+            "TODO_LIST_RESTORED" => Ok(WebhookEvent::TodoListRestored),
+            _ => Err(anyhow!("Invalid webhook event '{}'", s)),
+        }
+    }
+}
+
+/// Webhooks receive the events of the items of their owner.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Webhook {
+    pub id: ID,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+    /// Key of the HMAC-SHA256 signature of the deliveries, never exposed.
+    #[graphql(skip)]
+    pub secret: String,
+    #[graphql(skip)]
+    pub owner: Owner,
+}
+
+#[derive(Enum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// The last attempt failed, but the delivery will be retried.
+    Failed,
+    /// All attempts failed, the delivery is kept as a dead letter.
+    DeadLetter,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "PENDING",
+            WebhookDeliveryStatus::Delivered => "DELIVERED",
+            WebhookDeliveryStatus::Failed => "FAILED",
+            WebhookDeliveryStatus::DeadLetter => "DEAD_LETTER",
+        }
+    }
+}
+
+impl FromStr for WebhookDeliveryStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "PENDING" => Ok(WebhookDeliveryStatus::Pending),
+            "DELIVERED" => Ok(WebhookDeliveryStatus::Delivered),
+            "FAILED" => Ok(WebhookDeliveryStatus::Failed),
+            "DEAD_LETTER" => Ok(WebhookDeliveryStatus::DeadLetter),
+            _ => Err(anyhow!("Invalid webhook delivery status '{}'", s)),
+        }
+    }
+}
+
+/// Deliveries are stored in the item collection of their webhook.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct WebhookDelivery {
+    pub id: ID,
+    pub event: WebhookEvent,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, missing if no response was received.
+    pub response_status: Option<u16>,
+    /// Reason of the failure of the last attempt.
+    pub error: Option<String>,
+    /// JSON body of the request.
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Time of the next retry of a failed delivery.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    #[graphql(skip)]
+    pub webhook_id: ID,
+    /// Owner of the webhook, with which it's retrieved when the delivery is retried.
+    #[graphql(skip)]
+    pub owner: Owner,
+}
+
+impl WebhookDelivery {
+    /// New deliveries are attempted right away.
+    pub fn new(webhook: &Webhook, event: WebhookEvent, payload: String) -> WebhookDelivery {
+        let now = Utc::now();
+        WebhookDelivery {
+            id: ID::new(WEBHOOK_DELIVERY_TYPE_NAME),
+            event,
+            status: WebhookDeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+            payload,
+            created_at: now,
+            updated_at: now,
+            next_attempt_at: Some(now),
+            webhook_id: webhook.id.clone(),
+            owner: webhook.owner.clone(),
+        }
+    }
+}
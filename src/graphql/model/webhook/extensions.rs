@@ -0,0 +1,367 @@
+use std::convert::identity;
+
+use async_graphql::Result;
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Duration, DurationRound, SecondsFormat, Utc};
+use tokio_stream::StreamExt;
+
+use crate::dynamodb::errors::DynamoDbErrors;
+use crate::dynamodb::item::{datetime_attribute, ttl_attribute, ItemKey};
+use crate::dynamodb::table::MAX_TRANSACTION_ITEMS;
+use crate::dynamodb::{AttributesGetterExt, DynamoTable, RawAttributes, UpdateExpression};
+use crate::graphql::types::{Owner, ID};
+use crate::graphql::Key;
+
+use super::{
+    Webhook, WebhookDelivery, WebhookDeliveryStatus, WEBHOOK_DELIVERY_TYPE_NAME, WEBHOOK_TYPE_NAME,
+};
+
+const RETRY_BUCKET_PREFIX: &str = "webhook_retry#";
+
+/// Pending retries are partitioned by hour in GSI4 like the reminders, so the due ones are
+/// retrieved with a query per hour instead of a scan.
+pub fn retry_bucket(date: &DateTime<Utc>) -> DateTime<Utc> {
+    date.duration_trunc(Duration::hours(1)).unwrap_or(*date)
+}
+
+fn retry_bucket_key(bucket: &DateTime<Utc>) -> String {
+    format!("{}{}", RETRY_BUCKET_PREFIX, bucket.format("%Y-%m-%dT%H"))
+}
+
+/// Extension used to decorate the DynamoTable with specialized methods for Webhook
+#[async_trait::async_trait]
+pub trait DynamoTableWebhookExt {
+    async fn get_webhooks(&self, owner: &Owner) -> Result<Vec<Webhook>>;
+    async fn get_webhook(&self, owner: &Owner, id: &ID) -> Result<Option<Webhook>>;
+    async fn put_webhook(&self, webhook: &Webhook) -> Result<bool>;
+    async fn delete_webhook(&self, webhook: &Webhook) -> Result<()>;
+    /// Deliveries of a webhook, most recent first, starting after the given delivery. Also
+    /// returns whether there are more deliveries.
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<WebhookDelivery>, bool)>;
+    async fn put_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        expires_at: &DateTime<Utc>,
+    ) -> Result<bool>;
+    /// Deliveries of the bucket whose next attempt is due at `now`.
+    async fn get_due_webhook_deliveries(
+        &self,
+        bucket: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Result<Vec<WebhookDelivery>>;
+    /// Claims the next attempt of the delivery until the given date, so the other instances
+    /// don't make it meanwhile. Returns `false` if the delivery was attempted, claimed by another
+    /// instance or deleted with its webhook.
+    async fn claim_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        until: &DateTime<Utc>,
+    ) -> Result<bool>;
+    /// Records an attempt of the delivery, whose `attempts` were incremented, and releases its
+    /// claim. Returns `false` if the delivery was deleted with its webhook or recorded by another
+    /// instance meanwhile.
+    async fn update_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<bool>;
+}
+
+#[async_trait::async_trait]
+impl DynamoTableWebhookExt for DynamoTable {
+    async fn get_webhooks(&self, owner: &Owner) -> Result<Vec<Webhook>> {
+        let mut webhooks = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(owner, &ID::prefix(WEBHOOK_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                webhooks.push(build_webhook(self, item)?);
+            }
+        }
+        Ok(webhooks)
+    }
+
+    async fn get_webhook(&self, owner: &Owner, id: &ID) -> Result<Option<Webhook>> {
+        let output = self.get_item(&webhook_key(owner, id), identity).await?;
+        Ok(if let Some(item) = output.item {
+            Some(build_webhook(self, &item)?)
+        } else {
+            None
+        })
+    }
+
+    async fn put_webhook(&self, webhook: &Webhook) -> Result<bool> {
+        self.put_item(&webhook_key(&webhook.owner, &webhook.id), |req| {
+            req.item("url", AttributeValue::S(webhook.url.clone()))
+                .item("secret", AttributeValue::S(webhook.secret.clone()))
+                .item(
+                    "events",
+                    AttributeValue::Ss(
+                        webhook
+                            .events
+                            .iter()
+                            .map(|event| event.as_str().to_string())
+                            .collect(),
+                    ),
+                )
+                .item("created_at", datetime_attribute(&webhook.created_at))
+        })
+        .await?;
+        Ok(true)
+    }
+
+    /// The deliveries of the webhook are deleted afterwards in chunks, each of them being atomic.
+    async fn delete_webhook(&self, webhook: &Webhook) -> Result<()> {
+        self.delete_item(&webhook_key(&webhook.owner, &webhook.id), identity)
+            .await?;
+
+        let mut keys: Vec<Key> = Vec::new();
+        let mut paginator = self
+            .query_partition_by_prefix(&webhook.id, &ID::prefix(WEBHOOK_DELIVERY_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                keys.push(self.extract_key(item)?);
+            }
+        }
+        for chunk in keys.chunks(MAX_TRANSACTION_ITEMS) {
+            self.transact_write(|transaction| {
+                chunk.iter().fold(transaction, |transaction, key| {
+                    transaction.transact_items(self.transact_delete(key, identity))
+                })
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: &ID,
+        after: Option<&ID>,
+        limit: usize,
+    ) -> Result<(Vec<WebhookDelivery>, bool)> {
+        let (items, has_next_page) = self
+            .query_partition_page_with(
+                webhook_id,
+                &ID::prefix(WEBHOOK_DELIVERY_TYPE_NAME),
+                after,
+                limit,
+                |query| query.scan_index_forward(false),
+            )
+            .await?;
+        let deliveries = items
+            .iter()
+            .map(|item| build_webhook_delivery(self, item))
+            .collect::<Result<Vec<WebhookDelivery>>>()?;
+        Ok((deliveries, has_next_page))
+    }
+
+    async fn put_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        expires_at: &DateTime<Utc>,
+    ) -> Result<bool> {
+        self.put_item(&delivery_key(delivery), |req| {
+            let req = req
+                .item(
+                    "event",
+                    AttributeValue::S(delivery.event.as_str().to_string()),
+                )
+                .item(
+                    "status",
+                    AttributeValue::S(delivery.status.as_str().to_string()),
+                )
+                .item("attempts", AttributeValue::N(delivery.attempts.to_string()))
+                .item("payload", AttributeValue::S(delivery.payload.clone()))
+                .item("created_at", datetime_attribute(&delivery.created_at))
+                .item("updated_at", datetime_attribute(&delivery.updated_at))
+                .item("owner", AttributeValue::S(delivery.owner.to_string()))
+                .item(&self.config.ttl_attribute, ttl_attribute(expires_at));
+            match delivery.next_attempt_at {
+                Some(next_attempt_at) => req
+                    .item("next_attempt_at", datetime_attribute(&next_attempt_at))
+                    .item(
+                        &self.config.gsi4_partition_key,
+                        AttributeValue::S(retry_bucket_key(&retry_bucket(&next_attempt_at))),
+                    )
+                    .item(
+                        &self.config.gsi4_sort_key,
+                        datetime_attribute(&next_attempt_at),
+                    ),
+                None => req,
+            }
+        })
+        .await?;
+        Ok(true)
+    }
+
+    async fn get_due_webhook_deliveries(
+        &self,
+        bucket: &DateTime<Utc>,
+        now: &DateTime<Utc>,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let mut deliveries = Vec::new();
+        // Sort keys are formatted as the stored dates, so they're compared as strings.
+        let until = now.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut paginator = self
+            .query_gsi4_until(retry_bucket_key(bucket), &until)
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                deliveries.push(build_webhook_delivery(self, item)?);
+            }
+        }
+        Ok(deliveries)
+    }
+
+    async fn claim_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        until: &DateTime<Utc>,
+    ) -> Result<bool> {
+        let mut update = UpdateExpression::default();
+        update.set("claimed_until", datetime_attribute(until));
+        let output = self
+            .update_item(&delivery_key(delivery), |req| {
+                update.apply(
+                    req.condition_expression(
+                        "attribute_exists(#pk) AND attempts = :previous_attempts \
+                         AND attribute_exists(next_attempt_at) \
+                         AND (attribute_not_exists(claimed_until) OR claimed_until < :now)",
+                    )
+                    .expression_attribute_names("#pk", &self.config.partition_key)
+                    .expression_attribute_values(
+                        ":previous_attempts",
+                        AttributeValue::N(delivery.attempts.to_string()),
+                    )
+                    .expression_attribute_values(":now", datetime_attribute(&Utc::now())),
+                )
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(false),
+            output => {
+                output?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Failed deliveries are moved to the bucket of their next attempt, the others are removed
+    /// from the index. Dead letters are kept until their webhook is deleted.
+    async fn update_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<bool> {
+        let mut update = UpdateExpression::default();
+        update.set(
+            "status",
+            AttributeValue::S(delivery.status.as_str().to_string()),
+        );
+        update.set("attempts", AttributeValue::N(delivery.attempts.to_string()));
+        update.set_or_remove(
+            "response_status",
+            delivery
+                .response_status
+                .map(|status| AttributeValue::N(status.to_string())),
+        );
+        update.set_or_remove("error", delivery.error.clone().map(AttributeValue::S));
+        update.set("updated_at", datetime_attribute(&delivery.updated_at));
+        update.remove("claimed_until");
+        match delivery.next_attempt_at {
+            Some(next_attempt_at) => {
+                update.set("next_attempt_at", datetime_attribute(&next_attempt_at));
+                update.set(
+                    &self.config.gsi4_partition_key,
+                    AttributeValue::S(retry_bucket_key(&retry_bucket(&next_attempt_at))),
+                );
+                update.set(
+                    &self.config.gsi4_sort_key,
+                    datetime_attribute(&next_attempt_at),
+                );
+            }
+            None => {
+                update.remove("next_attempt_at");
+                update.remove(&self.config.gsi4_partition_key);
+                update.remove(&self.config.gsi4_sort_key);
+            }
+        }
+        if delivery.status == WebhookDeliveryStatus::DeadLetter {
+            update.remove(&self.config.ttl_attribute);
+        }
+        let output = self
+            .update_item(&delivery_key(delivery), |req| {
+                // Also avoids recreating a delivery deleted along with its webhook.
+                update.apply(
+                    req.condition_expression(
+                        "attribute_exists(#pk) AND attempts = :previous_attempts",
+                    )
+                    .expression_attribute_names("#pk", &self.config.partition_key)
+                    .expression_attribute_values(
+                        ":previous_attempts",
+                        AttributeValue::N((delivery.attempts - 1).to_string()),
+                    ),
+                )
+            })
+            .await;
+        match output {
+            Err(DynamoDbErrors::ConditionalCheckFailed) => Ok(false),
+            output => {
+                output?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Webhooks are stored in the partition of their owner.
+fn webhook_key(owner: &Owner, id: &ID) -> ItemKey<String> {
+    ItemKey {
+        partition: owner.to_string(),
+        sort: String::from(id),
+    }
+}
+
+fn delivery_key(delivery: &WebhookDelivery) -> Key {
+    Key {
+        partition: delivery.webhook_id.clone(),
+        sort: delivery.id.clone(),
+    }
+}
+
+fn build_webhook(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<Webhook> {
+    let key: ItemKey<String> = dynamodb.extract_key(item)?;
+    Ok(Webhook {
+        id: key.sort.parse()?,
+        url: item.get_string("url")?.clone(),
+        events: item
+            .get_string_set("events")?
+            .iter()
+            .map(|event| event.parse())
+            .collect::<Result<_, _>>()?,
+        created_at: item.get_from_string("created_at")?,
+        secret: item.get_string("secret")?.clone(),
+        owner: key.partition.parse()?,
+    })
+}
+
+fn build_webhook_delivery(dynamodb: &DynamoTable, item: &RawAttributes) -> Result<WebhookDelivery> {
+    let key: Key = dynamodb.extract_key(item)?;
+    Ok(WebhookDelivery {
+        id: key.sort,
+        event: item.get_from_string("event")?,
+        status: item.get_from_string("status")?,
+        attempts: item.get_optional_from_number("attempts")?.unwrap_or(0),
+        response_status: item.get_optional_from_number("response_status")?,
+        error: item.get_optional_string("error")?.cloned(),
+        payload: item.get_string("payload")?.clone(),
+        created_at: item.get_from_string("created_at")?,
+        updated_at: item.get_from_string("updated_at")?,
+        next_attempt_at: item.get_optional_from_string("next_attempt_at")?,
+        webhook_id: key.partition,
+        owner: item.get_from_string("owner")?,
+    })
+}
@@ -0,0 +1,80 @@
+use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result};
+use chrono::Utc;
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::types::{Owner, ID};
+use crate::graphql::webhooks::webhooks;
+
+use super::extensions::DynamoTableWebhookExt;
+use super::{Webhook, WebhookEvent, WEBHOOK_TYPE_NAME};
+
+const MIN_SECRET_LENGTH: usize = 16;
+
+#[derive(Debug, InputObject)]
+struct WebhookCreateInput {
+    url: String,
+    /// Key of the HMAC-SHA256 signature of the deliveries, at least 16 characters long.
+    secret: String,
+    events: Vec<WebhookEvent>,
+}
+
+#[derive(Default)]
+pub struct WebhookMutation;
+
+#[Object]
+impl WebhookMutation {
+    async fn webhook_create(
+        &self,
+        ctx: &Context<'_>,
+        input: WebhookCreateInput,
+    ) -> Result<Webhook> {
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        webhooks(ctx)
+            .check_url(&input.url)
+            .await
+            .map_err(|err| Errors::InvalidValue(err).extend())?;
+        if input.secret.len() < MIN_SECRET_LENGTH {
+            return Err(Errors::InvalidValue(format!(
+                "Webhook secrets must be at least {} characters long",
+                MIN_SECRET_LENGTH
+            ))
+            .extend());
+        }
+        if input.events.is_empty() {
+            return Err(
+                Errors::InvalidValue("Webhooks require at least one event".to_string()).extend(),
+            );
+        }
+
+        let mut events: Vec<WebhookEvent> = Vec::new();
+        for event in input.events {
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        let webhook = Webhook {
+            id: ID::new(WEBHOOK_TYPE_NAME),
+            url: input.url,
+            events,
+            created_at: Utc::now(),
+            secret: input.secret,
+            owner: Owner::from(principal),
+        };
+        dynamodb.put_webhook(&webhook).await.map(|_| webhook)
+    }
+
+    /// Also deletes the deliveries of the webhook.
+    async fn webhook_delete(&self, ctx: &Context<'_>, id: ID) -> Result<Webhook> {
+        check_id_kind(&id, WEBHOOK_TYPE_NAME)?;
+        let principal = principal(ctx)?;
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        let webhook = dynamodb
+            .get_webhook(&Owner::from(principal), &id)
+            .await?
+            .ok_or_else(|| Errors::NotFound.extend())?;
+        dynamodb.delete_webhook(&webhook).await?;
+        Ok(webhook)
+    }
+}
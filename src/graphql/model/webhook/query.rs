@@ -0,0 +1,51 @@
+use async_graphql::connection::Connection;
+use async_graphql::{Context, ErrorExtensions, Object, Result};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::errors::{check_id_kind, principal, Errors};
+use crate::graphql::limits::COLLECTION_COMPLEXITY;
+use crate::graphql::pagination::{build_connection, page_size};
+use crate::graphql::types::{Owner, ID};
+
+use super::extensions::DynamoTableWebhookExt;
+use super::{Webhook, WebhookDelivery, WEBHOOK_TYPE_NAME};
+
+#[derive(Default)]
+pub struct WebhookQuery;
+
+#[Object]
+impl WebhookQuery {
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn webhooks(&self, ctx: &Context<'_>) -> Result<Vec<Webhook>> {
+        let owner = Owner::from(principal(ctx)?);
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        dynamodb.get_webhooks(&owner).await
+    }
+
+    /// Deliveries of a webhook, most recent first, the cursor being the id of a delivery.
+    #[graphql(complexity = "COLLECTION_COMPLEXITY * child_complexity")]
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook: ID,
+        first: Option<usize>,
+        after: Option<ID>,
+    ) -> Result<Connection<String, WebhookDelivery>> {
+        check_id_kind(&webhook, WEBHOOK_TYPE_NAME)?;
+        let limit = page_size(first)?;
+        let owner = Owner::from(principal(ctx)?);
+        let dynamodb = ctx.data_unchecked::<DynamoTable>();
+        if dynamodb.get_webhook(&owner, &webhook).await?.is_none() {
+            return Err(Errors::NotFound.extend());
+        }
+        let (deliveries, has_next_page) = dynamodb
+            .get_webhook_deliveries(&webhook, after.as_ref(), limit)
+            .await?;
+        Ok(build_connection(
+            deliveries,
+            after.is_some(),
+            has_next_page,
+            |delivery| String::from(&delivery.id),
+        ))
+    }
+}
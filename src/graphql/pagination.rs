@@ -0,0 +1,39 @@
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::{ErrorExtensions, OutputType, Result};
+
+use crate::graphql::errors::Errors;
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Number of items of a page requested with `first`.
+pub fn page_size(first: Option<usize>) -> Result<usize> {
+    let size = first.unwrap_or(DEFAULT_PAGE_SIZE);
+    if size > MAX_PAGE_SIZE {
+        return Err(Errors::InvalidValue(format!(
+            "At most {} items can be retrieved at once",
+            MAX_PAGE_SIZE
+        ))
+        .extend());
+    }
+    Ok(size)
+}
+
+/// Connections only support forward pagination, a page having a previous one if it was
+/// requested with a cursor.
+pub fn build_connection<T, C>(
+    nodes: Vec<T>,
+    has_previous_page: bool,
+    has_next_page: bool,
+    cursor: C,
+) -> Connection<String, T>
+where
+    T: OutputType,
+    C: Fn(&T) -> String,
+{
+    let mut connection = Connection::new(has_previous_page, has_next_page);
+    connection
+        .edges
+        .extend(nodes.into_iter().map(|node| Edge::new(cursor(&node), node)));
+    connection
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest, NextRequest,
+};
+use async_graphql::{ErrorExtensionValues, Request, Response, ServerError, ServerResult, Value};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+// Message used by async-graphql when the hash of an automatic persisted query is unknown. Apollo
+// clients rely on it to send the full query again.
+const NOT_FOUND_MESSAGE: &str = "PersistedQueryNotFound";
+
+/// Queries allowed in strict mode, indexed by the hex encoded sha256 hash of their text. The
+/// manifest file is a JSON object mapping each hash to its query.
+pub struct PersistedQueryManifest(HashMap<String, String>);
+
+impl PersistedQueryManifest {
+    pub fn load(path: &str) -> anyhow::Result<PersistedQueryManifest> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read persisted queries manifest '{}'", path))?;
+        let queries: HashMap<String, String> = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid persisted queries manifest '{}'", path))?;
+        for (hash, query) in queries.iter() {
+            if *hash != sha256_hex(query) {
+                anyhow::bail!("Persisted query '{}' does not match its hash", hash);
+            }
+        }
+        info!("Loaded {} persisted queries from {}", queries.len(), path);
+        Ok(PersistedQueryManifest(queries))
+    }
+}
+
+/// Complements the automatic persisted queries of async-graphql. It adds an error code to unknown
+/// hashes and, when a manifest is provided, only executes the queries listed in it.
+pub struct PersistedQueries {
+    manifest: Option<Arc<PersistedQueryManifest>>,
+}
+
+impl PersistedQueries {
+    pub fn new(manifest: Option<PersistedQueryManifest>) -> PersistedQueries {
+        PersistedQueries {
+            manifest: manifest.map(Arc::new),
+        }
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            manifest: self.manifest.clone(),
+        })
+    }
+}
+
+struct PersistedQueriesExtension {
+    manifest: Option<Arc<PersistedQueryManifest>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        for error in response.errors.iter_mut() {
+            if error.message == NOT_FOUND_MESSAGE {
+                error
+                    .extensions
+                    .get_or_insert_with(ErrorExtensionValues::default)
+                    .set("code", "PERSISTED_QUERY_NOT_FOUND");
+            }
+        }
+        response
+    }
+
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if let Some(manifest) = &self.manifest {
+            let hash = if request.query.is_empty() {
+                persisted_query_hash(&request).unwrap_or_default()
+            } else {
+                sha256_hex(&request.query)
+            };
+            if let Some(query) = manifest.0.get(&hash) {
+                // The automatic persisted queries extension still checks the hash and caches the
+                // parsed document.
+                request.query = query.clone();
+            } else {
+                let mut error = ServerError::new("Query is not allowed", None);
+                let mut extensions = ErrorExtensionValues::default();
+                extensions.set("code", "PERSISTED_QUERY_NOT_ALLOWED");
+                error.extensions = Some(extensions);
+                return Err(error);
+            }
+        }
+        next.run(ctx, request).await
+    }
+}
+
+fn persisted_query_hash(request: &Request) -> Option<String> {
+    if let Some(Value::Object(persisted_query)) = request.extensions.get("persistedQuery") {
+        if let Some(Value::String(hash)) = persisted_query.get("sha256Hash") {
+            return Some(hash.clone());
+        }
+    }
+    None
+}
+
+fn sha256_hex(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
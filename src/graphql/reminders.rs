@@ -0,0 +1,166 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::model::todo::reminder::{reminder_bucket, DynamoTableReminderExt};
+use crate::graphql::model::Todo;
+
+use notifier::{LogNotifier, Notifier, Notifiers, SubscriptionNotifier, WebhookNotifier};
+
+pub mod notifier;
+
+/// Reminders sent while a subscriber lags behind by more than this are skipped for it.
+const EVENTS_CAPACITY: usize = 256;
+
+/// Duration of the claim of a reminder by an instance, after which another one can send it if the
+/// first one didn't record it as sent, such as when it stopped meanwhile.
+const CLAIM_DURATION_SECS: i64 = 300;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierKind {
+    Log,
+    Webhook,
+    Subscription,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReminderConfig {
+    pub enabled: bool,
+    pub poll_interval_secs: u64,
+    /// Due reminders older than this when the server starts are never sent, but logged.
+    pub lookback_hours: u32,
+    pub notifiers: Vec<NotifierKind>,
+    pub webhook_url: Option<String>,
+}
+
+/// Reminders sent within this process, consumed by the `todoReminders` subscription.
+#[derive(Clone)]
+pub struct ReminderEvents(broadcast::Sender<Todo>);
+
+impl Default for ReminderEvents {
+    fn default() -> Self {
+        ReminderEvents(broadcast::channel(EVENTS_CAPACITY).0)
+    }
+}
+
+impl ReminderEvents {
+    pub fn subscribe(&self) -> broadcast::Receiver<Todo> {
+        self.0.subscribe()
+    }
+
+    /// Events without any subscriber are simply dropped.
+    pub fn send(&self, todo: &Todo) {
+        let _ = self.0.send(todo.clone());
+    }
+}
+
+/// Starts the background task sending the due reminders, if enabled.
+pub fn spawn_scheduler(
+    config: ReminderConfig,
+    dynamodb: DynamoTable,
+    events: ReminderEvents,
+) -> Result<Option<JoinHandle<()>>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    for kind in &config.notifiers {
+        notifiers.push(match kind {
+            NotifierKind::Log => Box::new(LogNotifier),
+            NotifierKind::Webhook => {
+                let url = config
+                    .webhook_url
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("The webhook notifier requires a webhook_url"))?;
+                Box::new(WebhookNotifier::new(url)?)
+            }
+            NotifierKind::Subscription => Box::new(SubscriptionNotifier(events.clone())),
+        });
+    }
+    let notifier = Notifiers(notifiers);
+
+    info!("Reminder scheduler started.");
+    Ok(Some(tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(StdDuration::from_secs(config.poll_interval_secs.max(1)));
+        let mut since =
+            reminder_bucket(&(Utc::now() - Duration::hours(config.lookback_hours.into())));
+        if let Err(err) = log_missed_reminders(&dynamodb, &since).await {
+            error!("Failed to retrieve the missed reminders: {:?}", err);
+        }
+        loop {
+            interval.tick().await;
+            match send_due_reminders(&dynamodb, &notifier, since, Utc::now()).await {
+                Ok(next) => since = next,
+                Err(err) => error!("Failed to send the due reminders: {:?}", err),
+            }
+        }
+    })))
+}
+
+/// Reminders due before the lookback window are left pending, as they're likely irrelevant by
+/// now, but are logged so they don't go unnoticed.
+async fn log_missed_reminders(
+    dynamodb: &DynamoTable,
+    since: &DateTime<Utc>,
+) -> async_graphql::Result<()> {
+    let missed = dynamodb.get_missed_reminders(since).await?;
+    for (_, todo) in &missed {
+        warn!(
+            todo = %todo.id,
+            reminder_at = ?todo.reminder_at,
+            "Reminder missed before the lookback window, not sent"
+        );
+    }
+    if !missed.is_empty() {
+        warn!(
+            "{} reminders missed before the lookback window",
+            missed.len()
+        );
+    }
+    Ok(())
+}
+
+/// Sends the due reminders of the buckets from `since` up to `now`, and returns the bucket from
+/// which the next poll must start. Every instance runs the scheduler, so a reminder is claimed
+/// before being notified and only marked as sent afterwards. A failed or concurrently claimed
+/// reminder keeps its bucket polled until it's sent. Delivery is thus at-least-once, a reminder
+/// being sent twice only if an instance stops between its notification and its marking.
+async fn send_due_reminders(
+    dynamodb: &DynamoTable,
+    notifier: &dyn Notifier,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> async_graphql::Result<DateTime<Utc>> {
+    let current = reminder_bucket(&now);
+    let claimed_until = now + Duration::seconds(CLAIM_DURATION_SECS);
+    let mut next = current;
+    let mut bucket = since;
+    while bucket <= current {
+        for (key, todo) in dynamodb.get_due_reminders(&bucket, &now).await? {
+            if !dynamodb.claim_reminder(&key, &todo, &claimed_until).await? {
+                next = next.min(bucket);
+                continue;
+            }
+            match notifier.notify(&todo).await {
+                Ok(()) => {
+                    dynamodb.mark_reminder_sent(&key, &todo).await?;
+                }
+                Err(err) => {
+                    warn!(todo = %todo.id, "Failed to notify reminder: {:?}", err);
+                    dynamodb.release_reminder(&key, &claimed_until).await?;
+                    next = next.min(bucket);
+                }
+            }
+        }
+        bucket += Duration::hours(1);
+    }
+    Ok(next)
+}
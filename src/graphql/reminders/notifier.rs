@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Request, Uri};
+use hyper_rustls::HttpsConnector;
+use serde_json::json;
+use tracing::info;
+
+use crate::graphql::model::Todo;
+
+use super::ReminderEvents;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Destination of the due reminders. A failed notification is retried on the next poll, so
+/// notifiers must tolerate duplicates.
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, todo: &Todo) -> Result<()>;
+}
+
+/// Only writes the reminders to the logs.
+pub struct LogNotifier;
+
+#[async_trait::async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, todo: &Todo) -> Result<()> {
+        info!(
+            todo = %todo.id,
+            owner = %todo.owner,
+            reminder_at = ?todo.reminder_at,
+            "Reminder of '{}'",
+            todo.title
+        );
+        Ok(())
+    }
+}
+
+/// Posts the reminders as JSON to a fixed URL, any status other than 2XX being a failure.
+pub struct WebhookNotifier {
+    client: Client<HttpsConnector<HttpConnector>>,
+    url: Uri,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Result<WebhookNotifier> {
+        Ok(WebhookNotifier {
+            client: Client::builder().build(HttpsConnector::with_native_roots()),
+            url: url.parse()?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, todo: &Todo) -> Result<()> {
+        let body = json!({
+            "event": "todo.reminder",
+            "todo": {
+                "id": String::from(&todo.id),
+                "title": todo.title,
+                "owner": todo.owner.to_string(),
+                "list": todo.list_id.as_ref().map(String::from),
+                "dueAt": todo.due_at.map(|due_at| due_at.to_rfc3339()),
+                "reminderAt": todo.reminder_at.map(|reminder_at| reminder_at.to_rfc3339()),
+            },
+        });
+        let request = Request::post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(&body)?))?;
+        let response =
+            tokio::time::timeout(WEBHOOK_TIMEOUT, self.client.request(request)).await??;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Webhook responded with {}", response.status()))
+        }
+    }
+}
+
+/// Publishes the reminders to the `todoReminders` subscriptions of this process.
+pub struct SubscriptionNotifier(pub ReminderEvents);
+
+#[async_trait::async_trait]
+impl Notifier for SubscriptionNotifier {
+    async fn notify(&self, todo: &Todo) -> Result<()> {
+        self.0.send(todo);
+        Ok(())
+    }
+}
+
+/// Notifies all of the notifiers, failing if any of them did.
+pub struct Notifiers(pub Vec<Box<dyn Notifier>>);
+
+#[async_trait::async_trait]
+impl Notifier for Notifiers {
+    async fn notify(&self, todo: &Todo) -> Result<()> {
+        let mut result = Ok(());
+        for notifier in &self.0 {
+            if let Err(err) = notifier.notify(todo).await {
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,295 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{anyhow, Result};
+use async_graphql::Context;
+use serde::Deserialize;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, TantivyDocument, Term};
+use tokio_stream::StreamExt;
+use tracing::{error, info};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::model::todo::extensions::build_todo;
+use crate::graphql::model::todo_list::extensions::build_todo_list;
+use crate::graphql::model::{Todo, TodoList, TODO_LIST_TYPE_NAME, TODO_TYPE_NAME};
+use crate::graphql::types::{Owner, ID};
+
+/// Titles are more relevant than the notes and the tags of a todo.
+const TITLE_BOOST: f32 = 2.0;
+
+/// Updates committed at once at most, so a burst of mutations becomes searchable in steps.
+const MAX_UPDATES_PER_COMMIT: usize = 1000;
+
+/// Results skipped at most by a search, as they're all collected along with the returned ones.
+pub const MAX_SEARCH_OFFSET: usize = 1000;
+
+/// Like the events, the index is updated once the mutation succeeded.
+pub fn search<'a>(ctx: &Context<'a>) -> &'a SearchIndex {
+    ctx.data_unchecked::<SearchIndex>()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchConfig {
+    /// Directory of the index, created if missing.
+    pub index_dir: PathBuf,
+    /// Memory used by the writer before flushing its documents, split among its threads.
+    pub writer_memory_mb: usize,
+}
+
+/// Fields of the documents, a document being either a todo or a todo list.
+#[derive(Clone, Copy)]
+struct SearchFields {
+    id: Field,
+    owner: Field,
+    title: Field,
+    notes: Field,
+    tags: Field,
+}
+
+/// Change of the document of an item, removed when missing.
+struct SearchUpdate {
+    id: ID,
+    document: Option<TantivyDocument>,
+}
+
+/// Full-text index of the todos and todo lists outside of the trash, stored on the local disk.
+/// Only the ids are stored, the items being read from DynamoDB when searched.
+#[derive(Clone)]
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Arc<Mutex<IndexWriter>>,
+    updates: Sender<SearchUpdate>,
+    fields: SearchFields,
+}
+
+impl SearchIndex {
+    /// Opens the index, or creates it. The directory is locked by its writer, so a single process
+    /// can use it at once.
+    pub fn load(config: &SearchConfig) -> Result<SearchIndex> {
+        let mut schema = Schema::builder();
+        let fields = SearchFields {
+            id: schema.add_text_field("id", STRING | STORED),
+            owner: schema.add_text_field("owner", STRING),
+            title: schema.add_text_field("title", TEXT),
+            notes: schema.add_text_field("notes", TEXT),
+            tags: schema.add_text_field("tags", TEXT),
+        };
+        std::fs::create_dir_all(&config.index_dir)?;
+        let index = Index::open_or_create(MmapDirectory::open(&config.index_dir)?, schema.build())?;
+        let reader = index.reader()?;
+        let writer = Arc::new(Mutex::new(
+            index.writer(config.writer_memory_mb * 1024 * 1024)?,
+        ));
+        let (updates, received) = mpsc::channel();
+        {
+            let writer = writer.clone();
+            thread::Builder::new()
+                .name("search-writer".to_string())
+                .spawn(move || write_updates(&writer, fields.id, received))?;
+        }
+        info!("Search index opened in {}", config.index_dir.display());
+        Ok(SearchIndex {
+            index,
+            reader,
+            writer,
+            updates,
+            fields,
+        })
+    }
+
+    /// Indexes the todo, replacing its previous version if any.
+    pub fn index_todo(&self, todo: &Todo) {
+        self.update(&todo.id, Some(self.todo_document(todo)));
+    }
+
+    pub fn index_todo_list(&self, todo_list: &TodoList) {
+        self.update(&todo_list.id, Some(self.todo_list_document(todo_list)));
+    }
+
+    /// Removes a todo or a todo list, once moved to the trash.
+    pub fn remove(&self, id: &ID) {
+        self.update(id, None);
+    }
+
+    /// Ids of the items of the owner matching the query, most relevant first, skipping the first
+    /// `offset` ones. The query supports the syntax of tantivy, such as `"exact phrase"` or
+    /// `title:release`, and its invalid parts are ignored. Searching blocks on the index, so it
+    /// runs on the blocking threads.
+    pub async fn search(
+        &self,
+        owner: &Owner,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ID>> {
+        if offset.checked_add(limit).is_none() {
+            return Err(anyhow!("Too many search results requested"));
+        }
+        let index = self.clone();
+        let owner = owner.clone();
+        let query = query.to_string();
+        tokio::task::spawn_blocking(move || index.search_blocking(&owner, &query, offset, limit))
+            .await?
+    }
+
+    fn search_blocking(
+        &self,
+        owner: &Owner,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ID>> {
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![self.fields.title, self.fields.notes, self.fields.tags],
+        );
+        parser.set_field_boost(self.fields.title, TITLE_BOOST);
+        let (text_query, _) = parser.parse_query_lenient(query);
+        let owner_query = TermQuery::new(
+            Term::from_field_text(self.fields.owner, &owner.to_string()),
+            IndexRecordOption::Basic,
+        );
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, text_query),
+            (Occur::Must, Box::new(owner_query)),
+        ]);
+
+        let searcher = self.reader.searcher();
+        let hits = searcher.search(&query, &TopDocs::with_limit(limit).and_offset(offset))?;
+        hits.into_iter()
+            .map(|(_, address)| {
+                let document: TantivyDocument = searcher.doc(address)?;
+                document
+                    .get_first(self.fields.id)
+                    .and_then(|id| id.as_str())
+                    .ok_or_else(|| anyhow!("Search document without any id"))?
+                    .parse()
+            })
+            .collect()
+    }
+
+    /// Replaces the whole index with the todos and todo lists of the table, outside of the trash.
+    /// Returns the number of indexed items.
+    pub async fn rebuild(&self, dynamodb: &DynamoTable) -> Result<usize> {
+        let mut todos = Vec::new();
+        let mut paginator = dynamodb
+            .scan_by_sort_prefix(&ID::prefix(TODO_TYPE_NAME))
+            .filter_expression("attribute_not_exists(deleted_at)")
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = dynamodb.extract_key(item)?;
+                todos.push(build_todo(dynamodb, &key, item).map_err(|err| anyhow!(err.message))?);
+            }
+        }
+        let mut todo_lists = Vec::new();
+        let mut paginator = dynamodb
+            .scan_entities_by_prefix(&ID::prefix(TODO_LIST_TYPE_NAME))
+            .into_paginator()
+            .send();
+        while let Some(output) = paginator.next().await {
+            for item in output?.items().unwrap_or_default() {
+                let key = dynamodb.extract_key::<ID>(item)?;
+                let todo_list = build_todo_list(dynamodb, &key.partition, item)
+                    .map_err(|err| anyhow!(err.message))?;
+                if todo_list.deleted_at.is_none() {
+                    todo_lists.push(todo_list);
+                }
+            }
+        }
+
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| anyhow!("Poisoned index writer"))?;
+        writer.delete_all_documents()?;
+        for todo in &todos {
+            writer.add_document(self.todo_document(todo))?;
+        }
+        for todo_list in &todo_lists {
+            writer.add_document(self.todo_list_document(todo_list))?;
+        }
+        writer.commit()?;
+        Ok(todos.len() + todo_lists.len())
+    }
+
+    fn todo_document(&self, todo: &Todo) -> TantivyDocument {
+        doc!(
+            self.fields.id => String::from(&todo.id),
+            self.fields.owner => todo.owner.to_string(),
+            self.fields.title => todo.title.clone(),
+            self.fields.notes => todo.notes.clone().unwrap_or_default(),
+            self.fields.tags => todo.tags.join(" "),
+        )
+    }
+
+    fn todo_list_document(&self, todo_list: &TodoList) -> TantivyDocument {
+        doc!(
+            self.fields.id => String::from(&todo_list.id),
+            self.fields.owner => todo_list.owner.to_string(),
+            self.fields.title => todo_list.title.clone(),
+        )
+    }
+
+    /// Updates happen in the background, so mutations neither wait for them nor fail with them.
+    /// They're sent to a single writer in the order of the mutations, and become searchable once
+    /// committed along with the other pending updates.
+    fn update(&self, id: &ID, document: Option<TantivyDocument>) {
+        let update = SearchUpdate {
+            id: id.clone(),
+            document,
+        };
+        if self.updates.send(update).is_err() {
+            error!(
+                "Failed to update '{}' in the search index: writer stopped",
+                id
+            );
+        }
+    }
+}
+
+/// Applies the updates until every index is dropped, committing the pending ones at once.
+fn write_updates(writer: &Mutex<IndexWriter>, id_field: Field, updates: Receiver<SearchUpdate>) {
+    while let Ok(update) = updates.recv() {
+        let mut batch = vec![update];
+        while batch.len() < MAX_UPDATES_PER_COMMIT {
+            match updates.try_recv() {
+                Ok(update) => batch.push(update),
+                Err(_) => break,
+            }
+        }
+        let len = batch.len();
+        if let Err(err) = write_batch(writer, id_field, batch) {
+            error!(
+                "Failed to apply {} updates to the search index: {:?}",
+                len, err
+            );
+        }
+    }
+}
+
+fn write_batch(
+    writer: &Mutex<IndexWriter>,
+    id_field: Field,
+    batch: Vec<SearchUpdate>,
+) -> Result<()> {
+    let mut writer = writer
+        .lock()
+        .map_err(|_| anyhow!("Poisoned index writer"))?;
+    for update in batch {
+        writer.delete_term(Term::from_field_text(id_field, &String::from(&update.id)));
+        if let Some(document) = update.document {
+            writer.add_document(document)?;
+        }
+    }
+    writer.commit()?;
+    Ok(())
+}
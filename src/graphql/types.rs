@@ -1,3 +1,5 @@
 pub use id::ID;
+pub use owner::Owner;
 
 mod id;
+mod owner;
@@ -33,6 +33,24 @@ impl ID {
     pub fn has_type_name(&self, type_name: &str) -> bool {
         self.type_name.0 == type_name
     }
+
+    /// Same identifier with another type name, used for the different states of an entity.
+    pub fn with_type_name(&self, type_name: &str) -> ID {
+        ID {
+            type_name: TypeName(type_name.to_owned()),
+            ulid: self.ulid,
+        }
+    }
+
+    /// Unique part of the identifier, shared by the different states of an entity.
+    pub fn ulid(&self) -> Ulid {
+        self.ulid
+    }
+
+    /// Creation time of the entity, in milliseconds since the Unix epoch.
+    pub fn timestamp_ms(&self) -> u64 {
+        self.ulid.timestamp_ms()
+    }
 }
 
 impl Display for ID {
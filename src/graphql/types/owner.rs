@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::auth::Principal;
+
+/// Owner of the stored items: the tenant of the user if any, the user itself otherwise. It will
+/// be stored as a string formatted as 'owner#tenant#<tenant>' or 'owner#subject#<subject>' in
+/// DynamoDB, so a user whose subject is the id of a tenant doesn't get access to its items.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Owner(String);
+
+const PREFIX: &str = "owner#";
+const TENANT_PREFIX: &str = "tenant#";
+const SUBJECT_PREFIX: &str = "subject#";
+
+impl From<&Principal> for Owner {
+    fn from(principal: &Principal) -> Self {
+        match &principal.tenant {
+            Some(tenant) => Owner(format!("{}{}", TENANT_PREFIX, tenant)),
+            None => Owner(format!("{}{}", SUBJECT_PREFIX, principal.subject)),
+        }
+    }
+}
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", PREFIX, self.0)
+    }
+}
+
+impl FromStr for Owner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        s.strip_prefix(PREFIX)
+            .filter(|name| name.starts_with(TENANT_PREFIX) || name.starts_with(SUBJECT_PREFIX))
+            .map(|name| Owner(name.to_owned()))
+            .ok_or_else(|| anyhow!("Invalid owner format"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(subject: &str, tenant: Option<&str>) -> Principal {
+        Principal {
+            subject: subject.to_string(),
+            tenant: tenant.map(str::to_string),
+            scopes: Default::default(),
+        }
+    }
+
+    #[test]
+    fn tenants_and_subjects_are_distinct_owners() {
+        let tenant = Owner::from(&principal("alice", Some("acme")));
+        let subject = Owner::from(&principal("acme", None));
+        assert_ne!(tenant, subject);
+        assert_eq!(tenant.to_string(), "owner#tenant#acme");
+        assert_eq!(subject.to_string(), "owner#subject#acme");
+    }
+
+    #[test]
+    fn parses_displayed_owner() {
+        let owner = Owner::from(&principal("alice", None));
+        assert_eq!(owner.to_string().parse::<Owner>().unwrap(), owner);
+        assert!("owner#acme".parse::<Owner>().is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use std::time::Duration as StdDuration;
+
+use anyhow::{anyhow, Result};
+use async_graphql::Context;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Request, Uri};
+use hyper_rustls::HttpsConnector;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::dynamodb::DynamoTable;
+use crate::graphql::model::webhook::extensions::{retry_bucket, DynamoTableWebhookExt};
+use crate::graphql::model::webhook::{
+    Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookEvent,
+};
+use crate::graphql::model::{Todo, TodoList};
+use crate::graphql::types::Owner;
+
+use addresses::PublicResolver;
+
+pub mod addresses;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// Events are published once the mutation succeeded.
+pub fn webhooks<'a>(ctx: &Context<'a>) -> &'a WebhookDispatcher {
+    ctx.data_unchecked::<WebhookDispatcher>()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookConfig {
+    /// Attempts of a delivery before it becomes a dead letter.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each attempt.
+    pub initial_backoff_ms: u64,
+    pub timeout_secs: u64,
+    /// Number of days the deliveries are kept, dead letters excepted.
+    pub delivery_retention_days: u32,
+    /// Interval of the polls of the due retries, which resume the retries interrupted by a stop
+    /// of the server.
+    #[serde(default = "default_retry_poll_interval_secs")]
+    pub retry_poll_interval_secs: u64,
+    /// Serves `POST /webhooks/local`, which only logs the deliveries it receives.
+    pub local_receiver: bool,
+    /// Allows the URLs of the webhooks to resolve to loopback or private addresses, which is
+    /// required by the local receiver but lets the users reach the private network otherwise.
+    #[serde(default)]
+    pub allow_private_networks: bool,
+}
+
+fn default_retry_poll_interval_secs() -> u64 {
+    30
+}
+
+/// Publishes the events of the mutations to the webhooks of the owner of the changed items.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    client: Client<HttpsConnector<HttpConnector<PublicResolver>>>,
+    config: WebhookConfig,
+    dynamodb: DynamoTable,
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: WebhookConfig, dynamodb: DynamoTable) -> Result<WebhookDispatcher> {
+        // Same as `HttpsConnector::with_native_roots`, but connecting to public addresses only.
+        let mut http = HttpConnector::new_with_resolver(PublicResolver {
+            allow_private_networks: config.allow_private_networks,
+        });
+        http.enforce_http(false);
+        let mut tls = rustls::ClientConfig::new();
+        tls.root_store = match rustls_native_certs::load_native_certs() {
+            Ok(store) | Err((Some(store), _)) => store,
+            Err((None, err)) => {
+                return Err(anyhow!("Unable to load the native certificates: {}", err))
+            }
+        };
+        tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(WebhookDispatcher {
+            client: Client::builder().build(HttpsConnector::from((http, tls))),
+            config,
+            dynamodb,
+        })
+    }
+
+    /// Starts the background task polling the due retries. Every instance runs it, each attempt
+    /// being claimed beforehand, and the first poll covers the whole retention period so the
+    /// retries pending when the server stopped are resumed.
+    pub fn spawn_retries(&self) -> JoinHandle<()> {
+        let dispatcher = self.clone();
+        info!("Webhook retries started.");
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(StdDuration::from_secs(
+                dispatcher.config.retry_poll_interval_secs.max(1),
+            ));
+            let mut since = retry_bucket(
+                &(Utc::now() - Duration::days(dispatcher.config.delivery_retention_days.into())),
+            );
+            loop {
+                interval.tick().await;
+                match dispatcher.retry_due(since, Utc::now()).await {
+                    Ok(next) => since = next,
+                    Err(err) => error!("Failed to retry the due webhook deliveries: {:?}", err),
+                }
+            }
+        })
+    }
+
+    /// Attempts the due deliveries of the buckets from `since` up to `now`, and returns the bucket
+    /// from which the next poll must start. A delivery claimed by another instance keeps its
+    /// bucket polled, in case that instance stops before recording its attempt.
+    async fn retry_due(
+        &self,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> async_graphql::Result<DateTime<Utc>> {
+        let current = retry_bucket(&now);
+        let mut next = current;
+        let mut bucket = since;
+        while bucket <= current {
+            for delivery in self
+                .dynamodb
+                .get_due_webhook_deliveries(&bucket, &now)
+                .await?
+            {
+                let webhook = match self
+                    .dynamodb
+                    .get_webhook(&delivery.owner, &delivery.webhook_id)
+                    .await?
+                {
+                    Some(webhook) => webhook,
+                    // Its deliveries are being deleted along with it.
+                    None => continue,
+                };
+                if self.attempt(&webhook, delivery).await?.is_none() {
+                    next = next.min(bucket);
+                }
+            }
+            bucket += Duration::hours(1);
+        }
+        Ok(next)
+    }
+
+    /// Checks that a webhook URL is an absolute HTTP(S) URL whose host resolves to public
+    /// addresses. The host is resolved again on each delivery, as it may change meanwhile.
+    pub async fn check_url(&self, url: &str) -> Result<(), String> {
+        let uri = url
+            .parse::<Uri>()
+            .ok()
+            .filter(|uri| matches!(uri.scheme_str(), Some("http") | Some("https")))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid webhook URL '{}', it must be an absolute HTTP(S) URL",
+                    url
+                )
+            })?;
+        addresses::check_uri(&uri, self.config.allow_private_networks)
+            .await
+            .map_err(|err| format!("Invalid webhook URL '{}': {}", url, err))
+    }
+
+    pub fn todo_event(&self, event: WebhookEvent, todo: &Todo) {
+        self.dispatch(
+            &todo.owner,
+            event,
+            json!({
+                "id": String::from(&todo.id),
+                "title": todo.title,
+                "complete": todo.complete,
+                "list": todo.list_id.as_ref().map(String::from),
+                "parent": todo.parent_id.as_ref().map(String::from),
+                "dueAt": todo.due_at.map(|due_at| due_at.to_rfc3339()),
+                "priority": todo.priority.map(|priority| priority.as_str()),
+                "tags": todo.tags,
+            }),
+        )
+    }
+
+    pub fn todo_list_event(&self, event: WebhookEvent, todo_list: &TodoList) {
+        self.dispatch(
+            &todo_list.owner,
+            event,
+            json!({
+                "id": String::from(&todo_list.id),
+                "title": todo_list.title,
+                "archived": todo_list.archived,
+                "isTemplate": todo_list.is_template,
+            }),
+        )
+    }
+
+    /// Deliveries happen in the background, so mutations neither wait for them nor fail with
+    /// them.
+    fn dispatch(&self, owner: &Owner, event: WebhookEvent, data: Value) {
+        let dispatcher = self.clone();
+        let owner = owner.clone();
+        tokio::spawn(async move {
+            if let Err(err) = dispatcher.dispatch_now(&owner, event, data).await {
+                error!(
+                    "Failed to dispatch {} to webhooks: {:?}",
+                    event.as_str(),
+                    err
+                );
+            }
+        });
+    }
+
+    async fn dispatch_now(
+        &self,
+        owner: &Owner,
+        event: WebhookEvent,
+        data: Value,
+    ) -> async_graphql::Result<()> {
+        let webhooks = self.dynamodb.get_webhooks(owner).await?;
+        let payload = json!({
+            "event": event.as_str(),
+            "occurredAt": Utc::now().to_rfc3339(),
+            "data": data,
+        })
+        .to_string();
+        let expires_at = Utc::now() + Duration::days(self.config.delivery_retention_days.into());
+        for webhook in webhooks {
+            if !webhook.events.contains(&event) {
+                continue;
+            }
+            let delivery = WebhookDelivery::new(&webhook, event, payload.clone());
+            self.dynamodb
+                .put_webhook_delivery(&delivery, &expires_at)
+                .await?;
+            let dispatcher = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = dispatcher.deliver(&webhook, delivery).await {
+                    error!(webhook = %webhook.id, "Failed to record a delivery: {:?}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Retries with an exponential backoff until the webhook responds with a 2XX status. The
+    /// retries are also recorded on the delivery, so the poller resumes them if this process stops
+    /// meanwhile.
+    async fn deliver(
+        &self,
+        webhook: &Webhook,
+        mut delivery: WebhookDelivery,
+    ) -> async_graphql::Result<()> {
+        loop {
+            delivery = match self.attempt(webhook, delivery).await? {
+                Some(delivery) => delivery,
+                None => return Ok(()),
+            };
+            match delivery.next_attempt_at {
+                Some(next_attempt_at) => {
+                    let delay = (next_attempt_at - Utc::now()).to_std().unwrap_or_default();
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Makes an attempt of the delivery and records it. Returns the recorded delivery, or `None`
+    /// if the attempt was claimed by another instance or the webhook was deleted meanwhile.
+    async fn attempt(
+        &self,
+        webhook: &Webhook,
+        mut delivery: WebhookDelivery,
+    ) -> async_graphql::Result<Option<WebhookDelivery>> {
+        // Long enough for the request and the recording of its result.
+        let claimed_until = Utc::now() + Duration::seconds(self.config.timeout_secs as i64 * 2);
+        if !self
+            .dynamodb
+            .claim_webhook_delivery(&delivery, &claimed_until)
+            .await?
+        {
+            return Ok(None);
+        }
+        delivery.attempts += 1;
+        match self.post(webhook, &delivery).await {
+            Ok(status) => {
+                delivery.response_status = Some(status);
+                delivery.error = if (200..300).contains(&status) {
+                    None
+                } else {
+                    Some(format!("Unexpected status {}", status))
+                };
+            }
+            Err(err) => {
+                delivery.response_status = None;
+                delivery.error = Some(err.to_string());
+            }
+        }
+        delivery.updated_at = Utc::now();
+        delivery.status = if delivery.error.is_none() {
+            WebhookDeliveryStatus::Delivered
+        } else if delivery.attempts >= self.config.max_attempts {
+            warn!(webhook = %webhook.id, delivery = %delivery.id, "Webhook delivery dead-lettered");
+            WebhookDeliveryStatus::DeadLetter
+        } else {
+            WebhookDeliveryStatus::Failed
+        };
+        delivery.next_attempt_at = if delivery.status == WebhookDeliveryStatus::Failed {
+            let backoff = self.config.initial_backoff_ms << (delivery.attempts - 1).min(32);
+            Some(delivery.updated_at + Duration::milliseconds(backoff as i64))
+        } else {
+            None
+        };
+        Ok(if self.dynamodb.update_webhook_delivery(&delivery).await? {
+            Some(delivery)
+        } else {
+            None
+        })
+    }
+
+    /// Returns the status of the response. Deliveries to non-public addresses fail like
+    /// unreachable hosts.
+    async fn post(&self, webhook: &Webhook, delivery: &WebhookDelivery) -> Result<u16> {
+        let uri: Uri = webhook.url.parse()?;
+        addresses::check_uri(&uri, self.config.allow_private_networks).await?;
+        let request = Request::post(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, String::from(&delivery.id))
+            .header(
+                SIGNATURE_HEADER,
+                signature(&webhook.secret, delivery.payload.as_bytes())?,
+            )
+            .body(Body::from(delivery.payload.clone()))?;
+        let response = tokio::time::timeout(
+            StdDuration::from_secs(self.config.timeout_secs),
+            self.client.request(request),
+        )
+        .await??;
+        Ok(response.status().as_u16())
+    }
+}
+
+/// Receivers verify the deliveries by computing the HMAC-SHA256 of the body with the secret of
+/// the webhook, formatted as `sha256=<hex digest>`.
+fn signature(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::client::connect::dns::Name;
+use hyper::Uri;
+use tower_service::Service;
+
+/// Whether an address can be reached from the internet, so webhooks can't be used to reach the
+/// services of the private network of the server, such as the metadata of the cloud instance.
+pub fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public_v4(&ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+/// IPv4 address reached through an IPv6 one: IPv4-mapped (`::ffff:a.b.c.d`), IPv4-compatible
+/// (`::a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`) or 6to4 (`2002:aabb:ccdd::`) addresses.
+fn embedded_ipv4(ip: &Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., c, d] = segments;
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(ipv4(c, d)),
+        // The unspecified and loopback addresses are IPv6 ones.
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_unspecified() && !ip.is_loopback() => Some(ipv4(c, d)),
+        [0x2002, a, b, ..] => Some(ipv4(a, b)),
+        _ => None,
+    }
+}
+
+fn ipv4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from(((high as u32) << 16) | low as u32)
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "This network", shared address space (carrier-grade NAT) and reserved ranges.
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: &Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation ranges.
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+/// Resolves the host of a webhook URL, failing unless all its addresses are public. Private
+/// addresses are only allowed with `allow_private_networks`, such as for the local receiver.
+pub async fn resolve(host: &str, allow_private_networks: bool) -> Result<Vec<SocketAddr>, Error> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, 0)],
+        Err(_) => tokio::net::lookup_host((host, 0)).await?.collect(),
+    };
+    if addrs.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Host '{}' has no address", host),
+        ));
+    }
+    if !allow_private_networks && addrs.iter().any(|addr| !is_public(&addr.ip())) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("Host '{}' resolves to a non-public address", host),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Checks the host of a webhook URL before each delivery. Hosts which are IP addresses aren't
+/// resolved by the connector, so they're only checked here.
+pub async fn check_uri(uri: &Uri, allow_private_networks: bool) -> Result<(), Error> {
+    let host = uri
+        .host()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "URL without host"))?;
+    resolve(host, allow_private_networks).await.map(|_| ())
+}
+
+/// Resolver of the webhook client, only returning public addresses. As the connection uses the
+/// addresses it checked, a host can't resolve to a public address when checked and to a private
+/// one when connected.
+#[derive(Clone)]
+pub struct PublicResolver {
+    pub allow_private_networks: bool,
+}
+
+impl Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allow_private_networks = self.allow_private_networks;
+        Box::pin(async move {
+            resolve(name.as_str(), allow_private_networks)
+                .await
+                .map(Vec::into_iter)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+        ] {
+            assert!(!is_public(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:8.8.8.8",
+            "64:ff9b::8.8.8.8",
+            "2002:808:808::1",
+        ] {
+            assert!(is_public(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn checks_literal_hosts() {
+        let uri: Uri = "http://169.254.169.254/latest/meta-data".parse().unwrap();
+        assert!(check_uri(&uri, false).await.is_err());
+        assert!(check_uri(&uri, true).await.is_ok());
+        let uri: Uri = "http://[::1]:8000/webhooks/local".parse().unwrap();
+        assert!(check_uri(&uri, false).await.is_err());
+    }
+}
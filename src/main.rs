@@ -3,9 +3,11 @@ use tracing_log::LogTracer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
+mod auth;
 mod config;
 mod dynamodb;
 mod graphql;
+mod rate_limit;
 mod serving;
 
 #[tokio::main]
@@ -27,6 +29,34 @@ async fn main() {
     let config = config::load().unwrap();
     let db = dynamodb::DynamoTable::load(&config.dynamodb).await.unwrap();
 
+    // Maintenance commands
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        graphql::commands::run(&db, &config.search, &args)
+            .await
+            .unwrap();
+        return;
+    }
+
+    let search = graphql::search::SearchIndex::load(&config.search).unwrap();
+    let authenticator = auth::Authenticator::load(&config.auth).unwrap();
+    let rate_limiter = rate_limit::RateLimiter::in_memory(config.rate_limit).unwrap();
+    let reminder_events = graphql::reminders::ReminderEvents::default();
+
+    // Background tasks
+    graphql::reminders::spawn_scheduler(config.reminders, db.clone(), reminder_events.clone())
+        .unwrap();
+
     // Start webserver
-    serving::run_and_serve(config.serving, db).await;
+    serving::run_and_serve(
+        config.serving,
+        config.graphql,
+        config.webhooks,
+        authenticator,
+        rate_limiter,
+        db,
+        reminder_events,
+        search,
+    )
+    .await;
 }
@@ -0,0 +1,251 @@
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::parser::types::{DocumentOperations, ExecutableDocument, OperationType};
+use async_graphql::{ErrorExtensionValues, Request, Response, ServerError, ServerResult, Value};
+use lru::LruCache;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// Maximum number of tokens of a client, allowing short bursts.
+    pub capacity: u32,
+    /// Number of tokens given back to a client every second.
+    pub refill_per_second: f64,
+    /// Number of tokens consumed by each operation type.
+    pub query_cost: u32,
+    pub mutation_cost: u32,
+    pub subscription_cost: u32,
+    /// Whether the server is behind a proxy setting the `X-Forwarded-For` header. Anonymous
+    /// clients are otherwise identified by the address of the connection, as the header can be
+    /// forged.
+    #[serde(default)]
+    pub trusted_proxy: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Too many requests")]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl RateLimited {
+    /// Whole number of seconds, as expected by the `Retry-After` header.
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil() as u64
+    }
+
+    /// Seconds to wait of the `RATE_LIMITED` error of a response, if any.
+    pub fn from_response(response: &Response) -> Option<u64> {
+        response.errors.iter().find_map(|error| {
+            let extensions = error.extensions.as_ref()?;
+            match (extensions.get("code"), extensions.get("retryAfter")) {
+                (Some(Value::String(code)), Some(Value::Number(retry_after)))
+                    if code == RATE_LIMITED_CODE =>
+                {
+                    retry_after.as_u64()
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+const RATE_LIMITED_CODE: &str = "RATE_LIMITED";
+
+impl From<RateLimited> for ServerError {
+    fn from(err: RateLimited) -> Self {
+        let mut error = ServerError::new(err.to_string(), None);
+        let mut extensions = ErrorExtensionValues::default();
+        extensions.set("code", RATE_LIMITED_CODE);
+        extensions.set("retryAfter", err.retry_after_secs());
+        error.extensions = Some(extensions);
+        error
+    }
+}
+
+/// Storage of the token buckets. The in-process store only works for a single instance, a shared
+/// store is needed as soon as the service is scaled out.
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Consumes `cost` tokens from the bucket of the client, or returns how long it must wait
+    /// before having enough of them.
+    async fn acquire(
+        &self,
+        key: &str,
+        cost: u32,
+        config: &RateLimitConfig,
+    ) -> Result<(), RateLimited>;
+}
+
+/// The least recently used buckets are dropped beyond this, which only resets the limit of clients
+/// idle for long enough to have their bucket full again in practice.
+const MAX_BUCKETS: usize = 10_000;
+
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<LruCache<String, Bucket>>,
+}
+
+impl Default for InMemoryRateLimitStore {
+    fn default() -> Self {
+        InMemoryRateLimitStore {
+            buckets: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_BUCKETS).unwrap())),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, config: &RateLimitConfig) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * config.refill_per_second).min(config.capacity as f64);
+        self.updated_at = now;
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(
+        &self,
+        key: &str,
+        cost: u32,
+        config: &RateLimitConfig,
+    ) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_or_insert_mut(key.to_string(), || Bucket {
+            tokens: config.capacity as f64,
+            updated_at: now,
+        });
+        bucket.refill(now, config);
+        let cost = cost as f64;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(RateLimited {
+                retry_after: Duration::from_secs_f64(
+                    (cost - bucket.tokens) / config.refill_per_second,
+                ),
+            })
+        }
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, store: Box<dyn RateLimitStore>) -> anyhow::Result<Self> {
+        let max_cost = config
+            .query_cost
+            .max(config.mutation_cost)
+            .max(config.subscription_cost);
+        if config.refill_per_second <= 0.0 {
+            anyhow::bail!("Rate limit refill_per_second must be positive");
+        }
+        // Otherwise the operation could never be executed.
+        if max_cost > config.capacity {
+            anyhow::bail!(
+                "Rate limit capacity ({}) is lower than the highest operation cost ({})",
+                config.capacity,
+                max_cost
+            );
+        }
+        Ok(RateLimiter { config, store })
+    }
+
+    pub fn in_memory(config: RateLimitConfig) -> anyhow::Result<Self> {
+        Self::new(config, Box::new(InMemoryRateLimitStore::default()))
+    }
+
+    /// Whether anonymous clients can be identified by the `X-Forwarded-For` header.
+    pub fn trusted_proxy(&self) -> bool {
+        self.config.trusted_proxy
+    }
+
+    /// Consumes the tokens of an operation of type `operation` for the client identified by
+    /// `key`.
+    pub async fn check(&self, key: &str, operation: OperationType) -> Result<(), RateLimited> {
+        let cost = match operation {
+            OperationType::Query => self.config.query_cost,
+            OperationType::Mutation => self.config.mutation_cost,
+            OperationType::Subscription => self.config.subscription_cost,
+        };
+        self.store.acquire(key, cost, &self.config).await
+    }
+}
+
+/// Client of a request, added to the data of the request (or of the websocket session) for the
+/// [RateLimit] extension. Requests without it, such as internal ones, aren't limited.
+pub struct RateLimitKey(pub String);
+
+/// Checks the rate limit of every operation executed by the schema, whatever the transport. It
+/// must be registered after the persisted queries so operations only sent with their hash are
+/// costed by their actual type.
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Arc<RateLimiter>) -> RateLimit {
+        RateLimit { limiter }
+    }
+}
+
+impl ExtensionFactory for RateLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RateLimitExtension {
+            limiter: self.limiter.clone(),
+        })
+    }
+}
+
+struct RateLimitExtension {
+    limiter: Arc<RateLimiter>,
+}
+
+#[async_trait::async_trait]
+impl Extension for RateLimitExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if let Some(RateLimitKey(key)) = ctx.data_opt::<RateLimitKey>() {
+            let operation_name = request.operation_name.clone();
+            // The document is already parsed for the persisted queries, otherwise it's parsed
+            // once for both the limit and the execution. Invalid queries still consume tokens.
+            let operation = request
+                .parsed_query()
+                .map(|document| operation_type(document, operation_name.as_deref()))
+                .unwrap_or(OperationType::Query);
+            self.limiter.check(key, operation).await?;
+        }
+        next.run(ctx, request).await
+    }
+}
+
+/// Type of the executed operation, a `query` if the document doesn't contain it.
+fn operation_type(document: &ExecutableDocument, operation_name: Option<&str>) -> OperationType {
+    let operation = match (&document.operations, operation_name) {
+        (DocumentOperations::Single(operation), _) => Some(operation),
+        (DocumentOperations::Multiple(operations), Some(name)) => operations.get(name),
+        (DocumentOperations::Multiple(operations), None) => operations.values().next(),
+    };
+    operation
+        .map(|operation| operation.node.ty)
+        .unwrap_or(OperationType::Query)
+}
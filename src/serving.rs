@@ -1,11 +1,27 @@
-use actix_web::{web, web::Data, App, HttpResponse, HttpServer};
+use actix_web::http::header::{HeaderValue, AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{guard, web, web::Data, App, Either, HttpRequest, HttpResponse, HttpServer};
+use std::sync::Arc;
+
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use async_graphql::parser::types::OperationType;
+use async_graphql::{
+    Data as GraphQLData, ErrorExtensionValues, Request, Response, ServerError, Value,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use serde::Deserialize;
+use tracing::info;
 use tracing_actix_web::TracingLogger;
 
+use crate::auth::{is_introspection_query, AuthErrors, Authenticator, Principal};
 use crate::dynamodb::DynamoTable;
 use crate::graphql::build_schema;
+use crate::graphql::calendar;
+use crate::graphql::config::GraphQLConfig;
+use crate::graphql::reminders::ReminderEvents;
+use crate::graphql::search::SearchIndex;
+use crate::graphql::webhooks::{WebhookConfig, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+use crate::rate_limit::{RateLimitKey, RateLimited, RateLimiter};
 
 use super::graphql::GraphQLSchema;
 
@@ -14,14 +30,45 @@ pub struct ServingConfig {
     pub port: u16,
 }
 
-pub async fn run_and_serve(config: ServingConfig, db: DynamoTable) {
-    let schema: GraphQLSchema = build_schema(db);
+#[allow(clippy::too_many_arguments)]
+pub async fn run_and_serve(
+    config: ServingConfig,
+    graphql_config: GraphQLConfig,
+    webhook_config: WebhookConfig,
+    authenticator: Authenticator,
+    rate_limiter: RateLimiter,
+    db: DynamoTable,
+    reminder_events: ReminderEvents,
+    search: SearchIndex,
+) {
+    let local_receiver = webhook_config.local_receiver;
+    let rate_limiter = Arc::new(rate_limiter);
+    let schema: GraphQLSchema = build_schema(
+        &graphql_config,
+        db,
+        reminder_events,
+        webhook_config,
+        search,
+        rate_limiter.clone(),
+    )
+    .expect("Unable to build GraphQL schema");
+    let authenticator = Data::new(authenticator);
+    let rate_limiter = Data::from(rate_limiter);
 
     HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .app_data(Data::new(schema.clone()))
+            .app_data(authenticator.clone())
+            .app_data(rate_limiter.clone())
             .configure(configure)
+            .configure(|cfg| {
+                if local_receiver {
+                    cfg.service(
+                        web::resource("/webhooks/local").route(web::post().to(local_webhook)),
+                    );
+                }
+            })
     })
     .bind(("0.0.0.0", config.port))
     .expect("Unable to bind server")
@@ -34,16 +81,312 @@ fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::resource("/")
             .route(web::post().to(index))
+            .route(
+                web::get()
+                    .guard(guard::Header("upgrade", "websocket"))
+                    .to(index_subscription),
+            )
             .route(web::get().to(index_playground)),
     );
+    cfg.service(
+        web::resource("/lists/{id}.ics")
+            .route(web::get().to(calendar_export))
+            .route(web::post().to(calendar_import)),
+    );
+}
+
+async fn index(
+    schema: web::Data<GraphQLSchema>,
+    authenticator: web::Data<Authenticator>,
+    rate_limiter: web::Data<RateLimiter>,
+    http_req: HttpRequest,
+    req: GraphQLRequest,
+) -> Either<GraphQLResponse, HttpResponse> {
+    let mut req = req.into_inner();
+    // Authenticated users are limited individually, anonymous ones by their IP.
+    let client = if let Some(authorization) = http_req.headers().get(AUTHORIZATION) {
+        match authenticate(&authenticator, authorization) {
+            Ok(principal) => {
+                let client = format!("subject#{}", principal.subject);
+                req = req.data(principal);
+                client
+            }
+            Err(err) => return Either::Right(reject_token(&rate_limiter, &http_req, err).await),
+        }
+    } else if authenticator.allow_anonymous_introspection && is_introspection_query(&req.query) {
+        anonymous_client(&rate_limiter, &http_req)
+    } else {
+        return Either::Right(unauthorized(AuthErrors::MissingToken));
+    };
+
+    let response = schema.execute(req.data(RateLimitKey(client))).await;
+    match RateLimited::from_response(&response) {
+        Some(retry_after) => Either::Right(too_many_requests(response, retry_after)),
+        None => Either::Left(response.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarQuery {
+    token: Option<String>,
+}
+
+/// Todos of a list as an iCalendar document, for the calendar apps. Those which can't send a
+/// bearer token subscribe to the feed of the list with its token in the query string instead,
+/// and are rate limited like anonymous clients.
+async fn calendar_export(
+    schema: web::Data<GraphQLSchema>,
+    authenticator: web::Data<Authenticator>,
+    rate_limiter: web::Data<RateLimiter>,
+    http_req: HttpRequest,
+    list: web::Path<String>,
+    query: web::Query<CalendarQuery>,
+) -> HttpResponse {
+    let executed = match &query.token {
+        Some(token) => match calendar::feed_request(&list, token) {
+            Some(req) => execute_anonymous(&schema, &rate_limiter, &http_req, req).await,
+            None => return HttpResponse::NotFound().finish(),
+        },
+        None => match calendar::export_request(&list) {
+            Some(req) => {
+                execute_authenticated(&schema, &authenticator, &rate_limiter, &http_req, req).await
+            }
+            None => return HttpResponse::NotFound().finish(),
+        },
+    };
+    let response = match executed {
+        Ok(response) if response.is_ok() => response,
+        Ok(response) => return calendar_error(response),
+        Err(rejected) => return rejected,
+    };
+    match response.data {
+        Value::Object(mut data) => match data.remove("todoListCalendar") {
+            Some(Value::String(calendar)) => HttpResponse::Ok()
+                .content_type("text/calendar; charset=utf-8")
+                .body(calendar),
+            _ => HttpResponse::InternalServerError().finish(),
+        },
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Creates or updates the todos of a list from an uploaded iCalendar document, responding with
+/// the outcome of the GraphQL mutation.
+async fn calendar_import(
+    schema: web::Data<GraphQLSchema>,
+    authenticator: web::Data<Authenticator>,
+    rate_limiter: web::Data<RateLimiter>,
+    http_req: HttpRequest,
+    list: web::Path<String>,
+    body: String,
+) -> HttpResponse {
+    let req = match calendar::import_request(&list, body) {
+        Some(req) => req,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    match execute_authenticated(&schema, &authenticator, &rate_limiter, &http_req, req).await {
+        Ok(response) if response.is_ok() => HttpResponse::Ok().json(response),
+        Ok(response) => calendar_error(response),
+        Err(rejected) => rejected,
+    }
+}
+
+/// Unlike the GraphQL endpoint, requests of the other endpoints require a token, except for the
+/// calendar feeds.
+async fn execute_authenticated(
+    schema: &GraphQLSchema,
+    authenticator: &Authenticator,
+    rate_limiter: &RateLimiter,
+    http_req: &HttpRequest,
+    req: Request,
+) -> Result<Response, HttpResponse> {
+    let authorization = http_req
+        .headers()
+        .get(AUTHORIZATION)
+        .ok_or_else(|| unauthorized(AuthErrors::MissingToken))?;
+    let principal = match authenticate(authenticator, authorization) {
+        Ok(principal) => principal,
+        Err(err) => return Err(reject_token(rate_limiter, http_req, err).await),
+    };
+    let client = RateLimitKey(format!("subject#{}", principal.subject));
+    let response = schema.execute(req.data(principal).data(client)).await;
+    match RateLimited::from_response(&response) {
+        Some(retry_after) => Err(too_many_requests(response, retry_after)),
+        None => Ok(response),
+    }
+}
+
+/// Requests authorized by a token of their own, such as the calendar feeds, are limited like the
+/// anonymous clients.
+async fn execute_anonymous(
+    schema: &GraphQLSchema,
+    rate_limiter: &RateLimiter,
+    http_req: &HttpRequest,
+    req: Request,
+) -> Result<Response, HttpResponse> {
+    let client = RateLimitKey(anonymous_client(rate_limiter, http_req));
+    let response = schema.execute(req.data(client)).await;
+    match RateLimited::from_response(&response) {
+        Some(retry_after) => Err(too_many_requests(response, retry_after)),
+        None => Ok(response),
+    }
+}
+
+/// Anonymous clients are identified by the address of the connection, or by the one forwarded by
+/// the proxy if it's trusted.
+fn anonymous_client(rate_limiter: &RateLimiter, http_req: &HttpRequest) -> String {
+    let connection_info = http_req.connection_info();
+    let address = if rate_limiter.trusted_proxy() {
+        connection_info.realip_remote_addr().map(str::to_string)
+    } else {
+        http_req.peer_addr().map(|address| address.ip().to_string())
+    };
+    format!("ip#{}", address.as_deref().unwrap_or("unknown"))
+}
+
+/// Invalid tokens are counted against the IP of the client like a query, so they can't be used
+/// to probe the authentication without limit.
+async fn reject_token(
+    rate_limiter: &RateLimiter,
+    http_req: &HttpRequest,
+    err: AuthErrors,
+) -> HttpResponse {
+    let client = anonymous_client(rate_limiter, http_req);
+    match rate_limiter.check(&client, OperationType::Query).await {
+        Ok(()) => unauthorized(err),
+        Err(limited) => {
+            let retry_after = limited.retry_after_secs();
+            too_many_requests(Response::from_errors(vec![limited.into()]), retry_after)
+        }
+    }
+}
+
+fn authenticate(
+    authenticator: &Authenticator,
+    authorization: &HeaderValue,
+) -> Result<Principal, AuthErrors> {
+    authorization
+        .to_str()
+        .map_err(|_| AuthErrors::InvalidToken)
+        .and_then(|authorization| authenticator.authenticate(authorization))
+}
+
+/// Errors of the calendar endpoints are returned as a GraphQL response, with the status matching
+/// the code of the first error.
+fn calendar_error(response: Response) -> HttpResponse {
+    let code = response
+        .errors
+        .first()
+        .and_then(|error| error.extensions.as_ref())
+        .and_then(|extensions| extensions.get("code"));
+    let status = match code {
+        Some(Value::String(code)) if code == "NOT_FOUND" => StatusCode::NOT_FOUND,
+        Some(Value::String(code)) if code == "FORBIDDEN" => StatusCode::FORBIDDEN,
+        Some(Value::String(code)) if code == "INVALID_VALUE" => StatusCode::BAD_REQUEST,
+        Some(Value::String(code)) if code == "UNAUTHENTICATED" => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    HttpResponse::build(status).json(response)
+}
+
+/// Subscriptions are served over websockets. Browsers can't set the headers of a websocket, so
+/// the token can also be sent as `Authorization` in the payload of the connection init message.
+/// Every operation started on the connection is rate limited like a request.
+async fn index_subscription(
+    schema: web::Data<GraphQLSchema>,
+    authenticator: web::Data<Authenticator>,
+    rate_limiter: web::Data<RateLimiter>,
+    http_req: HttpRequest,
+    payload: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+    let header = http_req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .map(str::to_string);
+    let anonymous = anonymous_client(&rate_limiter, &http_req);
+    GraphQLSubscription::new(GraphQLSchema::clone(&schema))
+        .on_connection_init(move |init_payload| async move {
+            let authorization = header
+                .or_else(|| {
+                    init_payload
+                        .get("Authorization")
+                        .and_then(|authorization| authorization.as_str())
+                        .map(str::to_string)
+                })
+                .ok_or(AuthErrors::MissingToken)?;
+            let principal = match authenticator.authenticate(&authorization) {
+                Ok(principal) => principal,
+                Err(err) => {
+                    rate_limiter.check(&anonymous, OperationType::Query).await?;
+                    return Err(err.into());
+                }
+            };
+            let mut data = GraphQLData::default();
+            data.insert(RateLimitKey(format!("subject#{}", principal.subject)));
+            data.insert(principal);
+            Ok(data)
+        })
+        .start(&http_req, payload)
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalWebhookQuery {
+    status: Option<u16>,
+}
+
+/// Stand-in receiver of the webhooks for local tests. It logs the deliveries and responds with
+/// the `status` of the query string, 204 by default, to simulate failures.
+async fn local_webhook(
+    http_req: HttpRequest,
+    query: web::Query<LocalWebhookQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let header = |name: &str| {
+        http_req
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    info!(
+        event = %header(EVENT_HEADER),
+        delivery = %header(DELIVERY_HEADER),
+        signature = %header(SIGNATURE_HEADER),
+        "Webhook received: {}",
+        String::from_utf8_lossy(&body)
+    );
+    let status = query
+        .status
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::NO_CONTENT);
+    HttpResponse::build(status).finish()
+}
+
+/// Rejected requests still return a GraphQL response for the clients.
+fn unauthorized(err: AuthErrors) -> HttpResponse {
+    let mut error = ServerError::new(err.to_string(), None);
+    let mut extensions = ErrorExtensionValues::default();
+    extensions.set("code", "UNAUTHENTICATED");
+    error.extensions = Some(extensions);
+    HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .json(Response::from_errors(vec![error]))
 }
 
-async fn index(schema: web::Data<GraphQLSchema>, req: GraphQLRequest) -> GraphQLResponse {
-    schema.execute(req.into_inner()).await.into()
+/// Rate limited operations are rejected by the schema, the error is only translated to the status
+/// and header expected by HTTP clients.
+fn too_many_requests(response: Response, retry_after: u64) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, retry_after))
+        .json(response)
 }
 
 async fn index_playground() -> HttpResponse {
     HttpResponse::Ok()
         .content_type("text/html; charset=utf-8")
-        .body(playground_source(GraphQLPlaygroundConfig::new("/")))
+        .body(playground_source(
+            GraphQLPlaygroundConfig::new("/").subscription_endpoint("/"),
+        ))
 }
@@ -26,6 +26,36 @@ resource "aws_dynamodb_table" "graphql" {
     type = "S"
   }
 
+  attribute {
+    name = "GSI2-PK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI2-SK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI3-PK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI3-SK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI4-PK"
+    type = "S"
+  }
+
+  attribute {
+    name = "GSI4-SK"
+    type = "S"
+  }
+
   global_secondary_index {
     name               = "GSI1"
     hash_key           = "GSI1-PK"
@@ -35,4 +65,38 @@ resource "aws_dynamodb_table" "graphql" {
     # For the sake of simplicity, we're simply including all attributes in this example.
     projection_type    = "ALL"
   }
+
+  global_secondary_index {
+    name               = "GSI2"
+    hash_key           = "GSI2-PK"
+    range_key          = "GSI2-SK"
+    write_capacity     = 1
+    read_capacity      = 1
+    projection_type    = "ALL"
+  }
+
+  global_secondary_index {
+    name               = "GSI3"
+    hash_key           = "GSI3-PK"
+    range_key          = "GSI3-SK"
+    write_capacity     = 1
+    read_capacity      = 1
+    projection_type    = "ALL"
+  }
+
+  # Sparse index of the pending reminders.
+  global_secondary_index {
+    name               = "GSI4"
+    hash_key           = "GSI4-PK"
+    range_key          = "GSI4-SK"
+    write_capacity     = 1
+    read_capacity      = 1
+    projection_type    = "ALL"
+  }
+
+  # Purges the items of the trash once their retention period is over.
+  ttl {
+    attribute_name = "TTL"
+    enabled        = true
+  }
 }